@@ -0,0 +1,2 @@
+pub const TTL_BUMP_INTERVAL: u32 = 17280; // 17280 ledgers = 1 day
+pub const TTL_EXTEND_TO: u32 = 518400; // 518400 ledgers = 30 days
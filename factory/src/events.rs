@@ -0,0 +1,26 @@
+use soroban_sdk::{symbol_short, Address, BytesN, Env};
+
+pub(crate) fn emit_initialize(e: &Env, admin: &Address, wasm_hash: &BytesN<32>) {
+    let topics = (symbol_short!("init"), admin.clone());
+    e.events().publish(topics, wasm_hash.clone());
+}
+
+pub(crate) fn emit_set_wasm(e: &Env, from: &Address, wasm_hash: &BytesN<32>) {
+    let topics = (symbol_short!("set_wasm"), from.clone());
+    e.events().publish(topics, wasm_hash.clone());
+}
+
+pub(crate) fn emit_deploy(
+    e: &Env,
+    from: &Address,
+    token_a: &Address,
+    token_b: &Address,
+    maturity: u64,
+    address: &Address,
+) {
+    let topics = (symbol_short!("deploy"), from.clone());
+    e.events().publish(
+        topics,
+        (token_a.clone(), token_b.clone(), maturity, address.clone()),
+    );
+}
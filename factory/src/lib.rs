@@ -0,0 +1,252 @@
+#![no_std]
+
+mod constants;
+mod events;
+mod storage;
+mod test;
+mod types;
+
+use events::{emit_deploy, emit_initialize, emit_set_wasm};
+use soroban_sdk::{
+    contract, contractimpl,
+    xdr::{ScErrorCode, ScErrorType, ToXdr},
+    Address, BytesN, Env, InvokeError, Vec,
+};
+use storage::{
+    add_deployment, extend_instance, get_admin, get_deployments, get_maturities, get_wasm_hash,
+    put_admin, put_wasm_hash,
+};
+use swap::InitParams;
+use types::error::Error;
+
+mod swap {
+    soroban_sdk::contractimport!(
+        file = "../target/wasm32-unknown-unknown/release/swap_contract.wasm"
+    );
+}
+
+// Each deployment of a pair and maturity gets its own salt, so its address can be known in advance
+fn get_salt(
+    e: &Env,
+    token_a: &Address,
+    token_b: &Address,
+    maturity: u64,
+    index: u32,
+) -> BytesN<32> {
+    let data = (token_a.clone(), token_b.clone(), maturity, index).to_xdr(e);
+    e.crypto().sha256(&data)
+}
+
+// Passes through the error of a failed call to the swap contract
+fn check_swap_call<T, E>(
+    result: Result<Result<T, E>, Result<soroban_sdk::Error, InvokeError>>,
+) -> Result<(), soroban_sdk::Error> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(Ok(err)) => Err(err),
+        Err(Err(InvokeError::Contract(code))) => Err(soroban_sdk::Error::from_contract_error(code)),
+        Err(Err(InvokeError::Abort)) => Err(soroban_sdk::Error::from_type_and_code(
+            ScErrorType::Context,
+            ScErrorCode::InvalidAction,
+        )),
+    }
+}
+
+fn check_admin(e: &Env, from: &Address) -> Result<(), Error> {
+    match get_admin(e) {
+        None => Err(Error::ContractNotInitialized),
+        Some(admin) if admin != *from => Err(Error::Unauthorized),
+        Some(_) => Ok(()),
+    }
+}
+
+pub trait FactoryTrait {
+    // Initializes the factory.
+    //
+    // # Arguments
+    //
+    // * `admin` - Address of the admin,
+    // * `wasm_hash` - Hash of the installed swap contract WASM.
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn initialize(e: Env, admin: Address, wasm_hash: BytesN<32>) -> Result<(), Error>;
+
+    // Changes the swap contract WASM used by the next deployments (Only for admin).
+    // The contracts already deployed keep their WASM.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller,
+    // * `wasm_hash` - Hash of the installed swap contract WASM.
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn set_wasm(e: Env, from: Address, wasm_hash: BytesN<32>) -> Result<(), Error>;
+
+    // Returns the hash of the swap contract WASM used by the next deployments.
+    //
+    // # Returns
+    //
+    // Hash of the WASM or Error.
+    fn wasm(e: Env) -> Result<BytesN<32>, Error>;
+
+    // Deploys a swap contract, initializes it and sets up the positions of its series 0 in the
+    // same call (Only for admin). The caller becomes the admin of the swap contract.
+    // The contract is registered by its token pair and maturity, the time its series 0 reaches
+    // the Repay stage.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller,
+    // * `params` - Arguments of the initialization of the swap contract,
+    // * `positions_token_a` - Amount of positions of token A,
+    // * `positions_token_b` - Amount of positions of token B,
+    // * `amount_deposit_token_a` - Amount of each position of token A.
+    //
+    // # Returns
+    //
+    // Address of the swap contract or Error, the error of the swap contract if its setup fails.
+    fn deploy(
+        e: Env,
+        from: Address,
+        params: InitParams,
+        positions_token_a: u64,
+        positions_token_b: u64,
+        amount_deposit_token_a: i128,
+    ) -> Result<Address, soroban_sdk::Error>;
+
+    // Returns the address of a deployment of a token pair and maturity, deployed or not.
+    //
+    // # Arguments
+    //
+    // * `token_a` - Address of token A,
+    // * `token_b` - Address of token B,
+    // * `maturity` - Time the series 0 of the contract reaches the Repay stage,
+    // * `index` - Position of the deployment among the ones of the pair and maturity.
+    //
+    // # Returns
+    //
+    // Address of the swap contract.
+    fn address(e: Env, token_a: Address, token_b: Address, maturity: u64, index: u32) -> Address;
+
+    // Returns the maturities with deployments for a token pair, in order of first deployment.
+    //
+    // # Arguments
+    //
+    // * `token_a` - Address of token A,
+    // * `token_b` - Address of token B.
+    //
+    // # Returns
+    //
+    // Maturities of the pair.
+    fn maturities(e: Env, token_a: Address, token_b: Address) -> Vec<u64>;
+
+    // Returns the swap contracts deployed for a token pair and maturity, in deployment order.
+    //
+    // # Arguments
+    //
+    // * `token_a` - Address of token A,
+    // * `token_b` - Address of token B,
+    // * `maturity` - Time the series 0 of the contracts reaches the Repay stage.
+    //
+    // # Returns
+    //
+    // Addresses of the swap contracts.
+    fn deployments(e: Env, token_a: Address, token_b: Address, maturity: u64) -> Vec<Address>;
+}
+
+#[contract]
+struct Factory;
+
+#[contractimpl]
+impl FactoryTrait for Factory {
+    fn initialize(e: Env, admin: Address, wasm_hash: BytesN<32>) -> Result<(), Error> {
+        if get_admin(&e).is_some() {
+            return Err(Error::ContractAlreadyInitialized);
+        }
+        put_admin(&e, &admin);
+        put_wasm_hash(&e, &wasm_hash);
+        extend_instance(&e);
+        emit_initialize(&e, &admin, &wasm_hash);
+        Ok(())
+    }
+
+    fn set_wasm(e: Env, from: Address, wasm_hash: BytesN<32>) -> Result<(), Error> {
+        from.require_auth();
+
+        check_admin(&e, &from)?;
+
+        put_wasm_hash(&e, &wasm_hash);
+        extend_instance(&e);
+        emit_set_wasm(&e, &from, &wasm_hash);
+        Ok(())
+    }
+
+    fn wasm(e: Env) -> Result<BytesN<32>, Error> {
+        if get_admin(&e).is_none() {
+            return Err(Error::ContractNotInitialized);
+        }
+        Ok(get_wasm_hash(&e))
+    }
+
+    fn deploy(
+        e: Env,
+        from: Address,
+        params: InitParams,
+        positions_token_a: u64,
+        positions_token_b: u64,
+        amount_deposit_token_a: i128,
+    ) -> Result<Address, soroban_sdk::Error> {
+        from.require_auth();
+
+        check_admin(&e, &from)?;
+
+        let maturity = e.ledger().timestamp() + params.risk_params.time_to_exec + params.duration;
+        let index = get_deployments(&e, &params.token_a, &params.token_b, maturity).len();
+        let salt = get_salt(&e, &params.token_a, &params.token_b, maturity, index);
+        let address = e
+            .deployer()
+            .with_current_contract(salt)
+            .deploy(get_wasm_hash(&e));
+
+        // An error reverts the whole call with the error of the swap contract, the contract is
+        // never left deployed without its setup
+        let swap_client = swap::Client::new(&e, &address);
+        check_swap_call(swap_client.try_initialize(&from, &params))?;
+        check_swap_call(swap_client.try_init_pos(
+            &0,
+            &from,
+            &positions_token_a,
+            &positions_token_b,
+            &amount_deposit_token_a,
+        ))?;
+
+        add_deployment(&e, &params.token_a, &params.token_b, maturity, &address);
+        extend_instance(&e);
+        emit_deploy(
+            &e,
+            &from,
+            &params.token_a,
+            &params.token_b,
+            maturity,
+            &address,
+        );
+        Ok(address)
+    }
+
+    fn address(e: Env, token_a: Address, token_b: Address, maturity: u64, index: u32) -> Address {
+        let salt = get_salt(&e, &token_a, &token_b, maturity, index);
+        e.deployer().with_current_contract(salt).deployed_address()
+    }
+
+    fn maturities(e: Env, token_a: Address, token_b: Address) -> Vec<u64> {
+        get_maturities(&e, &token_a, &token_b)
+    }
+
+    fn deployments(e: Env, token_a: Address, token_b: Address, maturity: u64) -> Vec<Address> {
+        get_deployments(&e, &token_a, &token_b, maturity)
+    }
+}
@@ -0,0 +1,77 @@
+use soroban_sdk::{vec, Address, BytesN, Env, Vec};
+
+use crate::constants::{TTL_BUMP_INTERVAL, TTL_EXTEND_TO};
+use crate::types::storage::DataKey;
+
+pub(crate) fn get_admin(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&DataKey::Admin)
+}
+
+pub(crate) fn put_admin(e: &Env, admin: &Address) {
+    e.storage().instance().set(&DataKey::Admin, admin);
+}
+
+pub(crate) fn get_wasm_hash(e: &Env) -> BytesN<32> {
+    e.storage().instance().get(&DataKey::WasmHash).unwrap()
+}
+
+pub(crate) fn put_wasm_hash(e: &Env, wasm_hash: &BytesN<32>) {
+    e.storage().instance().set(&DataKey::WasmHash, wasm_hash);
+}
+
+pub(crate) fn get_maturities(e: &Env, token_a: &Address, token_b: &Address) -> Vec<u64> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Maturities(token_a.clone(), token_b.clone()))
+        .unwrap_or(vec![e])
+}
+
+pub(crate) fn get_deployments(
+    e: &Env,
+    token_a: &Address,
+    token_b: &Address,
+    maturity: u64,
+) -> Vec<Address> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Deployments(
+            token_a.clone(),
+            token_b.clone(),
+            maturity,
+        ))
+        .unwrap_or(vec![e])
+}
+
+// Registers a deployment, the first one of a maturity also adds it to the maturities of the pair
+pub(crate) fn add_deployment(
+    e: &Env,
+    token_a: &Address,
+    token_b: &Address,
+    maturity: u64,
+    address: &Address,
+) {
+    let mut deployments = get_deployments(e, token_a, token_b, maturity);
+    if deployments.is_empty() {
+        let mut maturities = get_maturities(e, token_a, token_b);
+        maturities.push_back(maturity);
+        let key = DataKey::Maturities(token_a.clone(), token_b.clone());
+        e.storage().persistent().set(&key, &maturities);
+        extend_persistent(e, &key);
+    }
+    deployments.push_back(address.clone());
+    let key = DataKey::Deployments(token_a.clone(), token_b.clone(), maturity);
+    e.storage().persistent().set(&key, &deployments);
+    extend_persistent(e, &key);
+}
+
+pub(crate) fn extend_instance(e: &Env) {
+    e.storage()
+        .instance()
+        .extend_ttl(TTL_EXTEND_TO - TTL_BUMP_INTERVAL, TTL_EXTEND_TO);
+}
+
+fn extend_persistent(e: &Env, key: &DataKey) {
+    e.storage()
+        .persistent()
+        .extend_ttl(key, TTL_EXTEND_TO - TTL_BUMP_INTERVAL, TTL_EXTEND_TO);
+}
@@ -0,0 +1,253 @@
+#![cfg(test)]
+extern crate std;
+
+use crate::swap::{self, AllocationPolicy, InitParams, RiskParams};
+use crate::types::error::Error;
+use crate::FactoryClient;
+
+use soroban_sdk::testutils::{Address as _, Events, Ledger};
+use soroban_sdk::{symbol_short, vec, Address, BytesN, Env, IntoVal, Symbol};
+
+mod oracle_mock {
+    soroban_sdk::contractimport!(
+        file = "../oracle_mock/target/wasm32-unknown-unknown/release/oracle_mock_contract.wasm"
+    );
+}
+
+// Symbol of the Stellar Asset Contracts registered for tests
+const TOKEN_NAME: Symbol = symbol_short!("aaa");
+const SCALE: i128 = 100_000_000_000_000;
+const TIME_TO_MATURE: u64 = 604800; // 604800sg = 1 week
+const TIME_TO_EXEC: u64 = 86400; // 86400sg = 12 hours
+const TIME_TO_REPAY: u64 = 172800; // 172800sg = 48 hours
+const TIMESTAMP: u64 = 12345;
+
+struct FactoryTest<'a> {
+    e: Env,
+    admin: Address,
+    token_a: Address,
+    token_b: Address,
+    oracle: Address,
+    wasm_hash: BytesN<32>,
+    contract: FactoryClient<'a>,
+}
+
+impl<'a> FactoryTest<'a> {
+    fn setup() -> Self {
+        let e = Env::default();
+        e.mock_all_auths();
+
+        e.ledger().with_mut(|li| {
+            li.timestamp = TIMESTAMP;
+        });
+
+        let admin = Address::generate(&e);
+        let token_a = e.register_stellar_asset_contract(admin.clone());
+        let token_b = e.register_stellar_asset_contract(admin.clone());
+
+        let oracle = e.register_contract_wasm(None, oracle_mock::WASM);
+        oracle_mock::Client::new(&e, &oracle).set_spot_rate(&SCALE);
+
+        let wasm_hash = e.deployer().upload_contract_wasm(swap::WASM);
+        let contract = FactoryClient::new(&e, &e.register_contract(None, crate::Factory {}));
+
+        FactoryTest {
+            e,
+            admin,
+            token_a,
+            token_b,
+            oracle,
+            wasm_hash,
+            contract,
+        }
+    }
+
+    fn swap_params(&self, duration: u64) -> InitParams {
+        InitParams {
+            token_a: self.token_a.clone(),
+            token_b: self.token_b.clone(),
+            name_token_a: TOKEN_NAME,
+            name_token_b: TOKEN_NAME,
+            forward_rate: SCALE,
+            duration,
+            oracle: self.oracle.clone(),
+            risk_params: RiskParams {
+                collateral_buffer: 20,
+                collateral_threshold: 125,
+                liquidation_reward: 1,
+                time_to_exec: TIME_TO_EXEC,
+                time_to_repay: TIME_TO_REPAY,
+                partial_liquidation: false,
+            },
+            allocation_policy: AllocationPolicy::Fifo,
+        }
+    }
+}
+
+#[test]
+fn test_initialize() {
+    let FactoryTest {
+        e,
+        admin,
+        wasm_hash,
+        contract,
+        ..
+    } = FactoryTest::setup();
+    assert_eq!(contract.try_wasm(), Err(Ok(Error::ContractNotInitialized)));
+
+    contract.initialize(&admin, &wasm_hash);
+    assert_eq!(contract.wasm(), wasm_hash);
+
+    let result = contract.try_initialize(&Address::generate(&e), &wasm_hash);
+    assert_eq!(result, Err(Ok(Error::ContractAlreadyInitialized)));
+}
+
+#[test]
+fn test_set_wasm() {
+    let FactoryTest {
+        e,
+        admin,
+        wasm_hash,
+        contract,
+        ..
+    } = FactoryTest::setup();
+    let new_wasm_hash = BytesN::from_array(&e, &[1; 32]);
+    let result = contract.try_set_wasm(&admin, &new_wasm_hash);
+    assert_eq!(result, Err(Ok(Error::ContractNotInitialized)));
+
+    contract.initialize(&admin, &wasm_hash);
+    let result = contract.try_set_wasm(&Address::generate(&e), &new_wasm_hash);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    contract.set_wasm(&admin, &new_wasm_hash);
+    assert_eq!(contract.wasm(), new_wasm_hash);
+}
+
+#[test]
+fn test_deploy() {
+    let test = FactoryTest::setup();
+    let FactoryTest {
+        e,
+        admin,
+        token_a,
+        token_b,
+        wasm_hash,
+        contract,
+        ..
+    } = &test;
+    contract.initialize(admin, wasm_hash);
+    let maturity = TIMESTAMP + TIME_TO_EXEC + TIME_TO_MATURE;
+    // Each deployment instantiates the swap contract, it starts with a fresh budget like a
+    // transaction of its own
+    let expected_address = contract.address(token_a, token_b, &maturity, &0);
+
+    e.budget().reset_default();
+
+    let address = contract.deploy(admin, &test.swap_params(TIME_TO_MATURE), &2, &2, &100);
+    assert_eq!(address, expected_address);
+    let last_event = e.events().all().last().unwrap();
+    assert_eq!(
+        vec![e, last_event],
+        vec![
+            e,
+            (
+                contract.address.clone(),
+                (symbol_short!("deploy"), admin.clone()).into_val(e),
+                (token_a.clone(), token_b.clone(), maturity, address.clone()).into_val(e),
+            )
+        ]
+    );
+
+    // The deployed contract has its positions set up and the factory admin as its admin
+    e.budget().reset_default();
+    let swap_client = swap::Client::new(e, &address);
+    assert_eq!(swap_client.limits(&0).0.cap, 200);
+    e.budget().reset_default();
+    let result = swap_client.try_init_pos(&0, &Address::generate(e), &1, &1, &100);
+    assert_eq!(result, Err(Ok(swap::Error::Unauthorized.into())));
+
+    // A second contract of the same pair and maturity gets the next salt
+    e.budget().reset_default();
+    let second_address = contract.deploy(admin, &test.swap_params(TIME_TO_MATURE), &2, &2, &100);
+    assert_eq!(
+        second_address,
+        contract.address(token_a, token_b, &maturity, &1)
+    );
+    assert_eq!(
+        contract.deployments(token_a, token_b, &maturity),
+        vec![e, address, second_address]
+    );
+
+    e.budget().reset_default();
+
+    let third_address = contract.deploy(admin, &test.swap_params(2 * TIME_TO_MATURE), &2, &2, &100);
+    let later_maturity = maturity + TIME_TO_MATURE;
+    assert_eq!(
+        contract.maturities(token_a, token_b),
+        vec![e, maturity, later_maturity]
+    );
+    assert_eq!(
+        contract.deployments(token_a, token_b, &later_maturity),
+        vec![e, third_address]
+    );
+    assert_eq!(contract.maturities(token_b, token_a), vec![e]);
+}
+
+#[test]
+fn test_deploy_unauthorized() {
+    let test = FactoryTest::setup();
+    let FactoryTest {
+        e,
+        admin,
+        wasm_hash,
+        contract,
+        ..
+    } = &test;
+    e.budget().reset_default();
+    let result = contract.try_deploy(admin, &test.swap_params(TIME_TO_MATURE), &2, &2, &100);
+    assert_eq!(result, Err(Ok(Error::ContractNotInitialized.into())));
+
+    contract.initialize(admin, wasm_hash);
+    e.budget().reset_default();
+    let result = contract.try_deploy(
+        &Address::generate(e),
+        &test.swap_params(TIME_TO_MATURE),
+        &2,
+        &2,
+        &100,
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized.into())));
+}
+
+#[test]
+fn test_deploy_reverted() {
+    let test = FactoryTest::setup();
+    let FactoryTest {
+        e,
+        admin,
+        token_a,
+        token_b,
+        wasm_hash,
+        contract,
+        ..
+    } = &test;
+    contract.initialize(admin, wasm_hash);
+    let maturity = TIMESTAMP + TIME_TO_EXEC + TIME_TO_MATURE;
+
+    let mut params = test.swap_params(TIME_TO_MATURE);
+    params.forward_rate = 0;
+    e.budget().reset_default();
+    let result = contract.try_deploy(admin, &params, &2, &2, &100);
+    assert_eq!(result, Err(Ok(swap::Error::InvalidForwardRate.into())));
+
+    e.budget().reset_default();
+
+    let result = contract.try_deploy(admin, &test.swap_params(TIME_TO_MATURE), &2, &2, &0);
+    assert_eq!(result, Err(Ok(swap::Error::InvalidPositionData.into())));
+
+    // Nothing is registered and the salt is still free
+    assert_eq!(contract.maturities(token_a, token_b), vec![e]);
+    e.budget().reset_default();
+    let address = contract.deploy(admin, &test.swap_params(TIME_TO_MATURE), &2, &2, &100);
+    assert_eq!(address, contract.address(token_a, token_b, &maturity, &0));
+}
@@ -0,0 +1,10 @@
+use soroban_sdk::contracterror;
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    ContractAlreadyInitialized = 1,
+    ContractNotInitialized = 2,
+    Unauthorized = 3,
+}
@@ -0,0 +1,12 @@
+use soroban_sdk::{contracttype, Address};
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Admin,
+    WasmHash,
+    // Maturities with deployments for a token pair
+    Maturities(Address, Address),
+    // Swap contracts deployed for a token pair and maturity, in deployment order
+    Deployments(Address, Address, u64),
+}
@@ -2,6 +2,8 @@
 use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Env, Symbol};
 
 const RATE: Symbol = symbol_short!("RATE");
+const TIMESTAMP: Symbol = symbol_short!("TIMESTAMP");
+const DECIMALS: Symbol = symbol_short!("DECIMALS");
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -20,6 +22,12 @@ pub enum Asset {
 pub trait OracleMockTrait {
     fn set_spot_rate(e: Env, spot_rate: i128) -> PriceData;
 
+    fn set_timestamp(e: Env, timestamp: u64);
+
+    fn set_decimals(e: Env, decimals: u32);
+
+    fn decimals(e: Env) -> u32;
+
     fn x_last_price(e: Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData>;
 }
 
@@ -30,17 +38,34 @@ struct OracleMock;
 impl OracleMockTrait for OracleMock {
     fn set_spot_rate(e: Env, spot_rate: i128) -> PriceData {
         e.storage().instance().set(&RATE, &spot_rate);
+        e.storage().instance().remove(&TIMESTAMP);
         PriceData {
             price: spot_rate,
-            timestamp: 0,
+            timestamp: e.ledger().timestamp(),
         }
     }
 
+    // Fixes the timestamp of the price, otherwise the price is always reported as fresh
+    fn set_timestamp(e: Env, timestamp: u64) {
+        e.storage().instance().set(&TIMESTAMP, &timestamp);
+    }
+
+    // Decimals of the reported prices, 14 unless changed
+    fn set_decimals(e: Env, decimals: u32) {
+        e.storage().instance().set(&DECIMALS, &decimals);
+    }
+
+    fn decimals(e: Env) -> u32 {
+        e.storage().instance().get(&DECIMALS).unwrap_or(14)
+    }
+
     fn x_last_price(e: Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData> {
-        let price = e.storage().instance().get(&RATE).unwrap_or(0);
-        Some(PriceData {
-            price,
-            timestamp: 0,
-        })
+        let price = e.storage().instance().get(&RATE)?;
+        let timestamp = e
+            .storage()
+            .instance()
+            .get(&TIMESTAMP)
+            .unwrap_or(e.ledger().timestamp());
+        Some(PriceData { price, timestamp })
     }
 }
@@ -1,10 +1,25 @@
 pub const SCALE: i128 = 100_000_000_000_000;
-pub const ORACLE_ADDRESS: &str = "CBKZFI26PDCZUJ5HYYKVB5BWCNYUSNA5LVL4R2JTRVSOB4XEP7Y34OPN";
+pub const SCALE_DECIMALS: u32 = 14;
 pub const ORACLE_FUNCTION: &str = "x_last_price";
+pub const MAX_PRICE_AGE: u64 = 3600; // 3600sg = 1 hour
+pub const TWAP_WINDOW: u64 = 0; // 0 uses the last price
+pub const OBSERVATIONS_CAPACITY: u32 = 24;
+pub const LEDGER_TIME: u64 = 5; // 5sg between ledgers on average
+pub const CLAIM_PERIOD: u64 = 2592000; // 2592000sg = 30 days to withdraw and reclaim after the max time
+pub const TTL_BUMP_INTERVAL: u32 = 17280; // 17280 ledgers = 1 day
+pub const UPGRADE_TIMELOCK: u64 = 259200; // 259200sg = 3 days between proposing and applying an upgrade
+pub const ORACLE_TIMELOCK: u64 = 259200; // 259200sg = 3 days between proposing and setting locked oracles
+pub const SCHEMA_VERSION: u32 = 1; // Layout of the stored data, increased by the upgrades that migrate it
+
+#[cfg(test)]
+pub const TIME_TO_MATURE: u64 = 604800; // 604800sg = 1 week
+#[cfg(test)]
 pub const COLLATERAL_BUFFER: i128 = 20;
+#[cfg(test)]
 pub const COLLATERAL_THRESHOLD: i128 = 125;
-pub const TIME_TO_EXEC: u64 = 86400;  // 86400sg = 12 hours
-pub const TIME_TO_REPAY: u64 = 172800; // 172800sg = 48 hours
-
 #[cfg(test)]
-pub const TIME_TO_MATURE: u64 = 604800; // 604800sg = 1 week
\ No newline at end of file
+pub const LIQUIDATION_REWARD: i128 = 1;
+#[cfg(test)]
+pub const TIME_TO_EXEC: u64 = 86400; // 86400sg = 12 hours
+#[cfg(test)]
+pub const TIME_TO_REPAY: u64 = 172800; // 172800sg = 48 hours
@@ -0,0 +1,318 @@
+use soroban_sdk::{symbol_short, Address, BytesN, Env, Symbol, Vec};
+
+use crate::token_data::{get_token_a_address, get_token_b_address};
+use crate::types::{
+    auction_params::AuctionParams, oracle_source::OracleSource,
+    partial_liquidation::PartialLiquidation, pending_oracles::PendingOracles,
+    pending_upgrade::PendingUpgrade, position_data::PositionData,
+};
+
+pub(crate) fn emit_initialize(
+    e: &Env,
+    admin: &Address,
+    forward_rate: i128,
+    duration: u64,
+    spot_rate: i128,
+) {
+    let topics = (symbol_short!("init"), admin.clone());
+    e.events().publish(
+        topics,
+        (
+            get_token_a_address(e),
+            get_token_b_address(e),
+            forward_rate,
+            duration,
+            spot_rate,
+        ),
+    );
+}
+
+pub(crate) fn emit_add_series(
+    e: &Env,
+    from: &Address,
+    series: u32,
+    forward_rate: i128,
+    duration: u64,
+    spot_rate: i128,
+) {
+    let topics = (Symbol::new(e, "add_series"), from.clone());
+    e.events()
+        .publish(topics, (series, forward_rate, duration, spot_rate));
+}
+
+pub(crate) fn emit_init_pos(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    positions_token_a: u64,
+    positions_token_b: u64,
+    amount_deposit_token_a: i128,
+    amount_deposit_token_b: i128,
+) {
+    let topics = (symbol_short!("init_pos"), series, from.clone());
+    e.events().publish(
+        topics,
+        (
+            positions_token_a,
+            positions_token_b,
+            amount_deposit_token_a,
+            amount_deposit_token_b,
+        ),
+    );
+}
+
+pub(crate) fn emit_cancel_deposit(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    collateral: i128,
+) {
+    let topics = (symbol_short!("cancel"), series, from.clone());
+    e.events()
+        .publish(topics, (token.clone(), amount, collateral));
+}
+
+pub(crate) fn emit_init_limits(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    position_a: &PositionData,
+    position_b: &PositionData,
+) {
+    let topics = (Symbol::new(e, "init_limits"), series, from.clone());
+    e.events()
+        .publish(topics, (position_a.clone(), position_b.clone()));
+}
+
+pub(crate) fn emit_increase_capacity(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    cap: i128,
+) {
+    let topics = (symbol_short!("inc_cap"), series, from.clone());
+    e.events().publish(topics, (token.clone(), amount, cap));
+}
+
+pub(crate) fn emit_deposit(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    collateral: i128,
+    total_deposit: (i128, i128),
+) {
+    let topics = (symbol_short!("deposit"), series, from.clone());
+    e.events().publish(
+        topics,
+        (
+            token.clone(),
+            amount,
+            collateral,
+            total_deposit.0,
+            total_deposit.1,
+        ),
+    );
+}
+
+pub(crate) fn emit_near_leg(e: &Env, series: u32, spot_rate: i128, timestamp: u64) {
+    let topics = (symbol_short!("near_leg"), series);
+    e.events().publish(topics, (spot_rate, timestamp));
+}
+
+pub(crate) fn emit_swap(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    total: i128,
+) {
+    let topics = (symbol_short!("swap"), series, from.clone());
+    e.events().publish(topics, (token.clone(), amount, total));
+}
+
+pub(crate) fn emit_repay(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    total_returned: i128,
+    total_to_repay: i128,
+) {
+    let topics = (symbol_short!("repay"), series, from.clone());
+    e.events().publish(
+        topics,
+        (token.clone(), amount, total_returned, total_to_repay),
+    );
+}
+
+// `used_liq_collateral` is the part of the counter token amount taken from liquidated collateral,
+// the rest of it was taken from the returned amount.
+pub(crate) fn emit_withdraw(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    amount_a: i128,
+    amount_b: i128,
+    used_liq_collateral: i128,
+    total_withdrawn: i128,
+) {
+    let topics = (symbol_short!("withdraw"), series, from.clone());
+    e.events().publish(
+        topics,
+        (amount_a, amount_b, used_liq_collateral, total_withdrawn),
+    );
+}
+
+pub(crate) fn emit_reclaim(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    total: i128,
+) {
+    let topics = (symbol_short!("reclaim"), series, from.clone());
+    e.events().publish(topics, (token.clone(), amount, total));
+}
+
+pub(crate) fn emit_reclaim_col(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    total: i128,
+) {
+    let topics = (Symbol::new(e, "reclaim_col"), series, from.clone());
+    e.events().publish(topics, (token.clone(), amount, total));
+}
+
+pub(crate) fn emit_liquidate(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    from: &Address,
+    token: &Address,
+    reward_amount: i128,
+    liquidated_collateral: i128,
+) {
+    let topics = (symbol_short!("liquidate"), series, to.clone());
+    e.events().publish(
+        topics,
+        (
+            from.clone(),
+            token.clone(),
+            reward_amount,
+            liquidated_collateral,
+        ),
+    );
+}
+
+// `amount` is the swapped amount closed by the partial liquidation.
+pub(crate) fn emit_partial_liquidate(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    from: &Address,
+    token: &Address,
+    liquidation: &PartialLiquidation,
+) {
+    let topics = (symbol_short!("liq_part"), series, to.clone());
+    e.events().publish(
+        topics,
+        (
+            from.clone(),
+            token.clone(),
+            liquidation.reward_amount,
+            liquidation.liquidated_collateral,
+            liquidation.amount,
+        ),
+    );
+}
+
+pub(crate) fn emit_buy_collateral(
+    e: &Env,
+    series: u32,
+    from: &Address,
+    token: &Address,
+    amount: i128,
+    cost: i128,
+) {
+    let topics = (symbol_short!("buy_col"), series, from.clone());
+    e.events().publish(topics, (token.clone(), amount, cost));
+}
+
+pub(crate) fn emit_set_spot(e: &Env, series: u32, from: &Address, rate: i128) {
+    let topics = (symbol_short!("set_spot"), series, from.clone());
+    e.events().publish(topics, rate);
+}
+
+pub(crate) fn emit_set_oracles(e: &Env, from: &Address, sources: &Vec<OracleSource>, quorum: u32) {
+    let topics = (Symbol::new(e, "set_oracles"), from.clone());
+    e.events().publish(topics, (sources.clone(), quorum));
+}
+
+pub(crate) fn emit_propose_oracles(e: &Env, from: &Address, pending_oracles: &PendingOracles) {
+    let topics = (Symbol::new(e, "propose_oracles"), from.clone());
+    e.events().publish(topics, pending_oracles.clone());
+}
+
+pub(crate) fn emit_set_max_age(e: &Env, from: &Address, max_age: u64) {
+    let topics = (Symbol::new(e, "set_max_age"), from.clone());
+    e.events().publish(topics, max_age);
+}
+
+pub(crate) fn emit_set_twap_window(e: &Env, from: &Address, window: u64) {
+    let topics = (Symbol::new(e, "set_twap_window"), from.clone());
+    e.events().publish(topics, window);
+}
+
+pub(crate) fn emit_set_auction(e: &Env, from: &Address, auction_params: &AuctionParams) {
+    let topics = (Symbol::new(e, "set_auction"), from.clone());
+    e.events().publish(topics, auction_params.clone());
+}
+
+pub(crate) fn emit_propose_upgrade(e: &Env, from: &Address, pending_upgrade: &PendingUpgrade) {
+    let topics = (Symbol::new(e, "propose_upgrade"), from.clone());
+    e.events().publish(topics, pending_upgrade.clone());
+}
+
+pub(crate) fn emit_cancel_upgrade(e: &Env, from: &Address, wasm_hash: &BytesN<32>) {
+    let topics = (Symbol::new(e, "cancel_upgrade"), from.clone());
+    e.events().publish(topics, wasm_hash.clone());
+}
+
+pub(crate) fn emit_upgrade(e: &Env, from: &Address, wasm_hash: &BytesN<32>) {
+    let topics = (symbol_short!("upgrade"), from.clone());
+    e.events().publish(topics, wasm_hash.clone());
+}
+
+pub(crate) fn emit_migrate(e: &Env, from: &Address, from_version: u32, to_version: u32) {
+    let topics = (symbol_short!("migrate"), from.clone());
+    e.events().publish(topics, (from_version, to_version));
+}
+
+pub(crate) fn emit_sweep_dust(e: &Env, from: &Address, token: &Address, amount: i128) {
+    let topics = (Symbol::new(e, "sweep_dust"), from.clone());
+    e.events().publish(topics, (token.clone(), amount));
+}
+
+pub(crate) fn emit_transfer_admin(
+    e: &Env,
+    from: &Address,
+    to: &Address,
+    token: &Address,
+    amount: i128,
+) {
+    let topics = (Symbol::new(e, "transfer_admin"), from.clone());
+    e.events()
+        .publish(topics, (to.clone(), token.clone(), amount));
+}
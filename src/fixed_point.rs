@@ -0,0 +1,161 @@
+use crate::constants::SCALE;
+use crate::types::error::Error;
+
+// Rounding applied to the result of a division.
+// Amounts owed to the contract round up and amounts paid by it round down,
+// the difference stays in the contract as dust swept by the admin
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Rounding {
+    // Towards negative infinity
+    Down,
+    // Towards positive infinity
+    Up,
+    // To the nearest value, ties to the even one
+    HalfEven,
+}
+
+// Returns x * y / denominator rounded as requested.
+// The product is kept in 256 bits so it never overflows, an error is returned
+// if the denominator is zero or the result doesn't fit in an i128.
+pub(crate) fn mul_div(
+    x: i128,
+    y: i128,
+    denominator: i128,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    if denominator == 0 {
+        return Err(Error::DivisionByZero);
+    }
+
+    let is_negative = (x < 0) ^ (y < 0) ^ (denominator < 0);
+    let denominator = denominator.unsigned_abs();
+    let (quotient, remainder) =
+        div_wide(mul_wide(x.unsigned_abs(), y.unsigned_abs()), denominator)?;
+
+    let round_away = remainder > 0
+        && match rounding {
+            Rounding::Down => is_negative,
+            Rounding::Up => !is_negative,
+            Rounding::HalfEven => {
+                let half = denominator - remainder;
+                remainder > half || (remainder == half && quotient % 2 == 1)
+            }
+        };
+    let quotient = match round_away {
+        true => quotient.checked_add(1).ok_or(Error::ArithmeticOverflow)?,
+        false => quotient,
+    };
+
+    match is_negative {
+        true if quotient <= i128::MIN.unsigned_abs() => Ok((quotient as i128).wrapping_neg()),
+        false if quotient <= i128::MAX as u128 => Ok(quotient as i128),
+        _ => Err(Error::ArithmeticOverflow),
+    }
+}
+
+// Same as mul_div but also moves the result from `from` decimals to `to` decimals
+pub(crate) fn mul_div_scaled(
+    x: i128,
+    y: i128,
+    denominator: i128,
+    from: u32,
+    to: u32,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    match to >= from {
+        true => mul_div(x, checked_mul(y, pow10(to - from)?)?, denominator, rounding),
+        false => mul_div(x, y, checked_mul(denominator, pow10(from - to)?)?, rounding),
+    }
+}
+
+// Percentage of an amount, the rate is expressed in hundredths
+pub(crate) fn calculate_percentage(
+    amount: i128,
+    rate: i128,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    mul_div(amount, rate, 100, rounding)
+}
+
+// Converts an amount of token A to token B, the rate is the amount of B per A scaled by SCALE
+pub(crate) fn convert_a_to_b(
+    amount: i128,
+    rate: i128,
+    decimals_a: u32,
+    decimals_b: u32,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    mul_div_scaled(amount, rate, SCALE, decimals_a, decimals_b, rounding)
+}
+
+// Converts an amount of token B to token A, the rate is the amount of B per A scaled by SCALE
+pub(crate) fn convert_b_to_a(
+    amount: i128,
+    rate: i128,
+    decimals_a: u32,
+    decimals_b: u32,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    mul_div_scaled(amount, SCALE, rate, decimals_b, decimals_a, rounding)
+}
+
+pub(crate) fn pow10(exponent: u32) -> Result<i128, Error> {
+    10_i128
+        .checked_pow(exponent)
+        .ok_or(Error::ArithmeticOverflow)
+}
+
+pub(crate) fn checked_mul(x: i128, y: i128) -> Result<i128, Error> {
+    x.checked_mul(y).ok_or(Error::ArithmeticOverflow)
+}
+
+pub(crate) fn checked_add(x: i128, y: i128) -> Result<i128, Error> {
+    x.checked_add(y).ok_or(Error::ArithmeticOverflow)
+}
+
+pub(crate) fn checked_sub(x: i128, y: i128) -> Result<i128, Error> {
+    x.checked_sub(y).ok_or(Error::ArithmeticOverflow)
+}
+
+// Full product of two u128 as (high, low) halves
+fn mul_wide(x: u128, y: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (x_high, x_low) = (x >> 64, x & MASK);
+    let (y_high, y_low) = (y >> 64, y & MASK);
+
+    let low_low = x_low * y_low;
+    let high_low = x_high * y_low;
+    let low_high = x_low * y_high;
+    let high_high = x_high * y_high;
+
+    let cross = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+    let low = (cross << 64) | (low_low & MASK);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (cross >> 64);
+    (high, low)
+}
+
+// Divides a 256 bit value by a u128, returns the quotient and the remainder.
+// Uses long division only when the value doesn't fit in 128 bits.
+fn div_wide((high, low): (u128, u128), denominator: u128) -> Result<(u128, u128), Error> {
+    if high == 0 {
+        return Ok((low / denominator, low % denominator));
+    }
+    if high >= denominator {
+        return Err(Error::ArithmeticOverflow);
+    }
+
+    let mut quotient: u128 = 0;
+    let mut remainder = high;
+    for bit in (0..128).rev() {
+        // The remainder is below the denominator, if shifting it overflows
+        // the value is above the denominator and the wrapped subtraction is exact
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Ok((quotient, remainder))
+}
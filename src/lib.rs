@@ -1,45 +1,82 @@
 #![no_std]
 
 mod constants;
+mod events;
+mod fixed_point;
+mod observation;
 mod oracle;
 mod position;
 mod position_data;
 mod storage;
 mod test;
 mod token_data;
+mod ttl;
 mod types;
 mod user;
 
 use core::cmp::{max, min};
 
-use constants::{COLLATERAL_BUFFER, COLLATERAL_THRESHOLD, SCALE, TIME_TO_EXEC, TIME_TO_REPAY};
-use oracle::get_oracle_spot_price;
-use position::{create_position, get_used_positions_a, get_used_positions_b, set_position_valid};
+use constants::{
+    CLAIM_PERIOD, MAX_PRICE_AGE, ORACLE_FUNCTION, ORACLE_TIMELOCK, SCALE, SCHEMA_VERSION,
+    TWAP_WINDOW, UPGRADE_TIMELOCK,
+};
+use events::{
+    emit_add_series, emit_buy_collateral, emit_cancel_deposit, emit_cancel_upgrade, emit_deposit,
+    emit_increase_capacity, emit_init_limits, emit_init_pos, emit_initialize, emit_liquidate,
+    emit_migrate, emit_near_leg, emit_partial_liquidate, emit_propose_oracles,
+    emit_propose_upgrade, emit_reclaim, emit_reclaim_col, emit_repay, emit_set_auction,
+    emit_set_max_age, emit_set_oracles, emit_set_spot, emit_set_twap_window, emit_swap,
+    emit_sweep_dust, emit_transfer_admin, emit_upgrade, emit_withdraw,
+};
+use fixed_point::{
+    calculate_percentage, checked_add, checked_mul, checked_sub, convert_a_to_b, convert_b_to_a,
+    mul_div, mul_div_scaled, Rounding,
+};
+use observation::{get_twap_price, is_valid_twap_window, record_observation};
+use oracle::{get_oracle_spot_price, get_sources_decimals};
+use position::{
+    cancel_position, create_position, get_position_offset, get_used_position, get_used_positions,
+    get_used_positions_count, get_user_positions, set_position_valid,
+};
 use position_data::{
-    are_positions_open, get_position_a, get_position_b, get_position_data, init_position_a,
-    init_position_b, ocupy_one_position,
+    get_position_a, get_position_b, get_position_data, is_valid_position_data, set_position_a,
+    set_position_b,
 };
-use soroban_sdk::{contract, contractimpl, token, Address, Env, Map, Symbol, Vec};
+use soroban_sdk::{contract, contractimpl, token, vec, Address, BytesN, Env, Map, Symbol, Vec};
 use storage::{
-    get_admin, get_forward_rate, get_init_time, get_spot_rate, get_time_to_mature, put_admin,
-    put_forward_rate, put_init_time, put_spot_rate, put_time_to_mature,
+    get_admin, get_allocation_policy, get_auction_params, get_forward_rate, get_init_time,
+    get_max_price_age, get_open_users, get_oracle_quorum, get_oracle_sources, get_pending_oracles,
+    get_pending_upgrade, get_risk_params, get_schema_version, get_series_count, get_spot_rate,
+    get_time_to_mature, get_twap_window, put_admin, put_allocation_policy, put_auction_params,
+    put_forward_rate, put_init_time, put_max_price_age, put_open_users, put_oracles,
+    put_pending_oracles, put_pending_upgrade, put_risk_params, put_schema_version,
+    put_series_count, put_spot_rate, put_time_to_mature, put_twap_window, remove_pending_oracles,
+    remove_pending_upgrade,
 };
 use token_data::{
+    add_swept_amount, add_token_auction_proceeds, add_token_auctioned_collateral,
     add_token_collateral_amount, add_token_deposited_amount, add_token_liquidated_collateral,
-    add_token_reclaimed_amount, add_token_returned_amount, add_token_swapped_amount,
-    add_token_used_liq_collateral, add_token_withdrawn_amount, add_token_withdrawn_collateral,
+    add_token_liquidation_rewards, add_token_reclaimed_amount, add_token_returned_amount,
+    add_token_swapped_amount, add_token_used_auction_proceeds, add_token_used_liq_collateral,
+    add_token_withdrawn_amount, add_token_withdrawn_collateral, get_owed_amount, get_swept_amount,
     get_token_a, get_token_a_address, get_token_b, get_token_b_address, init_token_a, init_token_b,
 };
+use ttl::{
+    bump_contract, bump_series, bump_user, get_live_until, get_series_live_until,
+    get_user_live_until,
+};
 use types::{
-    error::Error, position::Position, price_data::PriceData, stage::Stage, token::Token,
-    user::User, user_liq_data::UserLiqData,
+    allocation_policy::AllocationPolicy, asset::Asset, auction_params::AuctionParams, error::Error,
+    init_params::InitParams, oracle_source::OracleSource, partial_liquidation::PartialLiquidation,
+    pending_oracles::PendingOracles, pending_upgrade::PendingUpgrade, position::Position,
+    position_data::PositionData, price_data::PriceData, risk_params::RiskParams, stage::Stage,
+    token::Token, user::User, user_health::UserHealth, user_liq_data::UserLiqData,
+    user_state::UserState,
 };
 use user::{
-    get_collateral, get_deposited_amount, get_deposited_token, get_reclaimed_amount,
-    get_returned_amount, get_swapped_amount, get_user_balance, get_user_deposit,
-    get_withdrawn_amount, get_withdrawn_collateral, has_not_repaid, is_liquidated, put_collateral,
-    put_deposited_amount, put_deposited_token, put_is_liquidated, put_reclaimed_amount,
-    put_returned_amount, put_swapped_amount, put_withdrawn_amount, put_withdrawn_collateral,
+    get_collateral, get_deposited_amount, get_deposited_token, get_returned_amount,
+    get_user_balance, get_withdrawn_amount, is_liquidated, new_user, save_user, try_load_user,
+    update_user,
 };
 
 fn transfer(e: &Env, token: Address, to: Address, amount: i128) {
@@ -47,120 +84,414 @@ fn transfer(e: &Env, token: Address, to: Address, amount: i128) {
 }
 
 fn transfer_a(e: &Env, to: &Address, amount: i128) {
-    transfer(e, get_token_a(e).address, to.clone(), amount);
+    transfer(e, get_token_a_address(e), to.clone(), amount);
 }
 
 fn transfer_b(e: &Env, to: &Address, amount: i128) {
-    transfer(e, get_token_b(e).address, to.clone(), amount);
+    transfer(e, get_token_b_address(e), to.clone(), amount);
 }
 
-fn near_leg_time_reached(e: &Env) -> bool {
+fn near_leg_time_reached(e: &Env, series: u32) -> bool {
     let ledger_timestamp = e.ledger().timestamp();
-    let init_time: u64 = get_init_time(&e);
-    let exec_time: u64 = init_time + TIME_TO_EXEC;
+    let init_time: u64 = get_init_time(&e, series);
+    let exec_time: u64 = init_time + get_risk_params(e).time_to_exec;
     ledger_timestamp >= exec_time
 }
 
-fn set_spot_price(e: &Env) -> PriceData {
-    let price_data = get_oracle_spot_price(&e);
-    put_spot_rate(&e, price_data.price);
-    price_data
+// Every oracle price read is kept as an observation for the TWAP
+fn read_spot_price(e: &Env) -> Result<PriceData, Error> {
+    let price_data = get_oracle_spot_price(e)?;
+    record_observation(e, &price_data);
+    Ok(price_data)
+}
+
+// Price used to check if a user can be liquidated
+fn read_liquidation_price(e: &Env) -> Result<i128, Error> {
+    let price_data = read_spot_price(e)?;
+    get_twap_price(e, &price_data)
+}
+
+// Same price as read_liquidation_price without storing the observation
+fn get_liquidation_price(e: &Env) -> Result<i128, Error> {
+    let price_data = get_oracle_spot_price(e)?;
+    get_twap_price(e, &price_data)
+}
+
+fn set_spot_price(e: &Env, series: u32) -> Result<PriceData, Error> {
+    let price_data = read_spot_price(e)?;
+    put_spot_rate(&e, series, price_data.price);
+    Ok(price_data)
+}
+
+fn has_near_leg_executed(e: &Env, series: u32) -> bool {
+    get_stage(&e, series) >= Stage::Swap
+}
+
+// Without a price the spot rate is defined later by the near leg
+fn init_spot_rate(e: &Env, series: u32) -> Result<i128, Error> {
+    match set_spot_price(e, series) {
+        Ok(price_data) => Ok(price_data.price),
+        Err(Error::PriceUnavailable) => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+fn put_series(e: &Env, series: u32, forward_rate: i128, duration: u64) {
+    put_forward_rate(e, series, forward_rate);
+    put_init_time(e, series);
+    put_time_to_mature(e, series, duration);
+    put_series_count(e, series + 1);
+}
+
+fn check_series(e: &Env, series: u32) -> Result<(), Error> {
+    if series >= get_series_count(e) {
+        return Err(Error::InvalidSeries);
+    }
+    Ok(())
 }
 
-fn has_near_leg_executed(e: &Env) -> bool {
-    get_stage(&e) >= Stage::Swap
+// The oracles can't change while a series priced with them has open positions
+fn is_oracle_locked(e: &Env) -> bool {
+    (0..get_series_count(e)).any(|series| {
+        has_near_leg_executed(e, series)
+            && (!max_time_reached(e, series) || get_open_users(e, series) > 0)
+    })
 }
 
-fn max_time_reached(e: &Env) -> bool {
+fn max_time_reached(e: &Env, series: u32) -> bool {
     let ledger_timestamp = e.ledger().timestamp();
-    let init_time: u64 = get_init_time(&e);
-    let time_to_mature = get_time_to_mature(&e);
-    let time_limit: u64 = init_time + TIME_TO_EXEC + time_to_mature + TIME_TO_REPAY;
+    let init_time: u64 = get_init_time(&e, series);
+    let time_to_mature = get_time_to_mature(&e, series);
+    let risk_params = get_risk_params(e);
+    let time_limit: u64 =
+        init_time + risk_params.time_to_exec + time_to_mature + risk_params.time_to_repay;
     ledger_timestamp >= time_limit
 }
 
-fn get_min_collateral(e: &Env, to: &Address, spot_rate: i128, is_deposit_token_a: bool) -> i128 {
-    let swapped_amount = get_swapped_amount(&e, &to);
-    let og_spot_rate = get_spot_rate(&e);
-    let forward_rate = get_forward_rate(&e);
+// Swapped amount not closed by partial liquidations
+fn get_open_swapped_amount(e: &Env, series: u32, to: &Address) -> i128 {
+    try_load_user(e, series, to).map_or(0, |user| user.swapped_amount - user.liquidated_amount)
+}
+
+// Used deposited amount and amount to return of a swapped amount,
+// the amount to return is in the swapped token. Both round up as they are owed by the user.
+fn get_position_value(
+    e: &Env,
+    series: u32,
+    swapped_amount: i128,
+    is_deposit_token_a: bool,
+) -> Result<(i128, i128), Error> {
+    let og_spot_rate = get_spot_rate(e, series);
+    let forward_rate = get_forward_rate(e, series);
 
     if is_deposit_token_a {
-        let used_deposited_amount = convert_amount_token_b_to_a(swapped_amount, og_spot_rate);
-        let to_return_amount = convert_amount_token_a_to_b(used_deposited_amount, forward_rate);
-        let current_price = convert_amount_token_a_to_b(used_deposited_amount, spot_rate);
-        let min_col = calculate_percentage(used_deposited_amount, COLLATERAL_BUFFER);
-        if to_return_amount > current_price {
-            let mtm = to_return_amount - current_price;
-            let amount = convert_amount_token_b_to_a(mtm, spot_rate);
-            max(calculate_percentage(amount, COLLATERAL_THRESHOLD), min_col)
-        } else {
-            min_col
-        }
+        let used_deposited_amount =
+            convert_amount_token_b_to_a(e, swapped_amount, og_spot_rate, Rounding::Up)?;
+        let to_return_amount =
+            convert_amount_token_a_to_b(e, used_deposited_amount, forward_rate, Rounding::Up)?;
+        Ok((used_deposited_amount, to_return_amount))
     } else {
-        let used_deposited_amount = convert_amount_token_a_to_b(swapped_amount, og_spot_rate);
-        let to_return_amount = convert_amount_token_b_to_a(used_deposited_amount, forward_rate);
-        let current_price = convert_amount_token_b_to_a(used_deposited_amount, spot_rate);
-        let min_col = calculate_percentage(used_deposited_amount, COLLATERAL_BUFFER);
-        if to_return_amount > current_price {
-            let mtm = to_return_amount - current_price;
-            let amount = convert_amount_token_a_to_b(mtm, spot_rate);
-            max(calculate_percentage(amount, COLLATERAL_THRESHOLD), min_col)
-        } else {
-            min_col
+        let used_deposited_amount =
+            convert_amount_token_a_to_b(e, swapped_amount, og_spot_rate, Rounding::Up)?;
+        let to_return_amount =
+            convert_amount_token_b_to_a(e, used_deposited_amount, forward_rate, Rounding::Up)?;
+        Ok((used_deposited_amount, to_return_amount))
+    }
+}
+
+// Used deposited amount and mark to market loss of a swapped amount, both in the deposited token
+fn get_position_loss(
+    e: &Env,
+    series: u32,
+    swapped_amount: i128,
+    spot_rate: i128,
+    is_deposit_token_a: bool,
+) -> Result<(i128, i128), Error> {
+    let (used_deposited_amount, to_return_amount) =
+        get_position_value(e, series, swapped_amount, is_deposit_token_a)?;
+
+    if is_deposit_token_a {
+        let current_price =
+            convert_amount_token_a_to_b(e, used_deposited_amount, spot_rate, Rounding::Down)?;
+        let loss = match to_return_amount > current_price {
+            true => convert_amount_token_b_to_a(
+                e,
+                to_return_amount - current_price,
+                spot_rate,
+                Rounding::Up,
+            )?,
+            false => 0,
+        };
+        Ok((used_deposited_amount, loss))
+    } else {
+        let current_price =
+            convert_amount_token_b_to_a(e, used_deposited_amount, spot_rate, Rounding::Down)?;
+        let loss = match to_return_amount > current_price {
+            true => convert_amount_token_a_to_b(
+                e,
+                to_return_amount - current_price,
+                spot_rate,
+                Rounding::Up,
+            )?,
+            false => 0,
+        };
+        Ok((used_deposited_amount, loss))
+    }
+}
+
+fn get_min_collateral(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    spot_rate: i128,
+    is_deposit_token_a: bool,
+) -> Result<i128, Error> {
+    let swapped_amount = get_open_swapped_amount(e, series, to);
+    get_position_min_collateral(e, series, swapped_amount, spot_rate, is_deposit_token_a)
+}
+
+fn get_position_min_collateral(
+    e: &Env,
+    series: u32,
+    swapped_amount: i128,
+    spot_rate: i128,
+    is_deposit_token_a: bool,
+) -> Result<i128, Error> {
+    let risk_params = get_risk_params(e);
+    let (used_deposited_amount, loss) =
+        get_position_loss(e, series, swapped_amount, spot_rate, is_deposit_token_a)?;
+    let min_col = calculate_percentage(
+        used_deposited_amount,
+        risk_params.collateral_buffer,
+        Rounding::Up,
+    )?;
+    Ok(max(
+        calculate_percentage(loss, risk_params.collateral_threshold, Rounding::Up)?,
+        min_col,
+    ))
+}
+
+// Spot rate at which the minimum collateral reaches the collateral.
+// Returns 0 if there is no open position or the collateral is under the buffer at any price.
+fn get_liquidation_spot_rate(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    collateral: i128,
+    is_deposit_token_a: bool,
+) -> Result<i128, Error> {
+    let risk_params = get_risk_params(e);
+    let threshold = risk_params.collateral_threshold;
+    let swapped_amount = get_open_swapped_amount(e, series, to);
+    let (used_deposited_amount, to_return_amount) =
+        get_position_value(e, series, swapped_amount, is_deposit_token_a)?;
+    let min_col = calculate_percentage(
+        used_deposited_amount,
+        risk_params.collateral_buffer,
+        Rounding::Up,
+    )?;
+
+    if to_return_amount <= 0 || collateral < min_col {
+        return Ok(0);
+    }
+
+    let (decimals_a, decimals_b) = get_decimals(e);
+    let to_return = checked_mul(to_return_amount, threshold)?;
+    let covered = checked_add(
+        checked_mul(used_deposited_amount, threshold)?,
+        checked_mul(100, collateral)?,
+    )?;
+    if is_deposit_token_a {
+        // collateral = threshold * (to_return_amount / spot_rate - used_deposited_amount)
+        mul_div_scaled(
+            to_return,
+            SCALE,
+            covered,
+            decimals_b,
+            decimals_a,
+            Rounding::Down,
+        )
+    } else {
+        // collateral = threshold * (to_return_amount * spot_rate - used_deposited_amount)
+        mul_div_scaled(
+            covered,
+            SCALE,
+            to_return,
+            decimals_b,
+            decimals_a,
+            Rounding::Down,
+        )
+    }
+}
+
+// Collateral left after withdrawals and partial liquidations
+fn get_available_collateral(e: &Env, series: u32, to: &Address) -> i128 {
+    try_load_user(e, series, to).map_or(0, |user| {
+        user.collateral - user.withdrawn_collateral - user.liquidated_collateral
+    })
+}
+
+// Swapped amount to close so the remaining collateral covers the minimum collateral again.
+// The loss of the closed part is seized and a penalty on its used deposited amount is paid
+// to the liquidator. Returns None if closing part of the position is not enough.
+fn get_partial_liquidation(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    spot_rate: i128,
+    is_deposit_token_a: bool,
+    collateral: i128,
+    min_collateral: i128,
+) -> Result<Option<PartialLiquidation>, Error> {
+    let swapped_amount = get_open_swapped_amount(e, series, to);
+    let (used_deposited_amount, loss) =
+        get_position_loss(e, series, swapped_amount, spot_rate, is_deposit_token_a)?;
+    let penalty = calculate_percentage(
+        used_deposited_amount,
+        get_risk_params(e).liquidation_reward,
+        Rounding::Down,
+    )?;
+    // Minimum collateral released by closing the whole position
+    let released_collateral = min_collateral - loss - penalty;
+
+    if swapped_amount <= 0 || released_collateral <= 0 {
+        return Ok(None);
+    }
+
+    let mut amount = mul_div(
+        min_collateral - collateral,
+        swapped_amount,
+        released_collateral,
+        Rounding::Up,
+    )?;
+    // Rounding can leave the remaining position slightly under the minimum collateral,
+    // each step releases at least one more unit of collateral
+    let step = mul_div(swapped_amount, 1, released_collateral, Rounding::Up)?;
+
+    while amount < swapped_amount {
+        let liq_collateral = mul_div(loss, amount, swapped_amount, Rounding::Up)?;
+        let reward_amount = mul_div(penalty, amount, swapped_amount, Rounding::Down)?;
+        let remaining_min_collateral = get_position_min_collateral(
+            e,
+            series,
+            swapped_amount - amount,
+            spot_rate,
+            is_deposit_token_a,
+        )?;
+        if collateral - liq_collateral - reward_amount >= remaining_min_collateral {
+            return Ok(Some(PartialLiquidation {
+                amount,
+                liquidated_collateral: liq_collateral,
+                reward_amount,
+            }));
         }
+        amount += step;
+    }
+
+    Ok(None)
+}
+
+fn partially_liquidate_user(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    from: &Address,
+    user: &mut UserState,
+    liquidation: &PartialLiquidation,
+) -> i128 {
+    let reward_amount = liquidation.reward_amount;
+    let liq_collateral = liquidation.liquidated_collateral;
+    user.liquidated_amount += liquidation.amount;
+    user.liquidated_collateral += liq_collateral + reward_amount;
+    let token = &user.deposited_token;
+    if reward_amount > 0 {
+        transfer(e, token.clone(), from.clone(), reward_amount);
+        add_token_liquidation_rewards(e, series, token, reward_amount);
     }
+    if liq_collateral > 0 {
+        add_token_liquidated_collateral(e, series, token, liq_collateral);
+    }
+    emit_partial_liquidate(e, series, to, from, token, liquidation);
+    reward_amount
 }
 
-fn liquidate_user(e: &Env, to: &Address, from: &Address, spot_price: i128) -> i128 {
-    let withdrawn_collateral = get_withdrawn_collateral(&e, &to);
-    let collateral = get_collateral(&e, &to) - withdrawn_collateral;
+fn liquidate_user(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    from: &Address,
+    user: &mut UserState,
+    spot_price: i128,
+) -> Result<i128, Error> {
+    let collateral = get_available_collateral(e, series, to);
     let mut reward_amount: i128 = 0;
-    let expired_and_not_repaid = max_time_reached(&e) && has_not_repaid(&e, &to);
+    let risk_params = get_risk_params(e);
+    let reward_rate = risk_params.liquidation_reward;
+    let expired_and_not_repaid = max_time_reached(e, series) && has_not_repaid(e, series, to)?;
 
-    if is_liquidated(&e, &to) {
-        return 0;
+    if user.is_liquidated {
+        return Ok(0);
     }
 
-    if let Some(token) = get_deposited_token(&e, &to) {
-        if token == get_token_a_address(&e) {
-            // If user deposited a then it swapped b
-            // User a needs to have 150 of the corresponding to token b in its collateral
-            // we need to convert swapped amount into token a
-            let min_collateral = get_min_collateral(&e, &to, spot_price, true);
-
-            if (min_collateral > collateral) || expired_and_not_repaid {
-                reward_amount = calculate_percentage(collateral, 1);
-                let liq_collateral = min(min_collateral, collateral) - reward_amount;
-                put_is_liquidated(&e, &to, true);
-                transfer_a(&e, &from, reward_amount);
-                if liq_collateral > 0 {
-                    add_token_liquidated_collateral(&e, &token, liq_collateral)
-                };
-            }
-        } else {
-            let min_collateral = get_min_collateral(&e, &to, spot_price, false);
-
-            if (min_collateral > collateral) || expired_and_not_repaid {
-                reward_amount = calculate_percentage(collateral, 1);
-                let liq_collateral = min(min_collateral, collateral) - reward_amount;
-                put_is_liquidated(&e, &to, true);
-                transfer_b(&e, &from, reward_amount);
-                if liq_collateral > 0 {
-                    add_token_liquidated_collateral(&e, &token, min(min_collateral, collateral));
-                }
-            }
+    let token = user.deposited_token.clone();
+    // Close only part of the position when it is enough to restore the minimum collateral
+    if risk_params.partial_liquidation && !expired_and_not_repaid {
+        let is_deposit_token_a = token == get_token_a_address(e);
+        let min_collateral = get_min_collateral(e, series, to, spot_price, is_deposit_token_a)?;
+        if min_collateral <= collateral {
+            return Ok(0);
+        }
+        let partial_liquidation = get_partial_liquidation(
+            e,
+            series,
+            to,
+            spot_price,
+            is_deposit_token_a,
+            collateral,
+            min_collateral,
+        )?;
+        if let Some(liquidation) = partial_liquidation {
+            return Ok(partially_liquidate_user(
+                e,
+                series,
+                to,
+                from,
+                user,
+                &liquidation,
+            ));
         }
     }
-    reward_amount
+
+    // If user deposited a then it swapped b, its collateral in token a
+    // has to cover the swapped amount converted into token a and vice versa
+    let is_deposit_token_a = token == get_token_a_address(&e);
+    let min_collateral = get_min_collateral(&e, series, &to, spot_price, is_deposit_token_a)?;
+
+    if (min_collateral > collateral) || expired_and_not_repaid {
+        reward_amount = calculate_percentage(collateral, reward_rate, Rounding::Down)?;
+        // The reward is paid out of the seized collateral, only the rest is booked
+        let seized_collateral = max(min(min_collateral, collateral), reward_amount);
+        let liq_collateral = seized_collateral - reward_amount;
+        user.is_liquidated = true;
+        user.liquidated_collateral += seized_collateral;
+        transfer(&e, token.clone(), from.clone(), reward_amount);
+        add_token_liquidation_rewards(e, series, &token, reward_amount);
+        if liq_collateral > 0 {
+            add_token_liquidated_collateral(&e, series, &token, liq_collateral);
+        }
+        emit_liquidate(e, series, to, from, &token, reward_amount, liq_collateral);
+    }
+    Ok(reward_amount)
 }
 
-fn get_stage(e: &Env) -> Stage {
+fn get_stage(e: &Env, series: u32) -> Stage {
     let ledger_timestamp = e.ledger().timestamp();
-    let init_time = get_init_time(&e);
-    let time_to_mature = get_time_to_mature(&e);
-    let time_to_deposit = init_time + TIME_TO_EXEC;
+    let init_time = get_init_time(&e, series);
+    let time_to_mature = get_time_to_mature(&e, series);
+    let risk_params = get_risk_params(e);
+    let time_to_deposit = init_time + risk_params.time_to_exec;
     let time_to_swap = time_to_deposit + time_to_mature;
-    let time_limit = time_to_swap + TIME_TO_REPAY;
+    let time_limit = time_to_swap + risk_params.time_to_repay;
 
     match ledger_timestamp {
         ts if ts < time_to_deposit => Stage::Deposit,
@@ -171,20 +502,88 @@ fn get_stage(e: &Env) -> Stage {
 }
 
 // User
-fn get_user_amount_to_repay(e: &Env, to: &Address) -> i128 {
-    let forward_rate = get_forward_rate(&e);
-    let spot_rate = get_spot_rate(&e);
-    let swapped_amount = get_swapped_amount(&e, &to);
+fn get_user_amount_to_repay(e: &Env, series: u32, to: &Address) -> Result<i128, Error> {
+    let forward_rate = get_forward_rate(&e, series);
+    let spot_rate = get_spot_rate(&e, series);
+    let swapped_amount = get_open_swapped_amount(e, series, to);
     let mut repay_amount: i128 = 0;
-    if let Some(token) = get_deposited_token(&e, &to) {
+    if let Some(token) = get_deposited_token(&e, series, &to) {
         if token == get_token_a_address(&e) {
-            let used_deposited_amount = convert_amount_token_b_to_a(swapped_amount, spot_rate);
-            repay_amount = convert_amount_token_a_to_b(used_deposited_amount, forward_rate);
+            let used_deposited_amount =
+                convert_amount_token_b_to_a(e, swapped_amount, spot_rate, Rounding::Up)?;
+            repay_amount =
+                convert_amount_token_a_to_b(e, used_deposited_amount, forward_rate, Rounding::Up)?;
         } else {
             repay_amount = swapped_amount;
         }
     }
-    repay_amount
+    Ok(repay_amount)
+}
+
+// Whether the user still owes part of what it swapped, the part closed by a partial
+// liquidation is no longer owed
+fn has_not_repaid(e: &Env, series: u32, to: &Address) -> Result<bool, Error> {
+    Ok(get_returned_amount(e, series, to) < get_user_amount_to_repay(e, series, to)?)
+}
+
+// Whether the user has nothing left to reclaim, get back as collateral or withdraw.
+// The collateral is locked until the max time is reached, no user settles before that.
+fn is_settled(e: &Env, series: u32, to: &Address, user: &UserState) -> Result<bool, Error> {
+    if !max_time_reached(e, series) {
+        return Ok(false);
+    }
+
+    let deposit_left =
+        user.deposited_amount - get_used_deposited_amount(e, series, to)? - user.reclaimed_amount;
+    let collateral_left = user.collateral - user.withdrawn_collateral - user.liquidated_collateral;
+    if deposit_left > 0 || collateral_left > 0 || user.is_liquidated {
+        return Ok(deposit_left <= 0 && collateral_left <= 0);
+    }
+
+    let forward_rate = get_forward_rate(e, series);
+    let spot_rate = get_spot_rate(e, series);
+    let returned_amount = user.returned_amount;
+    let withdrawn_amount = user.withdrawn_amount;
+    // A withdraw left worth less than a unit of the other token can't be compensated,
+    // it stays in the contract as dust
+    let compensation = match user.deposited_token == get_token_a_address(e) {
+        true => {
+            let converted_returned_amount =
+                convert_amount_token_b_to_a(e, returned_amount, forward_rate, Rounding::Down)?;
+            let withdraw_left = max(converted_returned_amount - withdrawn_amount, 0);
+            convert_amount_token_a_to_b(e, withdraw_left, spot_rate, Rounding::Down)?
+        }
+        false => {
+            let converted_returned_amount =
+                convert_amount_token_a_to_b(e, returned_amount, forward_rate, Rounding::Down)?;
+            let withdraw_left = max(converted_returned_amount - withdrawn_amount, 0);
+            convert_amount_token_b_to_a(e, withdraw_left, spot_rate, Rounding::Down)?
+        }
+    };
+    Ok(compensation <= 0)
+}
+
+fn open_user(e: &Env, series: u32, user: &mut UserState) {
+    if !user.is_open {
+        user.is_open = true;
+        put_open_users(e, series, get_open_users(e, series) + 1);
+    }
+}
+
+fn close_user(e: &Env, series: u32, user: &mut UserState) {
+    if user.is_open {
+        user.is_open = false;
+        put_open_users(e, series, get_open_users(e, series) - 1);
+    }
+}
+
+// Closes the user once it has no claims left, what the series owes it is no longer kept
+// from the dust once every user closed
+fn settle_user(e: &Env, series: u32, to: &Address, user: &mut UserState) -> Result<(), Error> {
+    if user.is_open && is_settled(e, series, to, user)? {
+        close_user(e, series, user);
+    }
+    Ok(())
 }
 
 fn is_authorized(e: &Env, to: &Address) -> bool {
@@ -193,16 +592,136 @@ fn is_authorized(e: &Env, to: &Address) -> bool {
 }
 
 //Utils
-fn convert_amount_token_a_to_b(amount: i128, rate: i128) -> i128 {
-    amount * rate / SCALE
+type ConvertFn = fn(&Env, i128, i128, Rounding) -> Result<i128, Error>;
+
+// Deposits of the token a user deposited and of the other token of the series
+struct DepositSide {
+    is_token_a: bool,
+    total_deposited_amount: i128,
+    total_other_deposited_amount: i128,
+    convert_to_other: ConvertFn,
+    convert_from_other: ConvertFn,
+}
+
+fn get_decimals(e: &Env) -> (u32, u32) {
+    (get_token_a(e, 0).decimals, get_token_b(e, 0).decimals)
+}
+
+fn convert_amount_token_a_to_b(
+    e: &Env,
+    amount: i128,
+    rate: i128,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    let (decimals_a, decimals_b) = get_decimals(e);
+    convert_a_to_b(amount, rate, decimals_a, decimals_b, rounding)
+}
+
+fn convert_amount_token_b_to_a(
+    e: &Env,
+    amount: i128,
+    rate: i128,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    let (decimals_a, decimals_b) = get_decimals(e);
+    convert_b_to_a(amount, rate, decimals_a, decimals_b, rounding)
+}
+
+// The name must be the symbol of the token contract
+fn is_token_name(e: &Env, token: &Address, name: &Symbol) -> bool {
+    let symbol = match token::Client::new(e, token).try_symbol() {
+        Ok(Ok(symbol)) => symbol,
+        _ => return false,
+    };
+    let len = symbol.len() as usize;
+    let mut buf = [0u8; 32];
+    if len == 0 || len > buf.len() {
+        return false;
+    }
+    symbol.copy_into_slice(&mut buf[..len]);
+    if !buf[..len]
+        .iter()
+        .all(|c| c.is_ascii_alphanumeric() || *c == b'_')
+    {
+        return false;
+    }
+    match core::str::from_utf8(&buf[..len]) {
+        Ok(symbol) => Symbol::new(e, symbol) == *name,
+        Err(_) => false,
+    }
+}
+
+fn get_token_decimals(e: &Env, token: &Address) -> Result<u32, Error> {
+    match token::Client::new(e, token).try_decimals() {
+        Ok(Ok(decimals)) => Ok(decimals),
+        _ => Err(Error::InvalidTokenContract),
+    }
+}
+
+fn is_valid_risk_params(risk_params: &RiskParams) -> bool {
+    risk_params.collateral_buffer > 0
+        && risk_params.collateral_buffer <= 100
+        && risk_params.collateral_threshold > 100
+        && risk_params.liquidation_reward >= 0
+        && risk_params.liquidation_reward < risk_params.collateral_buffer
+        && risk_params.time_to_exec > 0
+        && risk_params.time_to_repay > 0
+}
+
+// The stage times of a series started now, up to the end of its claim period, must fit in a u64
+fn is_valid_duration(e: &Env, duration: u64, risk_params: &RiskParams) -> bool {
+    duration > 0
+        && e.ledger()
+            .timestamp()
+            .checked_add(risk_params.time_to_exec)
+            .and_then(|time| time.checked_add(duration))
+            .and_then(|time| time.checked_add(risk_params.time_to_repay))
+            .and_then(|time| time.checked_add(CLAIM_PERIOD))
+            .is_some()
+}
+
+fn is_valid_auction_params(auction_params: &AuctionParams) -> bool {
+    auction_params.end_price > 0
+        && auction_params.start_price >= auction_params.end_price
+        && auction_params.duration > 0
 }
 
-fn convert_amount_token_b_to_a(amount: i128, rate: i128) -> i128 {
-    (amount * SCALE) / rate
+// Liquidated collateral that was neither used in withdrawals nor sold in the auction
+fn get_auctionable_collateral(token: &Token) -> i128 {
+    token.liquidated_collateral - token.used_liq_collateral - token.auctioned_collateral
 }
 
-fn calculate_percentage(amount: i128, rate: i128) -> i128 {
-    (amount * rate) / 100
+// Price of the liquidated collateral in the counter token,
+// it falls linearly from the start price to the final price of the auction.
+// Rounds up as it is paid to the contract.
+fn get_auction_price(
+    e: &Env,
+    token: &Token,
+    auction_params: &AuctionParams,
+) -> Result<i128, Error> {
+    let spot_rate = read_liquidation_price(e)?;
+    let oracle_price = match token.address == get_token_a_address(e) {
+        true => spot_rate,
+        false => mul_div(SCALE, SCALE, spot_rate, Rounding::Up)?,
+    };
+    let duration = auction_params.duration as i128;
+    let elapsed = min(
+        e.ledger().timestamp() - token.auction_start,
+        auction_params.duration,
+    ) as i128;
+    let percentage = checked_sub(
+        checked_mul(auction_params.start_price, duration)?,
+        checked_mul(
+            checked_sub(auction_params.start_price, auction_params.end_price)?,
+            elapsed,
+        )?,
+    )?;
+    mul_div(
+        oracle_price,
+        percentage,
+        checked_mul(100, duration)?,
+        Rounding::Up,
+    )
 }
 
 fn is_valid_token(e: &Env, token: Address) -> bool {
@@ -211,146 +730,247 @@ fn is_valid_token(e: &Env, token: Address) -> bool {
     token == token_a_address || token == token_b_address
 }
 
+// Positions are filled in deposit order until the amount deposited in the other token,
+// so the offset of a position tells how much of the other token fills the ones before it.
+// Rounds down so the used amounts never exceed the amount deposited in the other token.
 fn calculate_used_deposited_amount(
+    e: &Env,
+    series: u32,
     user: &Address,
-    used_positions: Vec<Position>,
-    total_other_deposited_amount: i128,
-    base_deposit_amount: i128,
-    base_converted_amount: i128,
-    convert_currency: fn(i128, i128) -> i128,
+    side: &DepositSide,
     spot_rate: i128,
-) -> i128 {
+) -> Result<i128, Error> {
+    let is_deposit_token_a = side.is_token_a;
+    let total_other_deposited_amount = side.total_other_deposited_amount;
+    let convert_to_other = side.convert_to_other;
+    let convert_from_other = side.convert_from_other;
     let mut used_amount = 0;
-    let mut acum = 0;
-
-    for position in used_positions.iter() {
-        if position.is_valid {
-            acum += base_converted_amount;
-            if position.address == user.clone() {
-                used_amount += base_deposit_amount;
-                if acum >= total_other_deposited_amount {
-                    let surplus = convert_currency(acum - total_other_deposited_amount, spot_rate);
-                    return max(used_amount - surplus, 0);
-                }
-            }
-            if acum >= total_other_deposited_amount {
-                return used_amount;
-            }
+
+    for index in get_user_positions(e, series, user, is_deposit_token_a).iter() {
+        let position = get_used_position(e, series, is_deposit_token_a, index);
+        if !position.is_valid {
+            continue;
+        }
+        let offset = get_position_offset(e, series, is_deposit_token_a, index, &position);
+        let start = convert_to_other(e, offset, spot_rate, Rounding::Down)?;
+        if offset > 0 && start >= total_other_deposited_amount {
+            continue;
+        }
+        let end = convert_to_other(e, offset + position.amount, spot_rate, Rounding::Down)?;
+        if end <= total_other_deposited_amount {
+            used_amount += position.amount;
+        } else {
+            let surplus = convert_from_other(
+                e,
+                end - total_other_deposited_amount,
+                spot_rate,
+                Rounding::Down,
+            )?;
+            used_amount += max(position.amount - surplus, 0);
         }
     }
 
-    used_amount
+    Ok(used_amount)
 }
 
-fn get_used_deposited_amount(e: &Env, user: &Address) -> i128 {
-    let token_a_data = get_token_a(&e);
-    let token_b_data = get_token_b(&e);
-    let position_a = get_position_a(&e);
-    let position_b = get_position_b(&e);
-    let spot_rate = get_spot_rate(&e);
+fn get_used_deposited_amount(e: &Env, series: u32, user: &Address) -> Result<i128, Error> {
+    let token_a_data = get_token_a(e, series);
+    let token_b_data = get_token_b(e, series);
+    let spot_rate = get_spot_rate(e, series);
+    let is_deposit_token_a = token_a_data.address == get_deposited_token(e, series, user).unwrap();
+
+    let side = match is_deposit_token_a {
+        true => DepositSide {
+            is_token_a: true,
+            total_deposited_amount: token_a_data.deposited_amount,
+            total_other_deposited_amount: token_b_data.deposited_amount,
+            convert_to_other: convert_amount_token_a_to_b,
+            convert_from_other: convert_amount_token_b_to_a,
+        },
+        false => DepositSide {
+            is_token_a: false,
+            total_deposited_amount: token_b_data.deposited_amount,
+            total_other_deposited_amount: token_a_data.deposited_amount,
+            convert_to_other: convert_amount_token_b_to_a,
+            convert_from_other: convert_amount_token_a_to_b,
+        },
+    };
 
-    let amount = match token_a_data.address == get_deposited_token(&e, &user).unwrap() {
-        true => {
-            let used_positions_a = get_used_positions_a(&e);
-            let total_other_deposited_amount = token_b_data.deposited_amount;
-            let base_converted_amount =
-                convert_amount_token_a_to_b(position_a.deposit_amount, spot_rate);
-            calculate_used_deposited_amount(
-                user,
-                used_positions_a,
-                total_other_deposited_amount,
-                position_a.deposit_amount,
-                base_converted_amount,
-                convert_amount_token_b_to_a,
-                spot_rate,
-            )
+    let amount = match get_allocation_policy(e) {
+        AllocationPolicy::Fifo => {
+            calculate_used_deposited_amount(e, series, user, &side, spot_rate)?
         }
-        false => {
-            let used_positions_b = get_used_positions_b(&e);
-            let total_other_deposited_amount = token_a_data.deposited_amount;
-            let base_converted_amount =
-                convert_amount_token_b_to_a(position_b.deposit_amount, spot_rate);
-            calculate_used_deposited_amount(
-                user,
-                used_positions_b,
-                total_other_deposited_amount,
-                position_b.deposit_amount,
-                base_converted_amount,
-                convert_amount_token_a_to_b,
+        AllocationPolicy::ProRata => calculate_pro_rata_used_amount(
+            get_deposited_amount(e, series, user),
+            side.total_deposited_amount,
+            (side.convert_from_other)(
+                e,
+                side.total_other_deposited_amount,
                 spot_rate,
-            )
-        }
+                Rounding::Down,
+            )?,
+        )?,
     };
-    max(amount, 0)
+    Ok(max(amount, 0))
+}
+
+// Every depositor of the oversubscribed side gets the same fraction of its deposit used,
+// rounding down so the sum of the used amounts never exceeds the matched amount.
+fn calculate_pro_rata_used_amount(
+    user_deposited_amount: i128,
+    total_deposited_amount: i128,
+    total_other_converted_amount: i128,
+) -> Result<i128, Error> {
+    if total_deposited_amount == 0 {
+        return Ok(0);
+    }
+    let matched_amount = min(total_deposited_amount, total_other_converted_amount);
+    mul_div(
+        user_deposited_amount,
+        matched_amount,
+        total_deposited_amount,
+        Rounding::Down,
+    )
+}
+
+// Once a token has deposits its cap can't go below them and its minimum deposit can't change
+fn check_position_update(
+    token: &Token,
+    current: &PositionData,
+    new: &PositionData,
+) -> Result<(), Error> {
+    if new.cap < token.deposited_amount {
+        return Err(Error::CapBelowDeposited);
+    }
+    if new.min_deposit != current.min_deposit {
+        return Err(Error::MinDepositLocked);
+    }
+    Ok(())
+}
+
+fn configure_positions(
+    e: &Env,
+    series: u32,
+    position_a: &PositionData,
+    position_b: &PositionData,
+) -> Result<(), Error> {
+    if has_near_leg_executed(e, series) {
+        return Err(Error::WrongStageToConfigure);
+    }
+
+    if !is_valid_position_data(position_a) || !is_valid_position_data(position_b) {
+        return Err(Error::InvalidPositionData);
+    }
+
+    let token_a_data = get_token_a(e, series);
+    let token_b_data = get_token_b(e, series);
+    if token_a_data.deposited_amount > 0 {
+        check_position_update(&token_a_data, &get_position_a(e, series), position_a)?;
+    }
+    if token_b_data.deposited_amount > 0 {
+        check_position_update(&token_b_data, &get_position_b(e, series), position_b)?;
+    }
+
+    set_position_a(e, series, position_a);
+    set_position_b(e, series, position_b);
+    Ok(())
 }
 
 fn calculate_amount_deposit_token_b(
     e: &Env,
+    series: u32,
     positions_token_a: u64,
     positions_token_b: u64,
     amount_deposit_token_a: i128,
-) -> i128 {
-    let spot_rate = get_spot_rate(&e);
-    let total_amount_a: i128 = (positions_token_a as i128) * amount_deposit_token_a;
-    let amount_deposit_amount_a: i128 = total_amount_a / (positions_token_b as i128);
-    convert_amount_token_a_to_b(amount_deposit_amount_a, spot_rate)
+) -> Result<i128, Error> {
+    let spot_rate = get_spot_rate(&e, series);
+    let amount_deposit_amount_a = mul_div(
+        positions_token_a as i128,
+        amount_deposit_token_a,
+        positions_token_b as i128,
+        Rounding::Down,
+    )?;
+    convert_amount_token_a_to_b(e, amount_deposit_amount_a, spot_rate, Rounding::Down)
 }
 
 fn get_users_liq_data(
     e: &Env,
+    series: u32,
     deposits: Vec<Position>,
     is_deposit_token_a: bool,
-) -> Vec<UserLiqData> {
-    let spot_rate = get_oracle_spot_price(&e).price;
+    spot_rate: i128,
+) -> Result<Vec<UserLiqData>, Error> {
     let mut unique_addresses: Map<Address, bool> = Map::new(&e);
     let mut users: Vec<UserLiqData> = Vec::new(&e);
 
-    deposits.iter().for_each(|position| {
-        unique_addresses.set(position.address, true);
-    });
+    deposits
+        .iter()
+        .filter(|position| position.is_valid)
+        .for_each(|position| {
+            unique_addresses.set(position.address, true);
+        });
 
-    unique_addresses.iter().for_each(|(address, _)| {
+    for (address, _) in unique_addresses.iter() {
+        let user = try_load_user(&e, series, &address).unwrap();
         users.push_back(UserLiqData {
             address: address.clone(),
-            collateral: get_collateral(&e, &address),
-            min_collateral: get_min_collateral(&e, &address, spot_rate, is_deposit_token_a),
-            is_liquidated: is_liquidated(&e, &address),
+            collateral: user.collateral,
+            min_collateral: get_min_collateral(
+                &e,
+                series,
+                &address,
+                spot_rate,
+                is_deposit_token_a,
+            )?,
+            is_liquidated: user.is_liquidated,
+            liquidated_amount: user.liquidated_amount,
+            liquidated_collateral: user.liquidated_collateral,
         })
-    });
+    }
 
-    users
+    Ok(users)
 }
 
 pub trait SwapTrait {
-    // Initializes the contract.
+    // Initializes the contract and creates its first series, with id 0.
     //
     // # Arguments
     //
     // * `admin` - Address of the admin,
-    // * `token_a` - Address of token A to swap,
-    // * `token_b` - Address of token B to swap,
-    // * `name_token_a` - Symbol of token A to swap,
-    // * `name_token_b` - Symbol of token B to swap,
-    // * `forward_rate` - Forward rate,
-    // * `duration` - Contract duration until the contract matures.
+    // * `params` - Tokens to swap and their symbols, forward rate and duration of series 0,
+    //   oracle providing the spot rate, risk params and allocation policy.
     // # Returns
     //
-    // Spot rate or Error.
-    fn initialize(
-        e: Env,
-        admin: Address,
-        token_a: Address,
-        token_b: Address,
-        name_token_a: Symbol,
-        name_token_b: Symbol,
-        forward_rate: i128,
-        duration: u64,
-    ) -> Result<i128, Error>;
+    // Spot rate, 0 if the oracle has no price yet, or Error.
+    fn initialize(e: Env, admin: Address, params: InitParams) -> Result<i128, Error>;
+
+    // Adds a series with its own forward rate and maturity on the same pair (Only for admin).
+    // It starts in the Deposit stage, its positions are set with init_pos or init_limits.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller,
+    // * `forward_rate` - Forward rate of the series,
+    // * `duration` - Duration of the series until it matures.
+    //
+    // # Returns
+    //
+    // Id of the new series or Error.
+    fn add_series(e: Env, from: Address, forward_rate: i128, duration: u64) -> Result<u32, Error>;
+
+    // Returns the amount of series, their ids go from 0 to the amount minus one.
+    //
+    // # Returns
+    //
+    // Amount of series.
+    fn series_count(e: Env) -> u32;
 
     // Set the positions' values.
+    // Users can then deposit any amount from one position up to the total of the positions.
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `from` - Address of the caller (Only admin can initialize the positions),
     // * `positions_token_a` - Quantity of positions for Token A,
     // * `positions_token_b` - Quantity of positions for Token B,
@@ -360,17 +980,70 @@ pub trait SwapTrait {
     // Amount to deposit in each position of Token B or Error.
     fn init_pos(
         e: Env,
+        series: u32,
         from: Address,
         positions_token_a: u64,
         positions_token_b: u64,
         amount_deposit_token_a: i128,
     ) -> Result<i128, Error>;
 
+    // Set the deposit limits of each token (Only for admin).
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the caller,
+    // * `position_a` - Minimum and maximum amount per user and total cap for Token A,
+    // * `position_b` - Minimum and maximum amount per user and total cap for Token B.
+    //
+    // # Returns
+    //
+    // Ok or Error.
+    fn init_limits(
+        e: Env,
+        series: u32,
+        from: Address,
+        position_a: PositionData,
+        position_b: PositionData,
+    ) -> Result<(), Error>;
+
+    // Returns the deposit limits of each token.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
+    // # Returns
+    //
+    // Tuple containing the deposit limits: (limits for Token A, limits for Token B).
+    fn limits(e: Env, series: u32) -> (PositionData, PositionData);
+
+    // Increases the total cap of a token during the Deposit stage (Only for admin).
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the caller,
+    // * `token` - Address of the token,
+    // * `amount` - Amount to add to the cap.
+    //
+    // # Returns
+    //
+    // New cap of the token or Error.
+    fn increase_capacity(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<i128, Error>;
+
     // Deposit amount and collateral.
     // TODO: Add desired execution time
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `from` - Address of the user depositing,
     // * `token` - Address of the token to deposit,
     // * `amount` - Amount to deposit,
@@ -381,30 +1054,49 @@ pub trait SwapTrait {
     // Tuple: total deposit amount and total collateral amount or Error.
     fn deposit(
         e: Env,
+        series: u32,
         from: Address,
         token: Address,
         amount: i128,
         collateral: i128,
     ) -> Result<(i128, i128), Error>;
 
+    // Cancels the deposit of the user before the near leg,
+    // refunding its amount and collateral.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the user cancelling the deposit.
+    //
+    // # Returns
+    //
+    // Tuple: refunded deposit amount and refunded collateral amount or Error.
+    fn cancel_deposit(e: Env, series: u32, from: Address) -> Result<(i128, i128), Error>;
+
     // Executes neag leg.
     //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
     // # Returns
     //
     // Price and timestamp of spot rate or None if the asset is not supported.
-    fn near_leg(e: Env) -> Result<PriceData, Error>;
+    fn near_leg(e: Env, series: u32) -> Result<PriceData, Error>;
 
     // Transfers the desired token
     // Can only be called in the Execution Stage.
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `from` - Address of the user executing the swap
     //
     // # Returns
     //
     // Swapped amount or Error if near leg was not executed.
-    fn swap(e: Env, from: Address) -> Result<i128, Error>;
+    fn swap(e: Env, series: u32, from: Address) -> Result<i128, Error>;
 
     // Liquidate the Address if can be liquidated
     // Only possible after neaN Leg is executed
@@ -412,77 +1104,143 @@ pub trait SwapTrait {
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `to` - Address of the user to liquidate,
     // * `from` - Address of the user executing the liquidation
     //
     // # Returns
     //
-    // Reward amount if address liquidated, 0 if it was not or collateral was too low.
-    fn liquidate(e: Env, to: Address, from: Address) -> i128;
+    // Reward amount if address liquidated, 0 if it was not or collateral was too low,
+    // or Error if the oracle price is not valid.
+    fn liquidate(e: Env, series: u32, to: Address, from: Address) -> Result<i128, Error>;
 
-    // Repays the amount previously swapped.
+    // Buys liquidated collateral in the Dutch auction paying with the counter token.
+    // The price starts above the oracle price and falls over time since the last liquidation.
     //
     // # Arguments
     //
-    // * `from` - Address of the user repaying,
-    // * `token` - Address of the token to repay,
-    // * `amount` - Amount to repay
+    // * `series` - Id of the series,
+    // * `from` - Address of the buyer,
+    // * `token` - Address of the collateral token to buy,
+    // * `amount` - Amount of collateral to buy, limited to the collateral available,
+    // * `max_cost` - Maximum amount of the counter token to pay
     //
     // # Returns
     //
-    // Tuple: (total repaid amount, amount to repay) or Error.
-    fn repay(e: Env, from: Address, token: Address, amount: i128) -> Result<(i128, i128), Error>;
+    // Tuple: (amount of collateral bought, amount of the counter token paid) or Error.
+    fn buy_collateral(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+        max_cost: i128,
+    ) -> Result<(i128, i128), Error>;
 
-    // Withdraws the deposited amount using the forward rate.
+    // Returns the current auction price of the liquidated collateral.
     //
     // # Arguments
     //
-    // * `from` - Address of the user withdrawing
+    // * `series` - Id of the series,
+    // * `token` - Address of the collateral token
     //
     // # Returns
     //
-    // Tuple: (amount of token A withdrawn, amount of token B withdrawn) or Error.
-    fn withdraw(e: Env, from: Address) -> Result<(i128, i128), Error>;
+    // Price of the collateral in the counter token or Error.
+    fn auction_price(e: Env, series: u32, token: Address) -> Result<i128, Error>;
 
-    // Transfers the initial deposit surplus.
+    // Returns the health of a user's position at the current oracle price.
     //
     // # Arguments
     //
-    // * `from` - Address of the user reclaiming
+    // * `series` - Id of the series,
+    // * `to` - Address of the user
     //
     // # Returns
     //
-    // Reclaimed amount or an Error if the contract is open.
-    fn reclaim(e: Env, from: Address) -> Result<i128, Error>;
+    // Collateral, minimum collateral, health factor (collateral / minimum collateral scaled
+    // by SCALE), spot rate at which the user can be liquidated and whether the user can be
+    // liquidated now, or Error if the oracle price is not valid.
+    fn health(e: Env, series: u32, to: Address) -> Result<UserHealth, Error>;
 
-    // Transfers the deposited collateral.
+    // Repays the amount previously swapped.
     //
     // # Arguments
     //
-    // * `from` - Address of the user reclaiming
+    // * `series` - Id of the series,
+    // * `from` - Address of the user repaying,
+    // * `token` - Address of the token to repay,
+    // * `amount` - Amount to repay
+    //
+    // # Returns
+    //
+    // Tuple: (total repaid amount, amount to repay) or Error.
+    fn repay(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<(i128, i128), Error>;
+
+    // Withdraws the deposited amount using the forward rate.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the user withdrawing
+    //
+    // # Returns
+    //
+    // Tuple: (amount of token A withdrawn, amount of token B withdrawn) or Error.
+    fn withdraw(e: Env, series: u32, from: Address) -> Result<(i128, i128), Error>;
+
+    // Transfers the initial deposit surplus.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the user reclaiming
+    //
+    // # Returns
+    //
+    // Reclaimed amount or an Error if the contract is open.
+    fn reclaim(e: Env, series: u32, from: Address) -> Result<i128, Error>;
+
+    // Transfers the deposited collateral.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `from` - Address of the user reclaiming
     //
     // # Returns
     //
     // Reclaimed collateral amount or Error if user was liquidated or contract is open.
-    fn reclaim_col(e: Env, from: Address) -> Result<i128, Error>;
+    fn reclaim_col(e: Env, series: u32, from: Address) -> Result<i128, Error>;
 
     // Returns a user's balance.
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `to` - Address of the user's balance
     //
     // # Returns
     //
     // User balance.
-    fn balance(e: Env, to: Address) -> User;
+    fn balance(e: Env, series: u32, to: Address) -> User;
 
     // Returns the spot rate.
     //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
     // # Returns
     //
     // Spot rate value.
-    fn spot_rate(e: Env) -> i128;
+    fn spot_rate(e: Env, series: u32) -> i128;
 
     // Returns the Admin address.
     //
@@ -493,43 +1251,338 @@ pub trait SwapTrait {
 
     // Returns the two tokens and its balances
     //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
     // # Returns
     //
     // Tuple of Token Data.
-    fn tokens(e: Env) -> (Token, Token);
+    fn tokens(e: Env, series: u32) -> (Token, Token);
 
     // Set the spot rate (Only for admin)
     //
     // # Arguments
     //
+    // * `series` - Id of the series,
     // * `from` - Address of the user,
     // * `rate` - rate of spot rate
     //
     // # Returns
     //
     // None or Error.
-    fn set_spot(e: Env, from: Address, rate: i128) -> Result<(), Error>;
+    fn set_spot(e: Env, series: u32, from: Address, rate: i128) -> Result<(), Error>;
+
+    // Set the oracles used to get the spot rate (Only for admin)
+    // The spot rate is the median of the valid prices.
+    // While a series is between its near leg and the settlement of its users the oracles
+    // are locked, they can only be set to the ones proposed once their timelock ends.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the user,
+    // * `sources` - Oracles to query,
+    // * `quorum` - Minimum amount of valid prices
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn set_oracles(
+        e: Env,
+        from: Address,
+        sources: Vec<OracleSource>,
+        quorum: u32,
+    ) -> Result<(), Error>;
+
+    // Returns the oracles used to get the spot rate.
+    //
+    // # Returns
+    //
+    // Tuple: (oracle sources, quorum).
+    fn oracles(e: Env) -> (Vec<OracleSource>, u32);
+
+    // Proposes the oracles to set while they are locked (Only for admin).
+    // They can be set with set_oracles once the timelock ends, a new proposal restarts it.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the user,
+    // * `sources` - Oracles to query,
+    // * `quorum` - Minimum amount of valid prices
+    //
+    // # Returns
+    //
+    // Time from which the oracles can be set or Error.
+    fn propose_oracles(
+        e: Env,
+        from: Address,
+        sources: Vec<OracleSource>,
+        quorum: u32,
+    ) -> Result<u64, Error>;
+
+    // Returns the proposed oracles, so users can review them before they are set.
+    //
+    // # Returns
+    //
+    // Proposed oracles or None if there are no oracles proposed.
+    fn pending_oracles(e: Env) -> Option<PendingOracles>;
+
+    // Enables the Dutch auction of the liquidated collateral (Only for admin)
+    // Auction proceeds are used to pay the users before the liquidated collateral.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the user,
+    // * `auction_params` - Start price, final price and duration of the auction
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn set_auction(e: Env, from: Address, auction_params: AuctionParams) -> Result<(), Error>;
+
+    // Returns the auction parameters.
+    //
+    // # Returns
+    //
+    // Auction parameters or None if the auction is disabled.
+    fn auction(e: Env) -> Option<AuctionParams>;
+
+    // Returns the allocation policy defined at initialization.
+    //
+    // # Returns
+    //
+    // Allocation policy.
+    fn allocation_policy(e: Env) -> AllocationPolicy;
+
+    // Returns the risk parameters defined at initialization.
+    //
+    // # Returns
+    //
+    // Risk parameters.
+    fn risk_params(e: Env) -> RiskParams;
+
+    // Set the maximum age of the oracle price (Only for admin)
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the user,
+    // * `max_age` - Maximum age in seconds of a valid price
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn set_max_age(e: Env, from: Address, max_age: u64) -> Result<(), Error>;
+
+    // Returns the maximum age of the oracle price.
+    //
+    // # Returns
+    //
+    // Maximum age in seconds.
+    fn max_age(e: Env) -> u64;
+
+    // Set the window of the time weighted average price used for liquidations (Only for admin)
+    // Its 24 observations are stored at least window / 23 seconds apart, which can't be longer
+    // than the maximum age of the oracle price.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the user,
+    // * `window` - Window in seconds, 0 uses the last oracle price
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn set_twap_window(e: Env, from: Address, window: u64) -> Result<(), Error>;
+
+    // Returns the window of the time weighted average price.
+    //
+    // # Returns
+    //
+    // Window in seconds.
+    fn twap_window(e: Env) -> u64;
+
+    // Reads the oracle price and stores it as an observation for the time weighted average price.
+    // Anyone can call it.
+    //
+    // # Returns
+    //
+    // Price and timestamp of the spot rate or Error.
+    fn poke_price(e: Env) -> Result<PriceData, Error>;
 
     // Returns the current stage.
     //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
     // # Returns
     //
     // Contract Stage.
-    fn stage(e: Env) -> Stage;
+    fn stage(e: Env, series: u32) -> Stage;
 
-    // Returns the deposits made in each token.
+    // Returns a page of the deposits made in each token.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `start` - Index of the first deposit to return,
+    // * `limit` - Maximum amount of deposits to return for each token.
     //
     // # Returns
     //
     // Tuple containing arrays of deposits: (deposits for Token A, deposits for Token B).
-    fn deposits(e: Env) -> (Vec<Position>, Vec<Position>);
+    fn deposits(e: Env, series: u32, start: u32, limit: u32) -> (Vec<Position>, Vec<Position>);
 
-    // Returns the users info for liquidation
+    // Returns the amount of deposits made in each token.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
     //
     // # Returns
     //
-    // Tuple containing arrays of User Data: (Users for Token A, Users for Token B).
-    fn users(e: Env) -> (Vec<UserLiqData>, Vec<UserLiqData>);
+    // Tuple containing the amount of deposits: (deposits for Token A, deposits for Token B).
+    fn deposits_count(e: Env, series: u32) -> (u32, u32);
+
+    // Returns the liquidation info of the users with a deposit in a page of the deposits
+    // made in each token. A user with deposits in several pages is returned in each of them.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `start` - Index of the first deposit of the page,
+    // * `limit` - Maximum amount of deposits in the page for each token.
+    //
+    // # Returns
+    //
+    // Tuple containing arrays of User Data: (Users for Token A, Users for Token B)
+    // or Error if the oracle price is not valid.
+    fn users(
+        e: Env,
+        series: u32,
+        start: u32,
+        limit: u32,
+    ) -> Result<(Vec<UserLiqData>, Vec<UserLiqData>), Error>;
+
+    // Extends the TTL of the contract and of the entries of the given users until
+    // the end of the claim period. Anyone can call it.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `users` - Addresses of the users whose entries are extended.
+    fn bump_ttl(e: Env, series: u32, users: Vec<Address>);
+
+    // Returns the remaining TTL guaranteed by the last extensions.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series,
+    // * `users` - Addresses of the users to check.
+    //
+    // # Returns
+    //
+    // Tuple containing the ledgers left:
+    // (contract instance and entries shared by the series, entries of each user).
+    fn ttl(e: Env, series: u32, users: Vec<Address>) -> (u32, Vec<u32>);
+
+    // Returns the amount of users with claims left to settle.
+    //
+    // # Arguments
+    //
+    // * `series` - Id of the series.
+    //
+    // # Returns
+    //
+    // Amount of open users.
+    fn open_users(e: Env, series: u32) -> u32;
+
+    // Sweeps the rounding dust, the balance of the token above what the series still owe
+    // to their users (Only for admin)
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the admin,
+    // * `token` - Address of the token to sweep.
+    //
+    // # Returns
+    //
+    // Swept amount or Error.
+    fn sweep_dust(e: Env, from: Address, token: Address) -> Result<i128, Error>;
+
+    // Returns the amount of a token swept as dust.
+    //
+    // # Arguments
+    //
+    // * `token` - Address of the token.
+    //
+    // # Returns
+    //
+    // Swept amount.
+    fn swept(e: Env, token: Address) -> i128;
+
+    // Proposes an upgrade of the contract code (Only for admin).
+    // It can be applied with upgrade once the timelock ends, a new proposal restarts it.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller,
+    // * `new_wasm_hash` - Hash of the installed WASM to upgrade to.
+    //
+    // # Returns
+    //
+    // Time from which the upgrade can be applied or Error.
+    fn propose_upgrade(e: Env, from: Address, new_wasm_hash: BytesN<32>) -> Result<u64, Error>;
+
+    // Cancels the proposed upgrade (Only for admin).
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller.
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn cancel_upgrade(e: Env, from: Address) -> Result<(), Error>;
+
+    // Returns the proposed upgrade, so users can review it before it's applied.
+    //
+    // # Returns
+    //
+    // Proposed upgrade or None if there is no upgrade proposed.
+    fn pending_upgrade(e: Env) -> Option<PendingUpgrade>;
+
+    // Replaces the contract code with the proposed WASM once its timelock ends (Only for admin).
+    // The new code runs from the next call, migrate must be called next to update the stored data.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller,
+    // * `new_wasm_hash` - Hash of the proposed WASM.
+    //
+    // # Returns
+    //
+    // None or Error.
+    fn upgrade(e: Env, from: Address, new_wasm_hash: BytesN<32>) -> Result<(), Error>;
+
+    // Migrates the stored data to the schema version of the current code (Only for admin).
+    // Must be called right after an upgrade, until then the new code may fail to read the data.
+    //
+    // # Arguments
+    //
+    // * `from` - Address of the caller.
+    //
+    // # Returns
+    //
+    // Schema version of the stored data or Error.
+    fn migrate(e: Env, from: Address) -> Result<u32, Error>;
+
+    // Returns the schema version of the stored data.
+    //
+    // # Returns
+    //
+    // Schema version.
+    fn version(e: Env) -> u32;
 
     // Transfer amount of token from contract to address
     fn transfer_admin(
@@ -546,32 +1599,117 @@ struct Swap;
 
 #[contractimpl]
 impl SwapTrait for Swap {
-    fn initialize(
-        e: Env,
-        admin: Address,
-        token_a: Address,
-        token_b: Address,
-        name_token_a: Symbol,
-        name_token_b: Symbol,
-        forward_rate: i128,
-        duration: u64,
-    ) -> Result<i128, Error> {
+    fn initialize(e: Env, admin: Address, params: InitParams) -> Result<i128, Error> {
+        let InitParams {
+            token_a,
+            token_b,
+            name_token_a,
+            name_token_b,
+            forward_rate,
+            duration,
+            oracle,
+            risk_params,
+            allocation_policy,
+        } = params;
         match get_admin(&e) {
             Some(_) => Err(Error::ContractAlreadyInitialized),
             None => {
-                put_admin(&e, admin);
-                init_token_a(&e, &token_a, name_token_a);
-                init_token_b(&e, &token_b, name_token_b);
-                put_forward_rate(&e, forward_rate);
-                put_init_time(&e);
-                put_time_to_mature(&e, duration);
-                Ok(set_spot_price(&e).price)
+                if token_a == token_b {
+                    return Err(Error::SameTokens);
+                }
+                if forward_rate <= 0 {
+                    return Err(Error::InvalidForwardRate);
+                }
+                if duration == 0 {
+                    return Err(Error::InvalidDuration);
+                }
+                if !is_valid_risk_params(&risk_params) {
+                    return Err(Error::InvalidRiskParams);
+                }
+                if !is_valid_duration(&e, duration, &risk_params) {
+                    return Err(Error::InvalidDuration);
+                }
+                if !is_token_name(&e, &token_a, &name_token_a)
+                    || !is_token_name(&e, &token_b, &name_token_b)
+                {
+                    return Err(Error::TokenNameMismatch);
+                }
+                let decimals_a = get_token_decimals(&e, &token_a)?;
+                let decimals_b = get_token_decimals(&e, &token_b)?;
+                put_admin(&e, admin.clone());
+                init_token_a(&e, 0, &token_a, name_token_a.clone(), decimals_a);
+                init_token_b(&e, 0, &token_b, name_token_b.clone(), decimals_b);
+                put_series(&e, 0, forward_rate, duration);
+                put_risk_params(&e, &risk_params);
+                put_allocation_policy(&e, allocation_policy);
+                let source = OracleSource {
+                    address: oracle,
+                    function: Symbol::new(&e, ORACLE_FUNCTION),
+                    base_asset: Asset::Other(name_token_a),
+                    quote_asset: Asset::Other(name_token_b),
+                };
+                let sources = vec![&e, source];
+                let oracle_decimals = get_sources_decimals(&e, &sources)?;
+                put_oracles(&e, &sources, &oracle_decimals, 1);
+                put_max_price_age(&e, MAX_PRICE_AGE);
+                put_twap_window(&e, TWAP_WINDOW);
+                put_schema_version(&e, SCHEMA_VERSION);
+                let spot_rate = init_spot_rate(&e, 0)?;
+                bump_series(&e, 0);
+                emit_initialize(&e, &admin, forward_rate, duration, spot_rate);
+                Ok(spot_rate)
             }
         }
     }
 
+    fn add_series(e: Env, from: Address, forward_rate: i128, duration: u64) -> Result<u32, Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if forward_rate <= 0 {
+            return Err(Error::InvalidForwardRate);
+        }
+
+        if !is_valid_duration(&e, duration, &get_risk_params(&e)) {
+            return Err(Error::InvalidDuration);
+        }
+
+        let series = get_series_count(&e);
+        let token_a_data = get_token_a(&e, 0);
+        let token_b_data = get_token_b(&e, 0);
+        init_token_a(
+            &e,
+            series,
+            &token_a_data.address,
+            token_a_data.name,
+            token_a_data.decimals,
+        );
+        init_token_b(
+            &e,
+            series,
+            &token_b_data.address,
+            token_b_data.name,
+            token_b_data.decimals,
+        );
+        put_series(&e, series, forward_rate, duration);
+        let spot_rate = init_spot_rate(&e, series)?;
+        bump_series(&e, series);
+        emit_add_series(&e, &from, series, forward_rate, duration, spot_rate);
+        Ok(series)
+    }
+
+    fn series_count(e: Env) -> u32 {
+        get_series_count(&e)
+    }
+
     fn init_pos(
         e: Env,
+        series: u32,
         from: Address,
         positions_token_a: u64,
         positions_token_b: u64,
@@ -579,24 +1717,118 @@ impl SwapTrait for Swap {
     ) -> Result<i128, Error> {
         from.require_auth();
 
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+
         if !is_authorized(&e, &from) {
             return Err(Error::Unauthorized);
         }
 
-        init_position_a(&e, positions_token_a, amount_deposit_token_a);
         let amount_deposit_token_b = calculate_amount_deposit_token_b(
             &e,
+            series,
             positions_token_a,
             positions_token_b,
             amount_deposit_token_a,
+        )?;
+        let cap_a = checked_mul(positions_token_a as i128, amount_deposit_token_a)?;
+        let cap_b = checked_mul(positions_token_b as i128, amount_deposit_token_b)?;
+        configure_positions(
+            &e,
+            series,
+            &PositionData {
+                min_deposit: amount_deposit_token_a,
+                max_deposit: cap_a,
+                cap: cap_a,
+            },
+            &PositionData {
+                min_deposit: amount_deposit_token_b,
+                max_deposit: cap_b,
+                cap: cap_b,
+            },
+        )?;
+        emit_init_pos(
+            &e,
+            series,
+            &from,
+            positions_token_a,
+            positions_token_b,
+            amount_deposit_token_a,
+            amount_deposit_token_b,
         );
-        init_position_b(&e, positions_token_b, amount_deposit_token_b);
 
         Ok(amount_deposit_token_b)
     }
 
+    fn init_limits(
+        e: Env,
+        series: u32,
+        from: Address,
+        position_a: PositionData,
+        position_b: PositionData,
+    ) -> Result<(), Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        configure_positions(&e, series, &position_a, &position_b)?;
+        emit_init_limits(&e, series, &from, &position_a, &position_b);
+        Ok(())
+    }
+
+    fn limits(e: Env, series: u32) -> (PositionData, PositionData) {
+        (get_position_a(&e, series), get_position_b(&e, series))
+    }
+
+    fn increase_capacity(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if has_near_leg_executed(&e, series) {
+            return Err(Error::WrongStageToConfigure);
+        }
+
+        if !is_valid_token(&e, token.clone()) {
+            return Err(Error::InvalidToken);
+        }
+
+        if amount <= 0 {
+            return Err(Error::InvalidPositionData);
+        }
+
+        let mut position_data = get_position_data(&e, series, &token);
+        position_data.cap = checked_add(position_data.cap, amount)?;
+        match token == get_token_a_address(&e) {
+            true => set_position_a(&e, series, &position_data),
+            false => set_position_b(&e, series, &position_data),
+        }
+        emit_increase_capacity(&e, series, &from, &token, amount, position_data.cap);
+        Ok(position_data.cap)
+    }
+
     fn deposit(
         e: Env,
+        series: u32,
         from: Address,
         token: Address,
         amount: i128,
@@ -604,48 +1836,65 @@ impl SwapTrait for Swap {
     ) -> Result<(i128, i128), Error> {
         from.require_auth();
 
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
         if !is_valid_token(&e, token.clone()) {
             return Err(Error::InvalidToken);
         }
 
-        let near_leg_executed = has_near_leg_executed(&e);
-        let position_data = get_position_data(&e, &token);
-        let min_collateral = calculate_percentage(amount, COLLATERAL_BUFFER);
+        let near_leg_executed = has_near_leg_executed(&e, series);
+        let position_data = get_position_data(&e, series, &token);
+        let user_deposited_amount = get_deposited_amount(&e, series, &from) + amount;
+        let token_deposited_amount = match token == get_token_a_address(&e) {
+            true => get_token_a(&e, series).deposited_amount,
+            false => get_token_b(&e, series).deposited_amount,
+        };
+        let min_collateral =
+            calculate_percentage(amount, get_risk_params(&e).collateral_buffer, Rounding::Up)?;
 
         if collateral < min_collateral {
             return Err(Error::InsufficientCollateral);
         }
 
-        if !near_leg_executed && !are_positions_open(&position_data) {
-            return Err(Error::AllPositionsAreUsed);
+        if !near_leg_executed
+            && amount != 0
+            && (amount < 0
+                || user_deposited_amount < position_data.min_deposit
+                || user_deposited_amount > position_data.max_deposit)
+        {
+            return Err(Error::DepositAmountOutOfRange);
         }
 
-        if !near_leg_executed && amount != 0 && amount != position_data.deposit_amount {
-            return Err(Error::DepositAmountDoesntMatchPosition);
+        if !near_leg_executed && token_deposited_amount + amount > position_data.cap {
+            return Err(Error::DepositCapExceeded);
         }
 
         if near_leg_executed && amount != 0 {
             return Err(Error::CollateralOnlyCanBeDeposited);
         }
 
-        match get_deposited_token(&e, &from) {
-            Some(p) => {
-                if p != token {
+        let mut user = match try_load_user(&e, series, &from) {
+            Some(user) => {
+                if user.deposited_token != token {
                     return Err(Error::DifferentDepositedToken);
                 }
+                user
             }
-            None => put_deposited_token(&e, &from, &token),
-        }
+            None => new_user(&token),
+        };
 
         if !near_leg_executed && amount > 0 {
-            let position_index = create_position(&e, &from, &token);
+            let position_index =
+                create_position(&e, series, &from, &token, amount, token_deposited_amount);
 
             token::Client::new(&e, &token).transfer(&from, &e.current_contract_address(), &amount);
-            put_deposited_amount(&e, &from, amount);
-            add_token_deposited_amount(&e, &token, amount);
+            user.deposited_amount += amount;
+            add_token_deposited_amount(&e, series, &token, amount);
 
-            set_position_valid(&e, position_index, &token);
-            ocupy_one_position(&e, &token, &position_data);
+            set_position_valid(&e, series, position_index, &token);
         }
 
         if collateral > 0 {
@@ -654,150 +1903,393 @@ impl SwapTrait for Swap {
                 &e.current_contract_address(),
                 &collateral,
             );
-            put_collateral(&e, &from, collateral);
-            add_token_collateral_amount(&e, &token, collateral);
-        }
+            user.collateral += collateral;
+            add_token_collateral_amount(&e, series, &token, collateral);
+        }
+
+        if amount > 0 || collateral > 0 {
+            open_user(&e, series, &mut user);
+        }
+        save_user(&e, series, &from, &user);
+
+        let total_deposit = (user.deposited_amount, user.collateral);
+        emit_deposit(&e, series, &from, &token, amount, collateral, total_deposit);
+        Ok(total_deposit)
+    }
+
+    fn cancel_deposit(e: Env, series: u32, from: Address) -> Result<(i128, i128), Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
+        if has_near_leg_executed(&e, series) {
+            return Err(Error::WrongStageToCancel);
+        }
+
+        let token = match get_deposited_token(&e, series, &from) {
+            Some(token) => token,
+            None => return Err(Error::NothingToCancel),
+        };
+        let amount = get_deposited_amount(&e, series, &from);
+        let collateral = get_collateral(&e, series, &from);
+
+        if amount == 0 && collateral == 0 {
+            return Err(Error::NothingToCancel);
+        }
+
+        let is_a = token == get_token_a_address(&e);
+        for index in get_user_positions(&e, series, &from, is_a).iter() {
+            if get_used_position(&e, series, is_a, index).is_valid {
+                cancel_position(&e, series, is_a, index);
+            }
+        }
+
+        update_user(&e, series, &from, |user| {
+            user.deposited_amount -= amount;
+            user.collateral -= collateral;
+            close_user(&e, series, user);
+            Ok(())
+        })?;
+        add_token_deposited_amount(&e, series, &token, -amount);
+        add_token_collateral_amount(&e, series, &token, -collateral);
+
+        transfer(&e, token.clone(), from.clone(), amount + collateral);
+        emit_cancel_deposit(&e, series, &from, &token, amount, collateral);
+        Ok((amount, collateral))
+    }
+
+    fn swap(e: Env, series: u32, from: Address) -> Result<i128, Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
+        if get_stage(&e, series) != Stage::Swap {
+            return Err(Error::WrongStageToSwap);
+        }
+
+        let mut swap_amount: i128 = 0;
+        let spot_rate: i128 = get_spot_rate(&e, series);
+
+        if let Some(token) = get_deposited_token(&e, series, &from) {
+            if token == get_token_a_address(&e) {
+                let used_deposited_amount = get_used_deposited_amount(&e, series, &from)?;
+                let exp_swap_amount = convert_amount_token_a_to_b(
+                    &e,
+                    used_deposited_amount,
+                    spot_rate,
+                    Rounding::Down,
+                )?;
+
+                let token_b_data = get_token_b(&e, series);
+                let token_b_available_amount =
+                    token_b_data.deposited_amount - token_b_data.swapped_amount;
+
+                swap_amount = min(exp_swap_amount, token_b_available_amount);
+                transfer_b(&e, &from, swap_amount);
+                let total_swapped = update_user(&e, series, &from, |user| {
+                    user.swapped_amount += swap_amount;
+                    Ok(user.swapped_amount)
+                })?;
+                add_token_swapped_amount(&e, series, &token_b_data.address, swap_amount);
+                emit_swap(
+                    &e,
+                    series,
+                    &from,
+                    &token_b_data.address,
+                    swap_amount,
+                    total_swapped,
+                );
+            } else {
+                let used_deposited_amount = get_used_deposited_amount(&e, series, &from)?;
+                let exp_swap_amount = convert_amount_token_b_to_a(
+                    &e,
+                    used_deposited_amount,
+                    spot_rate,
+                    Rounding::Down,
+                )?;
+
+                let token_a_data = get_token_a(&e, series);
+                let token_a_available_amount =
+                    token_a_data.deposited_amount - token_a_data.swapped_amount;
+
+                swap_amount = min(exp_swap_amount, token_a_available_amount);
+                transfer_a(&e, &from, swap_amount);
+                let total_swapped = update_user(&e, series, &from, |user| {
+                    user.swapped_amount += swap_amount;
+                    Ok(user.swapped_amount)
+                })?;
+                add_token_swapped_amount(&e, series, &token_a_data.address, swap_amount);
+                emit_swap(
+                    &e,
+                    series,
+                    &from,
+                    &token_a_data.address,
+                    swap_amount,
+                    total_swapped,
+                );
+            }
+        }
+
+        Ok(swap_amount)
+    }
+
+    fn reclaim(e: Env, series: u32, from: Address) -> Result<i128, Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
+        if get_stage(&e, series) == Stage::Deposit {
+            return Err(Error::TimeNotReached);
+        }
+
+        let used_deposited_amount = get_used_deposited_amount(&e, series, &from)?;
+
+        update_user(&e, series, &from, |user| {
+            let amount = user.deposited_amount - used_deposited_amount - user.reclaimed_amount;
+            if amount <= 0 {
+                settle_user(&e, series, &from, user)?;
+                return Ok(0);
+            }
+
+            let token = user.deposited_token.clone();
+            transfer(&e, token.clone(), from.clone(), amount);
+            add_token_reclaimed_amount(&e, series, &token, amount);
+            user.reclaimed_amount += amount;
+            emit_reclaim(&e, series, &from, &token, amount, user.reclaimed_amount);
+
+            settle_user(&e, series, &from, user)?;
+            Ok(amount)
+        })
+    }
+
+    fn reclaim_col(e: Env, series: u32, from: Address) -> Result<i128, Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
+        if get_stage(&e, series) == Stage::Deposit {
+            return Err(Error::TimeNotReached);
+        }
+
+        let used_deposited_amount = get_used_deposited_amount(&e, series, &from)?;
+        let token_a_address = get_token_a_address(&e);
+        // The collateral seized by a liquidation is already booked as liquidated collateral
+        let min_col = if used_deposited_amount != 0
+            && !is_liquidated(&e, series, &from)
+            && !max_time_reached(&e, series)
+        {
+            let spot_rate = read_liquidation_price(&e)?;
+            let deposited_token = get_deposited_token(&e, series, &from).unwrap();
+            get_min_collateral(
+                &e,
+                series,
+                &from,
+                spot_rate,
+                token_a_address == deposited_token,
+            )?
+        } else {
+            0
+        };
+
+        update_user(&e, series, &from, |user| {
+            let collateral_amount = user.collateral - user.liquidated_collateral;
+            let withdraw_amount = collateral_amount - min_col - user.withdrawn_collateral;
+            if withdraw_amount <= 0 {
+                settle_user(&e, series, &from, user)?;
+                return Ok(0);
+            }
+
+            let token = user.deposited_token.clone();
+            transfer(&e, token.clone(), from.clone(), withdraw_amount);
+            user.withdrawn_collateral += withdraw_amount;
+            add_token_withdrawn_collateral(&e, series, &token, withdraw_amount);
+            emit_reclaim_col(
+                &e,
+                series,
+                &from,
+                &token,
+                withdraw_amount,
+                user.withdrawn_collateral,
+            );
 
-        Ok(get_user_deposit(&e, &from))
+            settle_user(&e, series, &from, user)?;
+            Ok(withdraw_amount)
+        })
     }
 
-    fn swap(e: Env, from: Address) -> Result<i128, Error> {
-        from.require_auth();
-
-        if get_stage(&e) != Stage::Swap {
-            return Err(Error::WrongStageToSwap);
-        }
-
-        let mut swap_amount: i128 = 0;
-        let spot_rate: i128 = get_spot_rate(&e);
-
-        if let Some(token) = get_deposited_token(&e, &from) {
-            if token == get_token_a_address(&e) {
-                let used_deposited_amount = get_used_deposited_amount(&e, &from);
-                let exp_swap_amount = convert_amount_token_a_to_b(used_deposited_amount, spot_rate);
+    fn balance(e: Env, series: u32, to: Address) -> User {
+        to.require_auth();
+        get_user_balance(&e, series, &to)
+    }
 
-                let token_b_data = get_token_b(&e);
-                let token_b_available_amount =
-                    token_b_data.deposited_amount - token_b_data.swapped_amount;
+    fn liquidate(e: Env, series: u32, to: Address, from: Address) -> Result<i128, Error> {
+        from.require_auth();
 
-                swap_amount = min(exp_swap_amount, token_b_available_amount);
-                transfer_b(&e, &from, swap_amount);
-                put_swapped_amount(&e, &from, swap_amount);
-                add_token_swapped_amount(&e, &token_b_data.address, swap_amount);
-            } else {
-                let used_deposited_amount = get_used_deposited_amount(&e, &from);
-                let exp_swap_amount = convert_amount_token_b_to_a(used_deposited_amount, spot_rate);
+        check_series(&e, series)?;
 
-                let token_a_data = get_token_a(&e);
-                let token_a_available_amount =
-                    token_a_data.deposited_amount - token_a_data.swapped_amount;
+        bump_series(&e, series);
+        bump_user(&e, series, &to);
 
-                swap_amount = min(exp_swap_amount, token_a_available_amount);
-                transfer_a(&e, &from, swap_amount);
-                put_swapped_amount(&e, &from, swap_amount);
-                add_token_swapped_amount(&e, &token_a_data.address, swap_amount);
-            }
+        let spot_price: i128 = read_liquidation_price(&e)?;
+        if get_deposited_token(&e, series, &to).is_none() {
+            return Ok(0);
         }
-
-        Ok(swap_amount)
+        update_user(&e, series, &to, |user| {
+            let reward_amount = liquidate_user(&e, series, &to, &from, user, spot_price)?;
+            settle_user(&e, series, &to, user)?;
+            Ok(reward_amount)
+        })
     }
 
-    fn reclaim(e: Env, from: Address) -> Result<i128, Error> {
+    fn buy_collateral(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+        max_cost: i128,
+    ) -> Result<(i128, i128), Error> {
         from.require_auth();
 
-        if get_stage(&e) == Stage::Deposit {
-            return Err(Error::TimeNotReached);
-        }
+        check_series(&e, series)?;
 
-        let deposited_amount = get_deposited_amount(&e, &from);
-        let used_deposited_amount = get_used_deposited_amount(&e, &from);
-        let reclaimed_amount = get_reclaimed_amount(&e, &from);
-        let amount = deposited_amount - used_deposited_amount - reclaimed_amount;
+        bump_series(&e, series);
 
-        if amount <= 9 {
-            return Ok(0);
-        }
+        let auction_params = match get_auction_params(&e) {
+            Some(auction_params) => auction_params,
+            None => return Err(Error::AuctionDisabled),
+        };
 
-        if let Some(token) = get_deposited_token(&e, &from) {
-            if token == get_token_a_address(&e) {
-                transfer_a(&e, &from, amount);
-                add_token_reclaimed_amount(&e, &token, amount);
-                put_reclaimed_amount(&e, &from, amount);
-            } else {
-                transfer_b(&e, &from, amount);
-                add_token_reclaimed_amount(&e, &token, amount);
-                put_reclaimed_amount(&e, &from, amount);
-            }
+        if !is_valid_token(&e, token.clone()) {
+            return Err(Error::InvalidToken);
         }
 
-        Ok(amount)
-    }
+        let (token_data, counter_token) = match token == get_token_a_address(&e) {
+            true => (get_token_a(&e, series), get_token_b_address(&e)),
+            false => (get_token_b(&e, series), get_token_a_address(&e)),
+        };
+        let amount = min(amount, get_auctionable_collateral(&token_data));
 
-    fn reclaim_col(e: Env, from: Address) -> Result<i128, Error> {
-        from.require_auth();
+        if amount <= 0 {
+            return Err(Error::NoCollateralToAuction);
+        }
 
-        if get_stage(&e) == Stage::Deposit {
-            return Err(Error::TimeNotReached);
+        let price = get_auction_price(&e, &token_data, &auction_params)?;
+        let counter_decimals = match token == get_token_a_address(&e) {
+            true => get_token_b(&e, series).decimals,
+            false => get_token_a(&e, series).decimals,
+        };
+        let cost = mul_div_scaled(
+            amount,
+            price,
+            SCALE,
+            token_data.decimals,
+            counter_decimals,
+            Rounding::Up,
+        )?;
+
+        if cost > max_cost {
+            return Err(Error::AuctionPriceTooHigh);
         }
 
-        let used_deposited_amount = get_used_deposited_amount(&e, &from);
-        let token_a_address = get_token_a_address(&e);
-        let min_col =
-            if used_deposited_amount != 0 && (is_liquidated(&e, &from) || !max_time_reached(&e)) {
-                let spot_rate = get_oracle_spot_price(&e).price;
-                let deposited_token = get_deposited_token(&e, &from).unwrap();
-                get_min_collateral(&e, &from, spot_rate, token_a_address == deposited_token)
-            } else {
-                0
-            };
+        token::Client::new(&e, &counter_token).transfer(
+            &from,
+            &e.current_contract_address(),
+            &cost,
+        );
+        transfer(&e, token.clone(), from.clone(), amount);
+        add_token_auctioned_collateral(&e, series, &token, amount);
+        add_token_auction_proceeds(&e, series, &counter_token, cost);
+        emit_buy_collateral(&e, series, &from, &token, amount, cost);
+        Ok((amount, cost))
+    }
 
-        let collateral_amount = get_collateral(&e, &from);
-        let withdrawn_collateral_amount = get_withdrawn_collateral(&e, &from);
-        let withdraw_amount = collateral_amount - min_col - withdrawn_collateral_amount;
+    fn auction_price(e: Env, series: u32, token: Address) -> Result<i128, Error> {
+        check_series(&e, series)?;
 
-        if withdraw_amount <= 0 {
-            return Ok(0);
-        }
+        let auction_params = match get_auction_params(&e) {
+            Some(auction_params) => auction_params,
+            None => return Err(Error::AuctionDisabled),
+        };
 
-        if let Some(token) = get_deposited_token(&e, &from) {
-            if token == token_a_address {
-                transfer_a(&e, &from, withdraw_amount);
-                put_withdrawn_collateral(&e, &from, withdraw_amount);
-                add_token_withdrawn_collateral(&e, &token, withdraw_amount);
-            } else {
-                transfer_b(&e, &from, withdraw_amount);
-                put_withdrawn_collateral(&e, &from, withdraw_amount);
-                add_token_withdrawn_collateral(&e, &token, withdraw_amount);
-            }
+        if !is_valid_token(&e, token.clone()) {
+            return Err(Error::InvalidToken);
         }
 
-        Ok(withdraw_amount)
+        let token_data = match token == get_token_a_address(&e) {
+            true => get_token_a(&e, series),
+            false => get_token_b(&e, series),
+        };
+        get_auction_price(&e, &token_data, &auction_params)
     }
 
-    fn balance(e: Env, to: Address) -> User {
-        to.require_auth();
-        get_user_balance(&e, &to)
+    fn health(e: Env, series: u32, to: Address) -> Result<UserHealth, Error> {
+        check_series(&e, series)?;
+
+        let spot_rate = get_liquidation_price(&e)?;
+        let deposited_token = get_deposited_token(&e, series, &to);
+        let is_deposit_token_a = deposited_token == Some(get_token_a_address(&e));
+        let collateral = get_available_collateral(&e, series, &to);
+        let min_collateral = get_min_collateral(&e, series, &to, spot_rate, is_deposit_token_a)?;
+        let health_factor = match min_collateral > 0 {
+            true => mul_div(collateral, SCALE, min_collateral, Rounding::Down)?,
+            false => i128::MAX,
+        };
+        let expired_and_not_repaid =
+            max_time_reached(&e, series) && has_not_repaid(&e, series, &to)?;
+
+        Ok(UserHealth {
+            collateral,
+            min_collateral,
+            health_factor,
+            liquidation_price: get_liquidation_spot_rate(
+                &e,
+                series,
+                &to,
+                collateral,
+                is_deposit_token_a,
+            )?,
+            is_liquidatable: deposited_token.is_some()
+                && !is_liquidated(&e, series, &to)
+                && (min_collateral > collateral || expired_and_not_repaid),
+        })
     }
 
-    fn liquidate(e: Env, to: Address, from: Address) -> i128 {
+    fn repay(
+        e: Env,
+        series: u32,
+        from: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<(i128, i128), Error> {
         from.require_auth();
 
-        let spot_price: i128 = get_oracle_spot_price(&e).price;
-        liquidate_user(&e, &to, &from, spot_price)
-    }
+        check_series(&e, series)?;
 
-    fn repay(e: Env, from: Address, token: Address, amount: i128) -> Result<(i128, i128), Error> {
-        from.require_auth();
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
 
         if !is_valid_token(&e, token.clone()) {
             return Err(Error::InvalidToken);
         }
 
-        if is_liquidated(&e, &from) {
+        if is_liquidated(&e, series, &from) {
             return Err(Error::LiquidatedUser);
         }
 
-        let deposited_token = get_deposited_token(&e, &from).unwrap();
+        let deposited_token = get_deposited_token(&e, series, &from).unwrap();
         let token_a_address = get_token_a_address(&e);
         let token_b_address = get_token_b_address(&e);
         if deposited_token == token_a_address {
@@ -810,8 +2302,8 @@ impl SwapTrait for Swap {
             }
         }
 
-        let prev_total_amount_to_repay = get_user_amount_to_repay(&e, &from);
-        let prev_total_returned_amount = get_returned_amount(&e, &from);
+        let prev_total_amount_to_repay = get_user_amount_to_repay(&e, series, &from)?;
+        let prev_total_returned_amount = get_returned_amount(&e, series, &from);
         let repay_amount = min(
             amount,
             prev_total_amount_to_repay - prev_total_returned_amount,
@@ -826,164 +2318,583 @@ impl SwapTrait for Swap {
             &e.current_contract_address(),
             &repay_amount,
         );
-        put_returned_amount(&e, &from, repay_amount);
-        add_token_returned_amount(&e, &token, repay_amount);
-
-        let total_returned_amount = get_returned_amount(&e, &from);
-        let total_amount_to_repay = get_user_amount_to_repay(&e, &from);
+        let total_returned_amount = update_user(&e, series, &from, |user| {
+            user.returned_amount += repay_amount;
+            Ok(user.returned_amount)
+        })?;
+        add_token_returned_amount(&e, series, &token, repay_amount);
+
+        let total_amount_to_repay = get_user_amount_to_repay(&e, series, &from)?;
+        emit_repay(
+            &e,
+            series,
+            &from,
+            &token,
+            repay_amount,
+            total_returned_amount,
+            total_amount_to_repay,
+        );
         Ok((total_returned_amount, total_amount_to_repay))
     }
 
-    fn withdraw(e: Env, from: Address) -> Result<(i128, i128), Error> {
+    fn withdraw(e: Env, series: u32, from: Address) -> Result<(i128, i128), Error> {
         from.require_auth();
 
-        let forward_rate = get_forward_rate(&e);
-        let spot_rate = get_oracle_spot_price(&e).price;
-        let og_spot_rate = get_spot_rate(&e);
-        let returned_amount = get_returned_amount(&e, &from);
-        let withdrawn_amount = get_withdrawn_amount(&e, &from);
-        let deposited_token = get_deposited_token(&e, &from).unwrap();
-        let token_a_data = get_token_a(&e);
-        let token_b_data = get_token_b(&e);
-        let mut withdraw_amount_a: i128 = 0;
-        let mut withdraw_amount_b: i128 = 0;
-
-        if !max_time_reached(&e) {
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+        bump_user(&e, series, &from);
+
+        let forward_rate = get_forward_rate(&e, series);
+        let spot_rate = read_spot_price(&e)?.price;
+        let og_spot_rate = get_spot_rate(&e, series);
+        let returned_amount = get_returned_amount(&e, series, &from);
+        let withdrawn_amount = get_withdrawn_amount(&e, series, &from);
+        let deposited_token = get_deposited_token(&e, series, &from).unwrap();
+        let token_a_data = get_token_a(&e, series);
+        let token_b_data = get_token_b(&e, series);
+
+        if !max_time_reached(&e, series) {
             return Err(Error::TimeNotReached);
         }
 
-        if is_liquidated(&e, &from) {
+        if is_liquidated(&e, series, &from) {
             return Err(Error::LiquidatedUser);
         }
 
-        if deposited_token == token_a_data.address {
-            let token_a_available_amount =
-                token_a_data.returned_amount - token_a_data.withdrawn_amount;
-            let converted_returned_amount =
-                convert_amount_token_b_to_a(returned_amount, forward_rate);
-            let exp_withdraw = max(converted_returned_amount - withdrawn_amount, 0);
-            withdraw_amount_a = min(exp_withdraw, token_a_available_amount);
-
-            if withdraw_amount_a > 0 {
-                transfer_a(&e, &from, withdraw_amount_a);
-                add_token_withdrawn_amount(&e, &deposited_token, withdraw_amount_a);
-                put_withdrawn_amount(&e, &from, withdraw_amount_a);
-            }
+        update_user(&e, series, &from, |user| {
+            let mut withdraw_amount_a: i128 = 0;
+            let mut withdraw_amount_b: i128 = 0;
+            let mut used_liq_collateral: i128 = 0;
 
-            if exp_withdraw > 0 && exp_withdraw > withdraw_amount_a {
-                //    return token b to compensate
-                let token_b_address = token_b_data.address;
-                let rem_withdraw = exp_withdraw - withdraw_amount_a;
-                let exp_withdraw_amount_b = convert_amount_token_a_to_b(rem_withdraw, spot_rate);
-                let used_returned = convert_amount_token_a_to_b(withdraw_amount_a, og_spot_rate);
-                let use_from_returned = min(returned_amount - used_returned, exp_withdraw_amount_b);
-                let max_collateral_available =
-                    token_b_data.liquidated_collateral - token_b_data.used_liq_collateral;
-                let use_from_col = min(
-                    exp_withdraw_amount_b - use_from_returned,
-                    max_collateral_available,
+            if deposited_token == token_a_data.address {
+                let token_a_available_amount =
+                    token_a_data.returned_amount - token_a_data.withdrawn_amount;
+                let converted_returned_amount =
+                    convert_amount_token_b_to_a(&e, returned_amount, forward_rate, Rounding::Down)?;
+                let exp_withdraw = max(converted_returned_amount - withdrawn_amount, 0);
+                let withdraw_from_returned = min(exp_withdraw, token_a_available_amount);
+                let withdraw_from_proceeds = min(
+                    exp_withdraw - withdraw_from_returned,
+                    token_a_data.auction_proceeds - token_a_data.used_auction_proceeds,
                 );
-                withdraw_amount_b = use_from_returned + use_from_col;
-                let converted_withdraw_amount_b =
-                    convert_amount_token_b_to_a(withdraw_amount_b, spot_rate) + 1;
-
-                transfer_b(&e, &from, withdraw_amount_b);
-                put_withdrawn_amount(&e, &from, converted_withdraw_amount_b);
-                add_token_withdrawn_amount(&e, &token_b_address, use_from_returned);
-                add_token_used_liq_collateral(&e, &token_b_address, use_from_col);
-            }
-        } else {
-            let token_b_available_amount =
-                token_b_data.returned_amount - token_b_data.withdrawn_amount;
-            let converted_returned_amount =
-                convert_amount_token_a_to_b(returned_amount, forward_rate);
-            let exp_withdraw = max(converted_returned_amount - withdrawn_amount, 0);
-            withdraw_amount_b = min(exp_withdraw, token_b_available_amount);
-
-            if withdraw_amount_b > 0 {
-                transfer_b(&e, &from, withdraw_amount_b);
-                add_token_withdrawn_amount(&e, &deposited_token, withdraw_amount_b);
-                put_withdrawn_amount(&e, &from, withdraw_amount_b);
-            }
+                withdraw_amount_a = withdraw_from_returned + withdraw_from_proceeds;
+
+                if withdraw_amount_a > 0 {
+                    transfer_a(&e, &from, withdraw_amount_a);
+                    add_token_withdrawn_amount(
+                        &e,
+                        series,
+                        &deposited_token,
+                        withdraw_from_returned,
+                    );
+                    add_token_used_auction_proceeds(
+                        &e,
+                        series,
+                        &deposited_token,
+                        withdraw_from_proceeds,
+                    );
+                    user.withdrawn_amount += withdraw_amount_a;
+                }
 
-            if exp_withdraw > 0 && exp_withdraw > withdraw_amount_b {
-                let rem_withdraw = exp_withdraw - withdraw_amount_b;
-                let exp_withdraw_amount_a = convert_amount_token_b_to_a(rem_withdraw, spot_rate);
-                let used_returned = convert_amount_token_b_to_a(withdraw_amount_b, og_spot_rate);
-                let use_from_returned = min(returned_amount - used_returned, exp_withdraw_amount_a);
-                let max_collateral_available =
-                    token_a_data.liquidated_collateral - token_a_data.used_liq_collateral;
-                let use_from_col = min(
-                    exp_withdraw_amount_a - use_from_returned,
-                    max_collateral_available,
+                if exp_withdraw > 0 && exp_withdraw > withdraw_amount_a {
+                    //    return token b to compensate
+                    let token_b_address = token_b_data.address.clone();
+                    let rem_withdraw = exp_withdraw - withdraw_amount_a;
+                    let exp_withdraw_amount_b =
+                        convert_amount_token_a_to_b(&e, rem_withdraw, spot_rate, Rounding::Down)?;
+                    // The liquidated counterparty won't withdraw the returned amount matching the proceeds
+                    let used_returned = convert_amount_token_a_to_b(
+                        &e,
+                        withdraw_from_returned,
+                        og_spot_rate,
+                        Rounding::Up,
+                    )?;
+                    let use_from_returned = min(
+                        max(returned_amount - used_returned, 0),
+                        exp_withdraw_amount_b,
+                    );
+                    let max_collateral_available = get_auctionable_collateral(&token_b_data);
+                    let use_from_col = min(
+                        exp_withdraw_amount_b - use_from_returned,
+                        max_collateral_available,
+                    );
+                    withdraw_amount_b = use_from_returned + use_from_col;
+                    let converted_withdraw_amount_b = convert_amount_token_b_to_a(
+                        &e,
+                        withdraw_amount_b,
+                        spot_rate,
+                        Rounding::Up,
+                    )?;
+
+                    transfer_b(&e, &from, withdraw_amount_b);
+                    user.withdrawn_amount += converted_withdraw_amount_b;
+                    add_token_withdrawn_amount(&e, series, &token_b_address, use_from_returned);
+                    add_token_used_liq_collateral(&e, series, &token_b_address, use_from_col);
+                    used_liq_collateral = use_from_col;
+                }
+            } else {
+                let token_b_available_amount =
+                    token_b_data.returned_amount - token_b_data.withdrawn_amount;
+                let converted_returned_amount =
+                    convert_amount_token_a_to_b(&e, returned_amount, forward_rate, Rounding::Down)?;
+                let exp_withdraw = max(converted_returned_amount - withdrawn_amount, 0);
+                let withdraw_from_returned = min(exp_withdraw, token_b_available_amount);
+                let withdraw_from_proceeds = min(
+                    exp_withdraw - withdraw_from_returned,
+                    token_b_data.auction_proceeds - token_b_data.used_auction_proceeds,
                 );
-                withdraw_amount_a = use_from_returned + use_from_col;
-                let converted_withdraw_amount_a =
-                    convert_amount_token_a_to_b(withdraw_amount_a, spot_rate) + 1;
-
-                transfer_a(&e, &from, withdraw_amount_a);
-                put_withdrawn_amount(&e, &from, converted_withdraw_amount_a);
-                add_token_withdrawn_amount(&e, &token_a_data.address, use_from_returned);
-                add_token_used_liq_collateral(&e, &token_a_data.address, use_from_col);
+                withdraw_amount_b = withdraw_from_returned + withdraw_from_proceeds;
+
+                if withdraw_amount_b > 0 {
+                    transfer_b(&e, &from, withdraw_amount_b);
+                    add_token_withdrawn_amount(
+                        &e,
+                        series,
+                        &deposited_token,
+                        withdraw_from_returned,
+                    );
+                    add_token_used_auction_proceeds(
+                        &e,
+                        series,
+                        &deposited_token,
+                        withdraw_from_proceeds,
+                    );
+                    user.withdrawn_amount += withdraw_amount_b;
+                }
+
+                if exp_withdraw > 0 && exp_withdraw > withdraw_amount_b {
+                    let rem_withdraw = exp_withdraw - withdraw_amount_b;
+                    let exp_withdraw_amount_a =
+                        convert_amount_token_b_to_a(&e, rem_withdraw, spot_rate, Rounding::Down)?;
+                    // The liquidated counterparty won't withdraw the returned amount matching the proceeds
+                    let used_returned = convert_amount_token_b_to_a(
+                        &e,
+                        withdraw_from_returned,
+                        og_spot_rate,
+                        Rounding::Up,
+                    )?;
+                    let use_from_returned = min(
+                        max(returned_amount - used_returned, 0),
+                        exp_withdraw_amount_a,
+                    );
+                    let max_collateral_available = get_auctionable_collateral(&token_a_data);
+                    let use_from_col = min(
+                        exp_withdraw_amount_a - use_from_returned,
+                        max_collateral_available,
+                    );
+                    withdraw_amount_a = use_from_returned + use_from_col;
+                    let converted_withdraw_amount_a = convert_amount_token_a_to_b(
+                        &e,
+                        withdraw_amount_a,
+                        spot_rate,
+                        Rounding::Up,
+                    )?;
+
+                    transfer_a(&e, &from, withdraw_amount_a);
+                    user.withdrawn_amount += converted_withdraw_amount_a;
+                    add_token_withdrawn_amount(
+                        &e,
+                        series,
+                        &token_a_data.address,
+                        use_from_returned,
+                    );
+                    add_token_used_liq_collateral(&e, series, &token_a_data.address, use_from_col);
+                    used_liq_collateral = use_from_col;
+                }
             }
-        }
 
-        Ok((withdraw_amount_a, withdraw_amount_b))
+            emit_withdraw(
+                &e,
+                series,
+                &from,
+                withdraw_amount_a,
+                withdraw_amount_b,
+                used_liq_collateral,
+                user.withdrawn_amount,
+            );
+            settle_user(&e, series, &from, user)?;
+            Ok((withdraw_amount_a, withdraw_amount_b))
+        })
     }
 
-    fn spot_rate(e: Env) -> i128 {
-        get_spot_rate(&e)
+    fn spot_rate(e: Env, series: u32) -> i128 {
+        get_spot_rate(&e, series)
     }
 
     fn admin(e: Env) -> Address {
         get_admin(&e).unwrap()
     }
 
-    fn near_leg(e: Env) -> Result<PriceData, Error> {
-        if !near_leg_time_reached(&e) {
+    fn near_leg(e: Env, series: u32) -> Result<PriceData, Error> {
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+
+        if !near_leg_time_reached(&e, series) {
             return Err(Error::TimeNotReached);
         }
 
-        if get_spot_rate(&e) != 0 {
+        if get_spot_rate(&e, series) != 0 {
             return Err(Error::SpotRateAlreadyDefined);
         }
 
-        Ok(set_spot_price(&e))
+        let price_data = set_spot_price(&e, series)?;
+        emit_near_leg(&e, series, price_data.price, price_data.timestamp);
+        Ok(price_data)
     }
 
-    fn tokens(e: Env) -> (Token, Token) {
-        let token_a = get_token_a(&e);
-        let token_b = get_token_b(&e);
+    fn tokens(e: Env, series: u32) -> (Token, Token) {
+        let token_a = get_token_a(&e, series);
+        let token_b = get_token_b(&e, series);
         (token_a, token_b)
     }
 
-    fn set_spot(e: Env, from: Address, amount: i128) -> Result<(), Error> {
+    fn set_spot(e: Env, series: u32, from: Address, amount: i128) -> Result<(), Error> {
+        from.require_auth();
+
+        check_series(&e, series)?;
+
+        bump_series(&e, series);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        put_spot_rate(&e, series, amount);
+        emit_set_spot(&e, series, &from, amount);
+        Ok(())
+    }
+
+    fn set_oracles(
+        e: Env,
+        from: Address,
+        sources: Vec<OracleSource>,
+        quorum: u32,
+    ) -> Result<(), Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if is_oracle_locked(&e) {
+            match get_pending_oracles(&e) {
+                Some(pending_oracles)
+                    if pending_oracles.sources == sources && pending_oracles.quorum == quorum =>
+                {
+                    if e.ledger().timestamp() < pending_oracles.unlock_time {
+                        return Err(Error::TimeNotReached);
+                    }
+                }
+                _ => return Err(Error::OracleLocked),
+            }
+        }
+
+        if quorum == 0 || quorum > sources.len() {
+            return Err(Error::InvalidQuorum);
+        }
+
+        let oracle_decimals = get_sources_decimals(&e, &sources)?;
+        put_oracles(&e, &sources, &oracle_decimals, quorum);
+        remove_pending_oracles(&e);
+        emit_set_oracles(&e, &from, &sources, quorum);
+        Ok(())
+    }
+
+    fn oracles(e: Env) -> (Vec<OracleSource>, u32) {
+        (get_oracle_sources(&e), get_oracle_quorum(&e))
+    }
+
+    fn propose_oracles(
+        e: Env,
+        from: Address,
+        sources: Vec<OracleSource>,
+        quorum: u32,
+    ) -> Result<u64, Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if quorum == 0 || quorum > sources.len() {
+            return Err(Error::InvalidQuorum);
+        }
+
+        let pending_oracles = PendingOracles {
+            sources,
+            quorum,
+            unlock_time: e.ledger().timestamp() + ORACLE_TIMELOCK,
+        };
+        put_pending_oracles(&e, &pending_oracles);
+        emit_propose_oracles(&e, &from, &pending_oracles);
+        Ok(pending_oracles.unlock_time)
+    }
+
+    fn pending_oracles(e: Env) -> Option<PendingOracles> {
+        get_pending_oracles(&e)
+    }
+
+    fn set_auction(e: Env, from: Address, auction_params: AuctionParams) -> Result<(), Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if !is_valid_auction_params(&auction_params) {
+            return Err(Error::InvalidAuctionParams);
+        }
+
+        put_auction_params(&e, &auction_params);
+        emit_set_auction(&e, &from, &auction_params);
+        Ok(())
+    }
+
+    fn auction(e: Env) -> Option<AuctionParams> {
+        get_auction_params(&e)
+    }
+
+    fn allocation_policy(e: Env) -> AllocationPolicy {
+        get_allocation_policy(&e)
+    }
+
+    fn risk_params(e: Env) -> RiskParams {
+        get_risk_params(&e)
+    }
+
+    fn set_max_age(e: Env, from: Address, max_age: u64) -> Result<(), Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        put_max_price_age(&e, max_age);
+        emit_set_max_age(&e, &from, max_age);
+        Ok(())
+    }
+
+    fn max_age(e: Env) -> u64 {
+        get_max_price_age(&e)
+    }
+
+    fn set_twap_window(e: Env, from: Address, window: u64) -> Result<(), Error> {
         from.require_auth();
 
+        bump_contract(&e);
+
         if !is_authorized(&e, &from) {
             return Err(Error::Unauthorized);
         }
 
-        Ok(put_spot_rate(&e, amount))
+        if !is_valid_twap_window(window, get_max_price_age(&e)) {
+            return Err(Error::InvalidTwapWindow);
+        }
+
+        put_twap_window(&e, window);
+        emit_set_twap_window(&e, &from, window);
+        Ok(())
+    }
+
+    fn twap_window(e: Env) -> u64 {
+        get_twap_window(&e)
+    }
+
+    fn poke_price(e: Env) -> Result<PriceData, Error> {
+        bump_contract(&e);
+
+        read_spot_price(&e)
     }
 
-    fn stage(e: Env) -> Stage {
-        get_stage(&e)
+    fn stage(e: Env, series: u32) -> Stage {
+        get_stage(&e, series)
     }
 
-    fn deposits(e: Env) -> (Vec<Position>, Vec<Position>) {
-        (get_used_positions_a(&e), get_used_positions_b(&e))
+    fn deposits(e: Env, series: u32, start: u32, limit: u32) -> (Vec<Position>, Vec<Position>) {
+        (
+            get_used_positions(&e, series, true, start, limit),
+            get_used_positions(&e, series, false, start, limit),
+        )
     }
 
-    fn users(e: Env) -> (Vec<UserLiqData>, Vec<UserLiqData>) {
-        let deposits_a = get_used_positions_a(&e);
-        let deposits_b = get_used_positions_b(&e);
+    fn deposits_count(e: Env, series: u32) -> (u32, u32) {
         (
-            get_users_liq_data(&e, deposits_a, true),
-            get_users_liq_data(&e, deposits_b, false),
+            get_used_positions_count(&e, series, true),
+            get_used_positions_count(&e, series, false),
         )
     }
 
+    fn users(
+        e: Env,
+        series: u32,
+        start: u32,
+        limit: u32,
+    ) -> Result<(Vec<UserLiqData>, Vec<UserLiqData>), Error> {
+        check_series(&e, series)?;
+
+        let spot_rate = read_liquidation_price(&e)?;
+        let deposits_a = get_used_positions(&e, series, true, start, limit);
+        let deposits_b = get_used_positions(&e, series, false, start, limit);
+        Ok((
+            get_users_liq_data(&e, series, deposits_a, true, spot_rate)?,
+            get_users_liq_data(&e, series, deposits_b, false, spot_rate)?,
+        ))
+    }
+
+    fn bump_ttl(e: Env, series: u32, users: Vec<Address>) {
+        bump_series(&e, series);
+        for user in users.iter() {
+            bump_user(&e, series, &user);
+        }
+    }
+
+    fn ttl(e: Env, series: u32, users: Vec<Address>) -> (u32, Vec<u32>) {
+        let sequence = e.ledger().sequence();
+        let mut users_ttl = Vec::new(&e);
+        for user in users.iter() {
+            users_ttl.push_back(get_user_live_until(&e, series, &user).saturating_sub(sequence));
+        }
+        let live_until = min(get_live_until(&e), get_series_live_until(&e, series));
+        (live_until.saturating_sub(sequence), users_ttl)
+    }
+
+    fn open_users(e: Env, series: u32) -> u32 {
+        get_open_users(&e, series)
+    }
+
+    fn sweep_dust(e: Env, from: Address, token: Address) -> Result<i128, Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        if !is_valid_token(&e, token.clone()) {
+            return Err(Error::InvalidToken);
+        }
+
+        // The balance is shared by every series, the ones whose users all settled owe nothing
+        // as what is left of their accounting is rounding
+        let owed_amount: i128 = (0..get_series_count(&e))
+            .filter(|series| !max_time_reached(&e, *series) || get_open_users(&e, *series) > 0)
+            .map(|series| get_owed_amount(&e, series, &token))
+            .sum();
+        let amount =
+            token::Client::new(&e, &token).balance(&e.current_contract_address()) - owed_amount;
+        if amount > 0 {
+            transfer(&e, token.clone(), from.clone(), amount);
+            add_swept_amount(&e, &token, amount);
+        }
+
+        emit_sweep_dust(&e, &from, &token, amount);
+        Ok(amount)
+    }
+
+    fn swept(e: Env, token: Address) -> i128 {
+        get_swept_amount(&e, &token)
+    }
+
+    fn propose_upgrade(e: Env, from: Address, new_wasm_hash: BytesN<32>) -> Result<u64, Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        let pending_upgrade = PendingUpgrade {
+            wasm_hash: new_wasm_hash,
+            unlock_time: e.ledger().timestamp() + UPGRADE_TIMELOCK,
+        };
+        put_pending_upgrade(&e, &pending_upgrade);
+        emit_propose_upgrade(&e, &from, &pending_upgrade);
+        Ok(pending_upgrade.unlock_time)
+    }
+
+    fn cancel_upgrade(e: Env, from: Address) -> Result<(), Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        let pending_upgrade = get_pending_upgrade(&e).ok_or(Error::UpgradeNotProposed)?;
+        remove_pending_upgrade(&e);
+        emit_cancel_upgrade(&e, &from, &pending_upgrade.wasm_hash);
+        Ok(())
+    }
+
+    fn pending_upgrade(e: Env) -> Option<PendingUpgrade> {
+        get_pending_upgrade(&e)
+    }
+
+    fn upgrade(e: Env, from: Address, new_wasm_hash: BytesN<32>) -> Result<(), Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        match get_pending_upgrade(&e) {
+            Some(pending_upgrade) if pending_upgrade.wasm_hash == new_wasm_hash => {
+                if e.ledger().timestamp() < pending_upgrade.unlock_time {
+                    return Err(Error::TimeNotReached);
+                }
+            }
+            _ => return Err(Error::UpgradeNotProposed),
+        }
+
+        remove_pending_upgrade(&e);
+        e.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+        emit_upgrade(&e, &from, &new_wasm_hash);
+        Ok(())
+    }
+
+    fn migrate(e: Env, from: Address) -> Result<u32, Error> {
+        from.require_auth();
+
+        bump_contract(&e);
+
+        if !is_authorized(&e, &from) {
+            return Err(Error::Unauthorized);
+        }
+
+        // Only the versions with a migration step to the current one are accepted. Data written
+        // by a newer code can't be read after going back to an older one, and the layout from
+        // before the schema version was stored (version 0) has no migration step
+        let version = get_schema_version(&e);
+        if version == 0 || version > SCHEMA_VERSION {
+            return Err(Error::InvalidSchemaVersion);
+        }
+
+        // Each schema version adds its migration step from the previous one here
+        put_schema_version(&e, SCHEMA_VERSION);
+        emit_migrate(&e, &from, version, SCHEMA_VERSION);
+        Ok(SCHEMA_VERSION)
+    }
+
+    fn version(e: Env) -> u32 {
+        get_schema_version(&e)
+    }
+
     fn transfer_admin(
         e: Env,
         from: Address,
@@ -993,16 +2904,21 @@ impl SwapTrait for Swap {
     ) -> Result<(), Error> {
         from.require_auth();
 
+        bump_contract(&e);
+
         if !is_authorized(&e, &from) {
             return Err(Error::Unauthorized);
         }
 
         if token == get_token_a_address(&e) {
-            Ok(transfer_a(&e, &to, amount))
+            transfer_a(&e, &to, amount);
         } else if token == get_token_b_address(&e) {
-            Ok(transfer_b(&e, &to, amount))
+            transfer_b(&e, &to, amount);
         } else {
-            Err(Error::InvalidToken)
+            return Err(Error::InvalidToken);
         }
+
+        emit_transfer_admin(&e, &from, &to, &token, amount);
+        Ok(())
     }
 }
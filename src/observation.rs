@@ -0,0 +1,111 @@
+use core::cmp::max;
+
+use soroban_sdk::{Env, Vec};
+use types::{error::Error, price_data::PriceData, storage::DataKey};
+
+use crate::constants::OBSERVATIONS_CAPACITY;
+use crate::fixed_point::{checked_add, checked_mul};
+use crate::storage::get_twap_window;
+use crate::ttl::extend_persistent;
+use crate::types;
+
+pub(crate) fn get_observations(e: &Env) -> Vec<PriceData> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::PriceObservations)
+        .unwrap_or(Vec::new(e))
+}
+
+// Observations are stored at least this far apart, so the buffer always spans the whole window.
+pub(crate) fn get_observation_interval(window: u64) -> u64 {
+    window.div_ceil(OBSERVATIONS_CAPACITY as u64 - 1)
+}
+
+// The buffer only covers a window if its observations are not further apart than a price stays fresh.
+pub(crate) fn is_valid_twap_window(window: u64, max_age: u64) -> bool {
+    get_observation_interval(window) <= max_age
+}
+
+// Keeps the last OBSERVATIONS_CAPACITY prices, at most one every `interval` seconds.
+// Returns false if the price was not stored.
+pub(crate) fn add_observation(
+    observations: &mut Vec<PriceData>,
+    price_data: &PriceData,
+    interval: u64,
+) -> bool {
+    if let Some(last) = observations.last() {
+        if price_data.timestamp < last.timestamp {
+            return false;
+        }
+        if price_data.timestamp == last.timestamp {
+            observations.pop_back();
+        } else if price_data.timestamp - last.timestamp < interval {
+            return false;
+        }
+    }
+
+    observations.push_back(price_data.clone());
+    while observations.len() > OBSERVATIONS_CAPACITY {
+        observations.pop_front();
+    }
+    true
+}
+
+pub(crate) fn record_observation(e: &Env, price_data: &PriceData) {
+    let mut observations = get_observations(e);
+    let interval = get_observation_interval(get_twap_window(e));
+    if !add_observation(&mut observations, price_data, interval) {
+        return;
+    }
+    e.storage()
+        .persistent()
+        .set(&DataKey::PriceObservations, &observations);
+    extend_persistent(e, &DataKey::PriceObservations);
+}
+
+// The latest price counts from its timestamp, even if it was too close to the last observation to be stored.
+pub(crate) fn get_twap_price(e: &Env, price_data: &PriceData) -> Result<i128, Error> {
+    let mut observations = get_observations(e);
+    let is_newer = match observations.last() {
+        Some(last) => price_data.timestamp > last.timestamp,
+        None => true,
+    };
+    if is_newer {
+        observations.push_back(price_data.clone());
+    }
+    Ok(get_twap(e, &observations, get_twap_window(e))?.unwrap_or(price_data.price))
+}
+
+// Time weighted average of the observations in the last `window` seconds,
+// each price is weighted by the time until the next observation.
+// Returns the last price if the window is 0 and None if there are no observations.
+fn get_twap(e: &Env, observations: &Vec<PriceData>, window: u64) -> Result<Option<i128>, Error> {
+    let last = match observations.last() {
+        Some(last) => last,
+        None => return Ok(None),
+    };
+    let now = e.ledger().timestamp();
+    let window_start = now.saturating_sub(window);
+    let mut weighted_sum: i128 = 0;
+    let mut total_time: u64 = 0;
+
+    for (i, observation) in observations.iter().enumerate() {
+        let end = match observations.get(i as u32 + 1) {
+            Some(next) => next.timestamp,
+            None => now,
+        };
+        let start = max(observation.timestamp, window_start);
+        if end > start {
+            weighted_sum = checked_add(
+                weighted_sum,
+                checked_mul(observation.price, (end - start) as i128)?,
+            )?;
+            total_time += end - start;
+        }
+    }
+
+    match total_time {
+        0 => Ok(Some(last.price)),
+        _ => Ok(Some(weighted_sum / total_time as i128)),
+    }
+}
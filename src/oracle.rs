@@ -1,17 +1,114 @@
-use crate::constants::{ORACLE_ADDRESS, ORACLE_FUNCTION};
-use crate::token_data::{get_token_a, get_token_b};
-use soroban_sdk::{vec, Address, Env, String, Symbol};
-use types::{asset::Asset, price_data::PriceData};
+use crate::constants::SCALE_DECIMALS;
+use crate::fixed_point::{mul_div_scaled, Rounding};
+use crate::storage::{
+    get_max_price_age, get_oracle_decimals, get_oracle_quorum, get_oracle_sources,
+};
+use soroban_sdk::{vec, Env, Symbol, Vec};
+use types::{error::Error, oracle_source::OracleSource, price_data::PriceData};
 
 use crate::types;
-pub fn get_oracle_spot_price(e: &Env) -> PriceData {
-    let oracle_address: String = String::from_str(&e, ORACLE_ADDRESS);
-    let target: Address = Address::from_string(&oracle_address);
-    let func: Symbol = Symbol::new(&e, ORACLE_FUNCTION);
-    let base_token = get_token_a(&e).name;
-    let base_asset = Asset::Other(base_token);
-    let quote_token = get_token_b(&e).name;
-    let quote_asset = Asset::Other(quote_token);
-    let args = vec![&e, base_asset, quote_asset].to_vals();
-    e.invoke_contract::<PriceData>(&target, &func, args)
+
+// Queries every oracle source and returns the median of the valid prices.
+// Sources failing or returning a stale or invalid price are dropped, if less than the
+// quorum respond the reason of the last dropped source is returned.
+pub fn get_oracle_spot_price(e: &Env) -> Result<PriceData, Error> {
+    let sources = get_oracle_sources(e);
+    let decimals = get_oracle_decimals(e);
+    let quorum = get_oracle_quorum(e);
+    let mut prices: Vec<i128> = Vec::new(e);
+    let mut timestamp = u64::MAX;
+    let mut last_error = Error::PriceUnavailable;
+
+    for (source, source_decimals) in sources.iter().zip(decimals.iter()) {
+        match get_source_price(e, &source, source_decimals) {
+            Ok(price_data) => {
+                insert_sorted(&mut prices, price_data.price);
+                timestamp = timestamp.min(price_data.timestamp);
+            }
+            Err(err) => last_error = err,
+        }
+    }
+
+    if prices.len() < quorum {
+        return Err(last_error);
+    }
+
+    Ok(PriceData {
+        price: median(&prices),
+        timestamp,
+    })
+}
+
+// Queries the decimals of the prices reported by every oracle source
+pub fn get_sources_decimals(e: &Env, sources: &Vec<OracleSource>) -> Result<Vec<u32>, Error> {
+    let mut decimals = Vec::new(e);
+    for source in sources.iter() {
+        match e.try_invoke_contract::<u32, soroban_sdk::Error>(
+            &source.address,
+            &Symbol::new(e, "decimals"),
+            Vec::new(e),
+        ) {
+            Ok(Ok(source_decimals)) => decimals.push_back(source_decimals),
+            _ => return Err(Error::InvalidOracle),
+        }
+    }
+    Ok(decimals)
+}
+
+fn get_source_price(e: &Env, source: &OracleSource, decimals: u32) -> Result<PriceData, Error> {
+    let args = vec![e, source.base_asset.clone(), source.quote_asset.clone()].to_vals();
+    let mut price_data = match e.try_invoke_contract::<Option<PriceData>, soroban_sdk::Error>(
+        &source.address,
+        &source.function,
+        args,
+    ) {
+        Ok(Ok(Some(price_data))) => price_data,
+        _ => return Err(Error::PriceUnavailable),
+    };
+    // Prices are normalized to the decimals of SCALE
+    price_data.price = mul_div_scaled(
+        price_data.price,
+        1,
+        1,
+        decimals,
+        SCALE_DECIMALS,
+        Rounding::HalfEven,
+    )?;
+    check_price(e, &price_data)?;
+    Ok(price_data)
+}
+
+fn check_price(e: &Env, price_data: &PriceData) -> Result<(), Error> {
+    if price_data.price <= 0 {
+        return Err(Error::InvalidPrice);
+    }
+
+    let now = e.ledger().timestamp();
+    if price_data.timestamp > now {
+        return Err(Error::InvalidPrice);
+    }
+    if now - price_data.timestamp > get_max_price_age(e) {
+        return Err(Error::StalePrice);
+    }
+
+    Ok(())
+}
+
+fn insert_sorted(prices: &mut Vec<i128>, price: i128) {
+    let index = prices
+        .iter()
+        .position(|p| p > price)
+        .unwrap_or(prices.len() as usize);
+    prices.insert(index as u32, price);
+}
+
+fn median(prices: &Vec<i128>) -> i128 {
+    let middle = prices.len() / 2;
+    if prices.len() % 2 == 1 {
+        prices.get(middle).unwrap()
+    } else {
+        // The prices are sorted, so the midpoint can't overflow
+        let low = prices.get(middle - 1).unwrap();
+        low + (prices.get(middle).unwrap() - low) / 2
+    }
 }
@@ -1,56 +1,221 @@
+use core::iter::successors;
+
 use soroban_sdk::{Address, Env, Vec};
 use types::position::Position;
 
 use crate::token_data::get_token_a_address;
+use crate::ttl::extend_series_persistent;
 use crate::types::{self, storage::DataKey};
 
-pub(crate) fn get_used_positions_a(e: &Env) -> Vec<Position> {
+fn get_user_positions_key(series: u32, user: &Address, is_a: bool) -> DataKey {
+    match is_a {
+        true => DataKey::UserPositionsA(series, user.clone()),
+        false => DataKey::UserPositionsB(series, user.clone()),
+    }
+}
+
+fn get_position_count_key(series: u32, is_a: bool) -> DataKey {
+    match is_a {
+        true => DataKey::PositionCountA(series),
+        false => DataKey::PositionCountB(series),
+    }
+}
+
+fn get_position_key(series: u32, is_a: bool, index: u32) -> DataKey {
+    match is_a {
+        true => DataKey::UsedPositionA(series, index),
+        false => DataKey::UsedPositionB(series, index),
+    }
+}
+
+fn get_cancelled_amount_key(series: u32, is_a: bool) -> DataKey {
+    match is_a {
+        true => DataKey::CancelledAmountA(series),
+        false => DataKey::CancelledAmountB(series),
+    }
+}
+
+fn get_cancelled_tree_key(series: u32, is_a: bool, node: u32) -> DataKey {
+    match is_a {
+        true => DataKey::CancelledTreeA(series, node),
+        false => DataKey::CancelledTreeB(series, node),
+    }
+}
+
+pub(crate) fn get_used_positions_count(e: &Env, series: u32, is_a: bool) -> u32 {
     e.storage()
         .persistent()
-        .get(&DataKey::UsedPositionsA)
-        .unwrap_or(Vec::new(&e))
+        .get(&get_position_count_key(series, is_a))
+        .unwrap_or(0)
 }
 
-pub(crate) fn get_used_positions_b(e: &Env) -> Vec<Position> {
+fn put_used_positions_count(e: &Env, series: u32, is_a: bool, count: u32) {
+    let key = get_position_count_key(series, is_a);
+    e.storage().persistent().set(&key, &count);
+    extend_series_persistent(e, series, &key);
+}
+
+pub(crate) fn get_used_position(e: &Env, series: u32, is_a: bool, index: u32) -> Position {
     e.storage()
         .persistent()
-        .get(&DataKey::UsedPositionsB)
-        .unwrap_or(Vec::new(&e))
+        .get(&get_position_key(series, is_a, index))
+        .unwrap()
 }
 
-fn get_used_positions(e: &Env, token: &Address) -> Vec<Position> {
-    let token_a_address = get_token_a_address(&e);
-    let key = match token_a_address == token.clone() {
-        true => DataKey::UsedPositionsA,
-        false => DataKey::UsedPositionsB,
-    };
-    e.storage().persistent().get(&key).unwrap_or(Vec::new(&e))
+fn put_used_position(e: &Env, series: u32, is_a: bool, index: u32, position: &Position) {
+    let key = get_position_key(series, is_a, index);
+    e.storage().persistent().set(&key, position);
+    extend_series_persistent(e, series, &key);
 }
 
-fn put_used_positions(e: &Env, token: &Address, used_positions: &Vec<Position>) {
-    let token_a_address = get_token_a_address(&e);
-    let key = match token_a_address == token.clone() {
-        true => DataKey::UsedPositionsA,
-        false => DataKey::UsedPositionsB,
-    };
-    e.storage().persistent().set(&key, used_positions);
+// Returns up to `limit` positions starting at index `start`
+pub(crate) fn get_used_positions(
+    e: &Env,
+    series: u32,
+    is_a: bool,
+    start: u32,
+    limit: u32,
+) -> Vec<Position> {
+    let count = get_used_positions_count(e, series, is_a);
+    let end = start.saturating_add(limit).min(count);
+    let mut positions = Vec::new(e);
+    for index in start..end {
+        positions.push_back(get_used_position(e, series, is_a, index));
+    }
+    positions
 }
 
-pub(crate) fn create_position(e: &Env, to: &Address, token: &Address) -> u32 {
+// Returns the indexes of the positions taken by the user, in ascending order
+pub(crate) fn get_user_positions(e: &Env, series: u32, user: &Address, is_a: bool) -> Vec<u32> {
+    e.storage()
+        .persistent()
+        .get(&get_user_positions_key(series, user, is_a))
+        .unwrap_or(Vec::new(e))
+}
+
+fn add_user_position(e: &Env, series: u32, user: &Address, is_a: bool, index: u32) {
+    let mut user_positions = get_user_positions(e, series, user, is_a);
+    user_positions.push_back(index);
+    let key = get_user_positions_key(series, user, is_a);
+    e.storage().persistent().set(&key, &user_positions);
+    extend_series_persistent(e, series, &key);
+}
+
+pub(crate) fn create_position(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    token: &Address,
+    amount: i128,
+    offset: i128,
+) -> u32 {
+    let is_a = token.clone() == get_token_a_address(e);
+    let cancelled_amount = get_cancelled_amount(e, series, is_a);
     let position = Position {
         address: to.clone(),
         is_valid: false,
+        amount,
+        offset: offset + cancelled_amount,
     };
-    let mut used_position = get_used_positions(&e, &token);
-    used_position.push_back(position);
-    put_used_positions(&e, &token, &used_position);
-    used_position.len() - 1
+    let index = get_used_positions_count(e, series, is_a);
+    if cancelled_amount > 0 {
+        init_cancelled_node(e, series, is_a, index + 1);
+    }
+    put_used_position(e, series, is_a, index, &position);
+    put_used_positions_count(e, series, is_a, index + 1);
+    add_user_position(e, series, to, is_a, index);
+    index
+}
+
+// Total amount of the cancelled positions
+fn get_cancelled_amount(e: &Env, series: u32, is_a: bool) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&get_cancelled_amount_key(series, is_a))
+        .unwrap_or(0)
+}
+
+fn put_cancelled_amount(e: &Env, series: u32, is_a: bool, amount: i128) {
+    let key = get_cancelled_amount_key(series, is_a);
+    e.storage().persistent().set(&key, &amount);
+    extend_series_persistent(e, series, &key);
+}
+
+// The cancelled amounts are kept in a Fenwick tree keyed by position index, where node
+// `n` holds the amount cancelled in the positions `n - lowbit(n)` up to `n - 1`
+fn get_cancelled_node(e: &Env, series: u32, is_a: bool, node: u32) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&get_cancelled_tree_key(series, is_a, node))
+        .unwrap_or(0)
+}
+
+fn put_cancelled_node(e: &Env, series: u32, is_a: bool, node: u32, amount: i128) {
+    let key = get_cancelled_tree_key(series, is_a, node);
+    e.storage().persistent().set(&key, &amount);
+    extend_series_persistent(e, series, &key);
+}
+
+fn lowbit(node: u32) -> u32 {
+    node & node.wrapping_neg()
+}
+
+// Nodes that add up to the amount cancelled in the positions before `index`
+pub(crate) fn get_cancelled_nodes(index: u32) -> impl Iterator<Item = u32> {
+    successors(Some(index), |node| Some(node - lowbit(*node))).take_while(|node| *node > 0)
+}
+
+// Amount cancelled in the positions before `index`
+fn get_cancelled_before(e: &Env, series: u32, is_a: bool, index: u32) -> i128 {
+    get_cancelled_nodes(index)
+        .map(|node| get_cancelled_node(e, series, is_a, node))
+        .sum()
+}
+
+// A new node covers positions created before it, so it starts with their cancelled amount
+fn init_cancelled_node(e: &Env, series: u32, is_a: bool, node: u32) {
+    let amount = get_cancelled_before(e, series, is_a, node - 1)
+        - get_cancelled_before(e, series, is_a, node - lowbit(node));
+    if amount > 0 {
+        put_cancelled_node(e, series, is_a, node, amount);
+    }
+}
+
+// Marks the position invalid and records its amount so that
+// the positions after it no longer count it in their offset
+pub(crate) fn cancel_position(e: &Env, series: u32, is_a: bool, index: u32) {
+    let mut position = get_used_position(e, series, is_a, index);
+    position.is_valid = false;
+    put_used_position(e, series, is_a, index, &position);
+
+    let count = get_used_positions_count(e, series, is_a) as u64;
+    let mut node = index as u64 + 1;
+    while node <= count {
+        let amount = get_cancelled_node(e, series, is_a, node as u32) + position.amount;
+        put_cancelled_node(e, series, is_a, node as u32, amount);
+        node += lowbit(node as u32) as u64;
+    }
+    let cancelled_amount = get_cancelled_amount(e, series, is_a) + position.amount;
+    put_cancelled_amount(e, series, is_a, cancelled_amount);
+}
+
+// Amount deposited in valid positions before the given one
+pub(crate) fn get_position_offset(
+    e: &Env,
+    series: u32,
+    is_a: bool,
+    index: u32,
+    position: &Position,
+) -> i128 {
+    if get_cancelled_amount(e, series, is_a) == 0 {
+        return position.offset;
+    }
+    position.offset - get_cancelled_before(e, series, is_a, index)
 }
 
-pub(crate) fn set_position_valid(e: &Env, position_index: u32, token: &Address) {
-    let mut used_position = get_used_positions(&e, &token);
-    let mut position = used_position.get(position_index).unwrap();
+pub(crate) fn set_position_valid(e: &Env, series: u32, position_index: u32, token: &Address) {
+    let is_a = token.clone() == get_token_a_address(e);
+    let mut position = get_used_position(e, series, is_a, position_index);
     position.is_valid = true;
-    used_position.set(position_index, position);
-    put_used_positions(&e, &token, &used_position);
+    put_used_position(e, series, is_a, position_index, &position);
 }
@@ -4,63 +4,41 @@ use crate::token_data::get_token_a_address;
 use crate::types;
 use types::{position_data::PositionData, storage::DataKey};
 
-pub(crate) fn init_position_a(e: &Env, limit: u64, amount: i128) {
-    e.storage().instance().set(
-        &DataKey::PositionA,
-        &PositionData {
-            limit,
-            used: 0,
-            deposit_amount: amount,
-        },
-    );
+pub(crate) fn set_position_a(e: &Env, series: u32, position: &PositionData) {
+    e.storage()
+        .instance()
+        .set(&DataKey::PositionA(series), position);
 }
 
-pub(crate) fn init_position_b(e: &Env, limit: u64, amount: i128) {
-    e.storage().instance().set(
-        &DataKey::PositionB,
-        &PositionData {
-            limit,
-            used: 0,
-            deposit_amount: amount,
-        },
-    );
+pub(crate) fn set_position_b(e: &Env, series: u32, position: &PositionData) {
+    e.storage()
+        .instance()
+        .set(&DataKey::PositionB(series), position);
 }
 
-pub(crate) fn set_position_a(e: &Env, position: &PositionData) {
-    e.storage().instance().set(&DataKey::PositionA, position);
+pub(crate) fn get_position_a(e: &Env, series: u32) -> PositionData {
+    e.storage()
+        .instance()
+        .get(&DataKey::PositionA(series))
+        .unwrap()
 }
 
-pub(crate) fn set_position_b(e: &Env, position: &PositionData) {
-    e.storage().instance().set(&DataKey::PositionB, position);
+pub(crate) fn get_position_b(e: &Env, series: u32) -> PositionData {
+    e.storage()
+        .instance()
+        .get(&DataKey::PositionB(series))
+        .unwrap()
 }
 
-pub(crate) fn get_position_a(e: &Env) -> PositionData {
-    e.storage().instance().get(&DataKey::PositionA).unwrap()
-}
-
-pub(crate) fn get_position_b(e: &Env) -> PositionData {
-    e.storage().instance().get(&DataKey::PositionB).unwrap()
-}
-
-pub(crate) fn get_position_data(e: &Env, token: &Address) -> PositionData {
+pub(crate) fn get_position_data(e: &Env, series: u32, token: &Address) -> PositionData {
     match token.clone() == get_token_a_address(&e) {
-        true => get_position_a(&e),
-        false => get_position_b(&e),
+        true => get_position_a(&e, series),
+        false => get_position_b(&e, series),
     }
 }
 
-pub(crate) fn are_positions_open(position: &PositionData) -> bool {
-    position.used < position.limit
-}
-
-pub(crate) fn ocupy_one_position(e: &Env, token: &Address, position: &PositionData) {
-    let new_position_data = PositionData {
-        limit: position.limit,
-        used: position.used + 1,
-        deposit_amount: position.deposit_amount,
-    };
-    match token.clone() == get_token_a_address(&e) {
-        true => set_position_a(&e, &new_position_data),
-        false => set_position_b(&e, &new_position_data),
-    }
+pub(crate) fn is_valid_position_data(position: &PositionData) -> bool {
+    position.min_deposit > 0
+        && position.min_deposit <= position.max_deposit
+        && position.max_deposit <= position.cap
 }
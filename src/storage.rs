@@ -1,5 +1,9 @@
-use soroban_sdk::{Address, Env};
-use types::storage::DataKey;
+use soroban_sdk::{Address, Env, Vec};
+use types::{
+    allocation_policy::AllocationPolicy, auction_params::AuctionParams,
+    oracle_source::OracleSource, pending_oracles::PendingOracles, pending_upgrade::PendingUpgrade,
+    risk_params::RiskParams, storage::DataKey,
+};
 
 use crate::types;
 
@@ -7,44 +11,199 @@ pub(crate) fn get_admin(e: &Env) -> Option<Address> {
     e.storage().instance().get(&DataKey::Admin)
 }
 
-pub(crate) fn get_spot_rate(e: &Env) -> i128 {
+// Series are numbered from 0, the first one is created at initialization
+pub(crate) fn get_series_count(e: &Env) -> u32 {
     e.storage()
         .instance()
-        .get(&DataKey::SpotRate)
+        .get(&DataKey::SeriesCount)
+        .unwrap_or(0)
+}
+
+pub(crate) fn get_spot_rate(e: &Env, series: u32) -> i128 {
+    e.storage()
+        .instance()
+        .get(&DataKey::SpotRate(series))
         .unwrap_or_default()
 }
 
-pub(crate) fn get_forward_rate(e: &Env) -> i128 {
-    e.storage().instance().get(&DataKey::ForwardRate).unwrap()
+pub(crate) fn get_forward_rate(e: &Env, series: u32) -> i128 {
+    e.storage()
+        .instance()
+        .get(&DataKey::ForwardRate(series))
+        .unwrap()
+}
+
+pub(crate) fn get_init_time(e: &Env, series: u32) -> u64 {
+    e.storage()
+        .instance()
+        .get(&DataKey::InitTime(series))
+        .unwrap()
 }
 
-pub(crate) fn get_init_time(e: &Env) -> u64 {
-    e.storage().instance().get(&DataKey::InitTime).unwrap()
+pub(crate) fn get_time_to_mature(e: &Env, series: u32) -> u64 {
+    e.storage()
+        .instance()
+        .get(&DataKey::TimeToMature(series))
+        .unwrap()
 }
 
-pub(crate) fn get_time_to_mature(e: &Env) -> u64 {
-    e.storage().instance().get(&DataKey::TimeToMature).unwrap()
+pub(crate) fn get_risk_params(e: &Env) -> RiskParams {
+    e.storage().instance().get(&DataKey::RiskParams).unwrap()
+}
+
+pub(crate) fn get_allocation_policy(e: &Env) -> AllocationPolicy {
+    e.storage()
+        .instance()
+        .get(&DataKey::AllocationPolicy)
+        .unwrap()
+}
+
+pub(crate) fn get_auction_params(e: &Env) -> Option<AuctionParams> {
+    e.storage().instance().get(&DataKey::AuctionParams)
+}
+
+pub(crate) fn get_pending_upgrade(e: &Env) -> Option<PendingUpgrade> {
+    e.storage().instance().get(&DataKey::PendingUpgrade)
+}
+
+pub(crate) fn get_pending_oracles(e: &Env) -> Option<PendingOracles> {
+    e.storage().instance().get(&DataKey::PendingOracles)
+}
+
+// Contracts initialized before the schema version was stored are on version 0
+pub(crate) fn get_schema_version(e: &Env) -> u32 {
+    e.storage()
+        .instance()
+        .get(&DataKey::SchemaVersion)
+        .unwrap_or(0)
+}
+
+pub(crate) fn get_oracle_sources(e: &Env) -> Vec<OracleSource> {
+    e.storage().instance().get(&DataKey::OracleSources).unwrap()
+}
+
+pub(crate) fn get_oracle_decimals(e: &Env) -> Vec<u32> {
+    e.storage()
+        .instance()
+        .get(&DataKey::OracleDecimals)
+        .unwrap()
+}
+
+pub(crate) fn get_oracle_quorum(e: &Env) -> u32 {
+    e.storage().instance().get(&DataKey::OracleQuorum).unwrap()
+}
+
+pub(crate) fn get_max_price_age(e: &Env) -> u64 {
+    e.storage().instance().get(&DataKey::MaxPriceAge).unwrap()
+}
+
+pub(crate) fn get_twap_window(e: &Env) -> u64 {
+    e.storage().instance().get(&DataKey::TwapWindow).unwrap()
+}
+
+// Amount of users of the series with claims left to settle
+pub(crate) fn get_open_users(e: &Env, series: u32) -> u32 {
+    e.storage()
+        .instance()
+        .get(&DataKey::OpenUsers(series))
+        .unwrap_or(0)
 }
 
 pub(crate) fn put_admin(e: &Env, address: Address) {
     e.storage().instance().set(&DataKey::Admin, &address);
 }
 
-pub(crate) fn put_forward_rate(e: &Env, rate: i128) {
-    e.storage().instance().set(&DataKey::ForwardRate, &rate);
+pub(crate) fn put_series_count(e: &Env, count: u32) {
+    e.storage().instance().set(&DataKey::SeriesCount, &count);
+}
+
+pub(crate) fn put_forward_rate(e: &Env, series: u32, rate: i128) {
+    e.storage()
+        .instance()
+        .set(&DataKey::ForwardRate(series), &rate);
 }
 
-pub(crate) fn put_spot_rate(e: &Env, amount: i128) {
-    e.storage().instance().set(&DataKey::SpotRate, &amount);
+pub(crate) fn put_spot_rate(e: &Env, series: u32, amount: i128) {
+    e.storage()
+        .instance()
+        .set(&DataKey::SpotRate(series), &amount);
 }
 
-pub(crate) fn put_init_time(e: &Env) {
+pub(crate) fn put_init_time(e: &Env, series: u32) {
     let time = e.ledger().timestamp();
-    e.storage().instance().set(&DataKey::InitTime, &time);
+    e.storage()
+        .instance()
+        .set(&DataKey::InitTime(series), &time);
+}
+
+pub(crate) fn put_time_to_mature(e: &Env, series: u32, duration: u64) {
+    e.storage()
+        .instance()
+        .set(&DataKey::TimeToMature(series), &duration);
+}
+
+pub(crate) fn put_risk_params(e: &Env, risk_params: &RiskParams) {
+    e.storage()
+        .instance()
+        .set(&DataKey::RiskParams, risk_params);
+}
+
+pub(crate) fn put_allocation_policy(e: &Env, allocation_policy: AllocationPolicy) {
+    e.storage()
+        .instance()
+        .set(&DataKey::AllocationPolicy, &allocation_policy);
+}
+
+pub(crate) fn put_auction_params(e: &Env, auction_params: &AuctionParams) {
+    e.storage()
+        .instance()
+        .set(&DataKey::AuctionParams, auction_params);
+}
+
+pub(crate) fn put_pending_upgrade(e: &Env, pending_upgrade: &PendingUpgrade) {
+    e.storage()
+        .instance()
+        .set(&DataKey::PendingUpgrade, pending_upgrade);
+}
+
+pub(crate) fn remove_pending_upgrade(e: &Env) {
+    e.storage().instance().remove(&DataKey::PendingUpgrade);
+}
+
+pub(crate) fn put_pending_oracles(e: &Env, pending_oracles: &PendingOracles) {
+    e.storage()
+        .instance()
+        .set(&DataKey::PendingOracles, pending_oracles);
+}
+
+pub(crate) fn remove_pending_oracles(e: &Env) {
+    e.storage().instance().remove(&DataKey::PendingOracles);
+}
+
+pub(crate) fn put_schema_version(e: &Env, version: u32) {
+    e.storage()
+        .instance()
+        .set(&DataKey::SchemaVersion, &version);
+}
+
+pub(crate) fn put_max_price_age(e: &Env, max_age: u64) {
+    e.storage().instance().set(&DataKey::MaxPriceAge, &max_age);
+}
+
+pub(crate) fn put_twap_window(e: &Env, window: u64) {
+    e.storage().instance().set(&DataKey::TwapWindow, &window);
+}
+
+pub(crate) fn put_open_users(e: &Env, series: u32, count: u32) {
+    e.storage()
+        .instance()
+        .set(&DataKey::OpenUsers(series), &count);
 }
 
-pub(crate) fn put_time_to_mature(e: &Env, duration: u64) {
+pub(crate) fn put_oracles(e: &Env, sources: &Vec<OracleSource>, decimals: &Vec<u32>, quorum: u32) {
+    e.storage().instance().set(&DataKey::OracleSources, sources);
     e.storage()
         .instance()
-        .set(&DataKey::TimeToMature, &duration);
+        .set(&DataKey::OracleDecimals, decimals);
+    e.storage().instance().set(&DataKey::OracleQuorum, &quorum);
 }
@@ -2,17 +2,35 @@
 extern crate std;
 
 use crate::constants::{
-    COLLATERAL_BUFFER, ORACLE_ADDRESS, SCALE, TIME_TO_EXEC, TIME_TO_MATURE, TIME_TO_REPAY,
+    CLAIM_PERIOD, COLLATERAL_BUFFER, COLLATERAL_THRESHOLD, LEDGER_TIME, LIQUIDATION_REWARD,
+    OBSERVATIONS_CAPACITY, ORACLE_TIMELOCK, SCALE, SCHEMA_VERSION, TIME_TO_EXEC, TIME_TO_MATURE,
+    TIME_TO_REPAY, TTL_BUMP_INTERVAL, UPGRADE_TIMELOCK,
 };
+use crate::fixed_point::{calculate_percentage, convert_a_to_b, convert_b_to_a, mul_div, Rounding};
+use crate::types::allocation_policy::AllocationPolicy;
+use crate::types::asset::Asset;
+use crate::types::auction_params::AuctionParams;
+use crate::types::error::Error;
+use crate::types::init_params::InitParams;
+use crate::types::oracle_source::OracleSource;
+use crate::types::partial_liquidation::PartialLiquidation;
+use crate::types::pending_oracles::PendingOracles;
+use crate::types::pending_upgrade::PendingUpgrade;
+use crate::types::position_data::PositionData;
+use crate::types::price_data::PriceData;
+use crate::types::risk_params::RiskParams;
 use crate::types::stage::Stage;
+use crate::types::storage::DataKey;
 use crate::types::user::User;
+use crate::types::user_health::UserHealth;
 use crate::types::user_liq_data::UserLiqData;
-use crate::types::{position::Position, storage::DataKey};
 use crate::SwapClient;
 
-use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::testutils::{Address as _, Events, Ledger};
 use soroban_sdk::token::StellarAssetClient;
-use soroban_sdk::{symbol_short, token, Address, Env, String, Vec};
+use soroban_sdk::{
+    symbol_short, token, vec, Address, BytesN, Env, IntoVal, String, Symbol, Val, Vec,
+};
 use token::Client as TokenClient;
 
 use self::oracle_mock::Client;
@@ -23,6 +41,13 @@ mod oracle_mock {
     );
 }
 
+// WASM built from this crate, `make test` builds it first
+const SWAP_WASM: &[u8] =
+    include_bytes!("../target/wasm32-unknown-unknown/release/swap_contract.wasm");
+
+// Symbol of the Stellar Asset Contracts registered for tests
+const TOKEN_NAME: Symbol = symbol_short!("aaa");
+
 fn create_token_contract<'a>(
     e: &Env,
     admin: &Address,
@@ -34,6 +59,56 @@ fn create_token_contract<'a>(
     )
 }
 
+// Token with configurable decimals, Stellar Asset Contracts always have 7 decimals
+mod token_mock {
+    use soroban_sdk::{contract, contractimpl, symbol_short, Address, Env, String, Symbol};
+
+    const DECIMALS: Symbol = symbol_short!("DECIMALS");
+    const SYMBOL: Symbol = symbol_short!("SYMBOL");
+
+    #[contract]
+    pub struct TokenMock;
+
+    #[contractimpl]
+    impl TokenMock {
+        pub fn initialize(e: Env, decimals: u32, symbol: String) {
+            e.storage().instance().set(&DECIMALS, &decimals);
+            e.storage().instance().set(&SYMBOL, &symbol);
+        }
+
+        pub fn mint(e: Env, to: Address, amount: i128) {
+            let balance = Self::balance(e.clone(), to.clone());
+            e.storage().persistent().set(&to, &(balance + amount));
+        }
+
+        pub fn balance(e: Env, id: Address) -> i128 {
+            e.storage().persistent().get(&id).unwrap_or(0)
+        }
+
+        pub fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+            from.require_auth();
+            let balance = Self::balance(e.clone(), from.clone());
+            if balance < amount {
+                panic!("insufficient balance");
+            }
+            e.storage().persistent().set(&from, &(balance - amount));
+            Self::mint(e, to, amount);
+        }
+
+        pub fn decimals(e: Env) -> u32 {
+            e.storage().instance().get(&DECIMALS).unwrap()
+        }
+
+        pub fn name(e: Env) -> String {
+            Self::symbol(e)
+        }
+
+        pub fn symbol(e: Env) -> String {
+            e.storage().instance().get(&SYMBOL).unwrap()
+        }
+    }
+}
+
 struct SwapTest<'a> {
     e: Env,
     token_admin: Address,
@@ -67,8 +142,7 @@ impl<'a> SwapTest<'a> {
 
         let contract = SwapClient::new(&e, &e.register_contract(None, crate::Swap {}));
 
-        let oracle_address = Address::from_string(&String::from_str(&e, ORACLE_ADDRESS));
-        let _ = &e.register_contract_wasm(Some(&oracle_address), oracle_mock::WASM);
+        let oracle_address = e.register_contract_wasm(None, oracle_mock::WASM);
         let oracle_client = oracle_mock::Client::new(&e, &oracle_address);
         oracle_client.set_spot_rate(&SCALE);
 
@@ -92,6 +166,109 @@ impl<'a> SwapTest<'a> {
         });
     }
 
+    // Closes ledgers at the average ledger time
+    fn add_ledgers(e: &Env, ledgers: u32) {
+        e.ledger().with_mut(|li| {
+            li.sequence_number += ledgers;
+            li.timestamp += ledgers as u64 * LEDGER_TIME;
+        });
+    }
+
+    fn risk_params() -> RiskParams {
+        RiskParams {
+            collateral_buffer: COLLATERAL_BUFFER,
+            collateral_threshold: COLLATERAL_THRESHOLD,
+            liquidation_reward: LIQUIDATION_REWARD,
+            time_to_exec: TIME_TO_EXEC,
+            time_to_repay: TIME_TO_REPAY,
+            partial_liquidation: false,
+        }
+    }
+
+    // Series 0 trades token A for token B at a forward rate of 1 and matures in a week
+    fn init_params(token_a: &Address, token_b: &Address, oracle: &Address) -> InitParams {
+        InitParams {
+            token_a: token_a.clone(),
+            token_b: token_b.clone(),
+            name_token_a: TOKEN_NAME,
+            name_token_b: TOKEN_NAME,
+            forward_rate: SCALE,
+            duration: TIME_TO_MATURE,
+            oracle: oracle.clone(),
+            risk_params: SwapTest::risk_params(),
+            allocation_policy: AllocationPolicy::Fifo,
+        }
+    }
+
+    fn create_oracle(e: &Env, spot_rate: i128) -> Client<'a> {
+        let oracle_address = e.register_contract_wasm(None, oracle_mock::WASM);
+        let oracle_client = oracle_mock::Client::new(e, &oracle_address);
+        oracle_client.set_spot_rate(&spot_rate);
+        oracle_client
+    }
+
+    fn oracle_source(e: &Env, oracle: &Address) -> OracleSource {
+        OracleSource {
+            address: oracle.clone(),
+            function: Symbol::new(e, "x_last_price"),
+            base_asset: Asset::Other(TOKEN_NAME),
+            quote_asset: Asset::Other(TOKEN_NAME),
+        }
+    }
+
+    fn assert_last_event(e: &Env, contract: &Address, topics: Vec<Val>, data: Val) {
+        let last_event = e
+            .events()
+            .all()
+            .iter()
+            .filter(|(address, _, _)| address == contract)
+            .last()
+            .unwrap();
+        assert_eq!(
+            vec![e, last_event],
+            vec![e, (contract.clone(), topics, data)]
+        );
+    }
+
+    // The contract balance of each token has to cover what its Token accounting still owes:
+    // unmatched deposits, repaid amounts left to withdraw, collateral left to the users,
+    // liquidated collateral left to withdraw or auction and unused auction proceeds
+    fn assert_liabilities_covered(e: &Env, contract: &SwapClient, users: &[&Address]) {
+        // Checked after every step of long flows, each step starts with a fresh budget
+        e.budget().reset_default();
+        let (token_a_data, token_b_data) = contract.tokens(&0);
+        for token_data in [token_a_data, token_b_data] {
+            let users_collateral: i128 = users
+                .iter()
+                .map(|user| contract.balance(&0, user))
+                .filter(|balance| balance.deposited_token == token_data.address)
+                .map(|balance| {
+                    balance.collateral
+                        - balance.withdrawn_collateral
+                        - balance.liquidated_collateral
+                })
+                .sum();
+            let liabilities = token_data.deposited_amount
+                - token_data.swapped_amount
+                - token_data.reclaimed_amount
+                + token_data.returned_amount
+                - token_data.withdrawn_amount
+                + users_collateral
+                + token_data.liquidated_collateral
+                - token_data.used_liq_collateral
+                - token_data.auctioned_collateral
+                + token_data.auction_proceeds
+                - token_data.used_auction_proceeds;
+            let balance = TokenClient::new(e, &token_data.address).balance(&contract.address);
+            assert!(
+                balance >= liabilities,
+                "balance {} below liabilities {}",
+                balance,
+                liabilities
+            );
+        }
+    }
+
     // fn mint_token(token_admin_client: StellarAssetClient<'a>, to: &Address, amount: i128) {
     //     token_admin_client.mint(&to, &amount);
     // }
@@ -105,16 +282,15 @@ fn test_init() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     let spot_rate = contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &0,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     assert_eq!(spot_rate, SCALE);
 }
@@ -128,25 +304,22 @@ fn test_re_init() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
 }
 
@@ -158,18 +331,17 @@ fn test_init_pos() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let amount_position_b = contract.init_pos(&token_admin, &100, &50, &100);
+    let amount_position_b = contract.init_pos(&0, &token_admin, &100, &50, &100);
     assert_eq!(amount_position_b, 200);
 }
 
@@ -181,18 +353,17 @@ fn test_init_pos_odd() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let amount_position_b = contract.init_pos(&token_admin, &10, &3, &100);
+    let amount_position_b = contract.init_pos(&0, &token_admin, &10, &3, &100);
     assert_eq!(amount_position_b, 333);
 }
 
@@ -206,18 +377,17 @@ fn test_init_pos_unauthorized() {
         token_b,
         contract,
         user_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&user_a, &100, &50, &100);
+    contract.init_pos(&0, &user_a, &100, &50, &100);
 }
 
 #[test]
@@ -230,20 +400,19 @@ fn test_deposit() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    let (amount_a, collateral_a) = contract.deposit(&user_a, &token_a.address, &100, &20);
-    let (amount_b, collateral_b) = contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    let (amount_a, collateral_a) = contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    let (amount_b, collateral_b) = contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     assert_eq!(amount_a, 100);
     assert_eq!(amount_b, 200);
@@ -264,19 +433,18 @@ fn test_deposit_wrong_amount() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &200, &200);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &50, &50);
 }
 
 #[test]
@@ -291,21 +459,20 @@ fn test_deposit_all_positions_closed() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_b, &1000);
-    contract.init_pos(&token_admin, &1, &1, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &40);
-    contract.deposit(&user_b, &token_a.address, &100, &40);
+    contract.init_pos(&0, &token_admin, &1, &1, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &40);
+    contract.deposit(&0, &user_b, &token_a.address, &100, &40);
 }
 
 #[test]
@@ -320,20 +487,19 @@ fn test_deposit_insuficient_collateral() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_b, &1000);
-    contract.init_pos(&token_admin, &1, &1, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &10);
+    contract.init_pos(&0, &token_admin, &1, &1, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &10);
 }
 
 #[test]
@@ -347,20 +513,19 @@ fn test_deposit_high_collateral() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_b, &1000);
-    contract.init_pos(&token_admin, &1, &1, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &100);
+    contract.init_pos(&0, &token_admin, &1, &1, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &100);
 }
 
 #[test]
@@ -374,22 +539,21 @@ fn test_mature_date_error_withdraw() {
         user_a,
         user_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
 
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
-    contract.withdraw(&user_a);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    contract.withdraw(&0, &user_a);
 }
 
 #[test]
@@ -400,18 +564,17 @@ fn test_set_spot_rate() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.set_spot(&token_admin, &forward_rate);
+    contract.set_spot(&0, &token_admin, &forward_rate);
 }
 
 #[test]
@@ -424,18 +587,17 @@ fn test_set_spot_rate_unauthorized() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.set_spot(&user_a, &forward_rate);
+    contract.set_spot(&0, &user_a, &forward_rate);
 }
 
 #[test]
@@ -449,20 +611,19 @@ fn test_deposit_amount_after_near_leg() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &100, &100);
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.deposit(&user_a, &token_a.address, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &100);
 }
 
 #[test]
@@ -475,21 +636,20 @@ fn test_deposit_collateral_after_near_leg() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let (amount_a, collateral_a) = contract.deposit(&user_a, &token_a.address, &0, &50);
+    let (amount_a, collateral_a) = contract.deposit(&0, &user_a, &token_a.address, &0, &50);
 
     assert_eq!(amount_a, 100);
     assert_eq!(collateral_a, 70);
@@ -506,30 +666,29 @@ fn test_swap() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
     assert_eq!(token_b.balance(&user_a), 0);
-    let swapped_amount = contract.swap(&user_a);
+    let swapped_amount = contract.swap(&0, &user_a);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_b.balance(&user_a), 100);
 
     assert_eq!(token_a.balance(&user_b), 0);
-    let swapped_amount = contract.swap(&user_b);
+    let swapped_amount = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_a.balance(&user_b), 100);
 }
@@ -546,40 +705,39 @@ fn test_swap_with_order() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
     assert_ne!(user_a, user_c);
 
     token_admin_client_a.mint(&user_c, &1000);
 
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_c, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
     assert_eq!(token_b.balance(&user_a), 0);
-    let swapped_amount = contract.swap(&user_a);
+    let swapped_amount = contract.swap(&0, &user_a);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_b.balance(&user_a), 100);
 
     assert_eq!(token_a.balance(&user_b), 0);
-    let swapped_amount = contract.swap(&user_b);
+    let swapped_amount = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount, 200);
     assert_eq!(token_a.balance(&user_b), 200);
 
-    let swapped_amount = contract.swap(&user_c);
+    let swapped_amount = contract.swap(&0, &user_c);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_b.balance(&user_c), 100);
 }
@@ -596,38 +754,37 @@ fn test_swap_with_order_not_enough() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
     token_admin_client_a.mint(&user_c, &1000);
 
-    contract.init_pos(&token_admin, &100, &100, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_c, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
     assert_eq!(token_b.balance(&user_a), 0);
-    let swapped_amount = contract.swap(&user_a);
+    let swapped_amount = contract.swap(&0, &user_a);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_b.balance(&user_a), 100);
 
     assert_eq!(token_a.balance(&user_b), 0);
-    let swapped_amount = contract.swap(&user_b);
+    let swapped_amount = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount, 100);
     assert_eq!(token_a.balance(&user_b), 100);
 
-    let swapped_amount = contract.swap(&user_c);
+    let swapped_amount = contract.swap(&0, &user_c);
     assert_eq!(swapped_amount, 0);
     assert_eq!(token_b.balance(&user_c), 0);
 }
@@ -643,28 +800,27 @@ fn test_reclaim_used_position_and_swap() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let reclaimed_a = contract.reclaim(&user_a);
+    let reclaimed_a = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_a, 0);
     assert_eq!(token_a.balance(&user_a), 880);
 
-    let swapped_a = contract.swap(&user_a);
-    let swapped_b = contract.swap(&user_b);
+    let swapped_a = contract.swap(&0, &user_a);
+    let swapped_b = contract.swap(&0, &user_b);
 
     assert_eq!(swapped_a, 100);
     assert_eq!(token_b.balance(&user_a), 100);
@@ -684,31 +840,30 @@ fn test_reclaim_unused_position_and_swap() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
     assert_eq!(token_a.balance(&user_a), 640);
-    let reclaimed_a = contract.reclaim(&user_a);
+    let reclaimed_a = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_a, 100);
     assert_eq!(token_a.balance(&user_a), 740);
 
-    let swapped_a = contract.swap(&user_a);
-    let swapped_b = contract.swap(&user_b);
+    let swapped_a = contract.swap(&0, &user_a);
+    let swapped_b = contract.swap(&0, &user_b);
 
     assert_eq!(swapped_a, 200);
     assert_eq!(token_b.balance(&user_a), 200);
@@ -728,26 +883,25 @@ fn test_repay_a() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
+    contract.swap(&0, &user_a);
 
     assert_eq!(token_b.balance(&user_a), 100);
-    let (repaid, total_amount_to_repay) = contract.repay(&user_a, &token_b.address, &100);
+    let (repaid, total_amount_to_repay) = contract.repay(&0, &user_a, &token_b.address, &100);
     assert_eq!(repaid, 100);
     assert_eq!(total_amount_to_repay, 100);
     assert_eq!(token_b.balance(&user_a), 0);
@@ -764,26 +918,25 @@ fn test_repay_b() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
 
     assert_eq!(token_a.balance(&user_b), 100);
-    let (repaid, total_amount_to_repay) = contract.repay(&user_b, &token_a.address, &100);
+    let (repaid, total_amount_to_repay) = contract.repay(&0, &user_b, &token_a.address, &100);
     assert_eq!(repaid, 100);
     assert_eq!(total_amount_to_repay, 100);
     assert_eq!(token_a.balance(&user_b), 0);
@@ -801,27 +954,26 @@ fn test_repay_error() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
+    contract.swap(&0, &user_a);
 
-    let (repaid, total_amount_to_repay) = contract.repay(&user_a, &token_b.address, &100);
+    let (repaid, total_amount_to_repay) = contract.repay(&0, &user_a, &token_b.address, &100);
     assert_eq!(repaid, 100);
     assert_eq!(total_amount_to_repay, 100);
-    contract.repay(&user_a, &token_b.address, &1);
+    contract.repay(&0, &user_a, &token_b.address, &1);
 }
 
 #[test]
@@ -835,27 +987,26 @@ fn test_repay_instances() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
+    contract.swap(&0, &user_a);
 
-    let (repaid, total_amount_to_repay) = contract.repay(&user_a, &token_b.address, &50);
+    let (repaid, total_amount_to_repay) = contract.repay(&0, &user_a, &token_b.address, &50);
     assert_eq!(repaid, 50);
     assert_eq!(total_amount_to_repay, 100);
-    let (repaid, total_amount_to_repay) = contract.repay(&user_a, &token_b.address, &50);
+    let (repaid, total_amount_to_repay) = contract.repay(&0, &user_a, &token_b.address, &50);
     assert_eq!(repaid, 100);
     assert_eq!(total_amount_to_repay, 100);
 }
@@ -871,24 +1022,23 @@ fn test_balance() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
-    );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
-    SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.repay(&user_a, &token_b.address, &100);
-    let balance = contract.balance(&user_a);
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    let balance = contract.balance(&0, &user_a);
     assert_eq!(
         balance,
         User {
@@ -901,6 +1051,8 @@ fn test_balance() {
             collateral: 20,
             withdrawn_collateral: 0,
             is_liquidated: false,
+            liquidated_amount: 0,
+            liquidated_collateral: 0,
         }
     );
 }
@@ -916,33 +1068,32 @@ fn test_withdraw_a() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &100);
-    contract.repay(&user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
     assert_eq!(token_a.balance(&user_a), 880);
     assert_eq!(token_b.balance(&user_a), 0);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (100, 0));
     assert_eq!(token_a.balance(&user_a), 980);
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 0));
     assert_eq!(token_a.balance(&user_a), 980);
 }
@@ -958,32 +1109,31 @@ fn test_withdraw_b() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &100);
-    contract.repay(&user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
     assert_eq!(token_b.balance(&user_b), 760);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 100));
     assert_eq!(token_b.balance(&user_b), 860);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 0));
     assert_eq!(token_b.balance(&user_b), 860);
 }
@@ -1005,24 +1155,22 @@ fn test_withdraw_liquidated() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
     oracle_client.set_spot_rate(&85_000_000_000_000);
-    contract.liquidate(&user_a, &token_admin);
+    contract.liquidate(&0, &user_a, &token_admin);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    contract.withdraw(&user_a);
+    contract.withdraw(&0, &user_a);
 }
 
 #[test]
@@ -1038,36 +1186,35 @@ fn test_withdraw_a_insuficient_balance() {
         contract,
         token_admin_client_a,
         token_admin_client_b,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_a, &200);
     token_admin_client_b.mint(&user_b, &200);
-    contract.init_pos(&token_admin, &2, &2, &1000);
-    contract.deposit(&user_a, &token_a.address, &1000, &200);
-    contract.deposit(&user_b, &token_b.address, &1000, &200);
+    contract.init_pos(&0, &token_admin, &2, &2, &1000);
+    contract.deposit(&0, &user_a, &token_a.address, &1000, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &1000, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &1000);
+    contract.repay(&0, &user_a, &token_b.address, &1000);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 0));
 
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 1000));
     assert_eq!(token_a.balance(&user_a), 0);
     assert_eq!(token_b.balance(&user_a), 1000);
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 0));
     assert_eq!(token_b.balance(&user_a), 1000);
 }
@@ -1085,41 +1232,40 @@ fn test_withdraw_b_insuficient_balance() {
         contract,
         token_admin_client_a,
         token_admin_client_b,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_a, &200);
     token_admin_client_b.mint(&user_b, &200);
-    contract.init_pos(&token_admin, &2, &2, &1000);
-    contract.deposit(&user_a, &token_a.address, &1000, &200);
-    contract.deposit(&user_b, &token_b.address, &1000, &200);
+    contract.init_pos(&0, &token_admin, &2, &2, &1000);
+    contract.deposit(&0, &user_a, &token_a.address, &1000, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &1000, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &1000);
+    contract.repay(&0, &user_b, &token_a.address, &1000);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 0));
 
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (1000, 0));
     assert_eq!(token_a.balance(&user_b), 1000);
     assert_eq!(token_b.balance(&user_b), 0);
 
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 0));
     assert_eq!(token_a.balance(&user_b), 1000);
 
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 0));
 }
 
@@ -1142,42 +1288,40 @@ fn test_withdraw_b_multiple_a_insuficient_balance() {
     oracle_client.set_spot_rate(&80_000_000_000_000);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
 
     token_admin_client_a.mint(&user_a, &200);
     token_admin_client_a.mint(&user_c, &600);
     token_admin_client_b.mint(&user_b, &200);
-    contract.init_pos(&token_admin, &2, &1, &500);
-    contract.deposit(&user_a, &token_a.address, &500, &100);
-    contract.deposit(&user_c, &token_a.address, &500, &100);
-    contract.deposit(&user_b, &token_b.address, &800, &160);
-    SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
-    contract.swap(&user_c);
+    contract.init_pos(&0, &token_admin, &2, &1, &500);
+    contract.deposit(&0, &user_a, &token_a.address, &500, &100);
+    contract.deposit(&0, &user_c, &token_a.address, &500, &100);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &160);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    contract.swap(&0, &user_c);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &375);
-    contract.repay(&user_b, &token_a.address, &1000);
+    contract.repay(&0, &user_a, &token_b.address, &375);
+    contract.repay(&0, &user_b, &token_a.address, &1000);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    contract.liquidate(&user_c, &user_a);
-    let withdrawn_amount = contract.withdraw(&user_a);
+    contract.liquidate(&0, &user_c, &user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (500, 0));
 
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (468, 375));
     assert_eq!(token_a.balance(&user_b), 468);
     assert_eq!(token_b.balance(&user_b), 615);
 
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 0));
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (0, 0));
 }
 
@@ -1200,39 +1344,37 @@ fn test_withdraw_a_multiples_b_insuficient_balance() {
     oracle_client.set_spot_rate(&80_000_000_000_000);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
 
     token_admin_client_a.mint(&user_a, &200);
     token_admin_client_b.mint(&user_b, &200);
     token_admin_client_b.mint(&user_c, &600);
-    contract.init_pos(&token_admin, &1, &2, &1000);
-    contract.deposit(&user_a, &token_a.address, &1000, &200);
-    contract.deposit(&user_b, &token_b.address, &400, &80);
-    contract.deposit(&user_c, &token_b.address, &400, &80);
-    SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
-    contract.swap(&user_c);
+    contract.init_pos(&0, &token_admin, &1, &2, &1000);
+    contract.deposit(&0, &user_a, &token_a.address, &1000, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &400, &80);
+    contract.deposit(&0, &user_c, &token_b.address, &400, &80);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    contract.swap(&0, &user_c);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &750);
-    contract.repay(&user_b, &token_a.address, &500);
+    contract.repay(&0, &user_a, &token_b.address, &750);
+    contract.repay(&0, &user_b, &token_a.address, &500);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    contract.liquidate(&user_c, &user_b);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    contract.liquidate(&0, &user_c, &user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 375));
 
-    let withdrawn_amount = contract.withdraw(&user_a);
+    let withdrawn_amount = contract.withdraw(&0, &user_a);
     assert_eq!(withdrawn_amount, (500, 400));
     assert_eq!(token_a.balance(&user_a), 500);
     assert_eq!(token_b.balance(&user_a), 450);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 0));
     assert_eq!(token_a.balance(&user_a), 500);
 }
@@ -1261,28 +1403,27 @@ fn test_withdraw_liquidated_devaluation() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_REPAY,
+        &InitParams {
+            forward_rate,
+            duration: TIME_TO_REPAY,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let deposit_amount_b = contract.init_pos(&token_admin, &10, &10, &10_000_000);
+    let deposit_amount_b = contract.init_pos(&0, &token_admin, &10, &10, &10_000_000);
     assert_eq!(deposit_amount_b, 5_263_157);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &5_263_157, &1_052_632);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &5_263_157, &1_052_632);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     oracle_client.set_spot_rate(&43_668_122_270_000);
-    let reward_amount_a = contract.liquidate(&user_a, &token_admin);
+    let reward_amount_a = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount_a, 20_000);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &10_000_000);
+    contract.repay(&0, &user_b, &token_a.address, &10_000_000);
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_b = contract.withdraw(&user_b);
+    let withdrawn_b = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_b, (11_979_998, 0));
 }
 
@@ -1297,21 +1438,20 @@ fn test_reclaim_at_deposit() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
-    contract.reclaim(&user_b);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    contract.reclaim(&0, &user_b);
 }
 
 #[test]
@@ -1331,26 +1471,24 @@ fn test_reclaim_a_at_swap() {
     oracle_client.set_spot_rate(&93_105_303_347_992);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &186, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &186, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 0);
     assert_eq!(token_a.balance(&user_a), 880);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 0);
 }
 
@@ -1371,30 +1509,28 @@ fn test_reclaim_b_at_swap() {
     oracle_client.set_spot_rate(&93_105_303_347_992);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &10, &5, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &186, &40);
+    contract.init_pos(&0, &token_admin, &10, &5, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &186, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 92);
     assert_eq!(token_b.balance(&user_b), 866);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 0);
     assert_eq!(token_b.balance(&user_b), 866);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 0);
 }
 
@@ -1415,27 +1551,25 @@ fn test_reclaim_a_at_swap_one_deposit() {
     oracle_client.set_spot_rate(&93_105_303_347_992);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &10, &10, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &10, &10, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let swapped_a = contract.swap(&user_a);
-    let swapped_b = contract.swap(&user_b);
+    let swapped_a = contract.swap(&0, &user_a);
+    let swapped_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_a, 0);
     assert_eq!(swapped_b, 0);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 99);
     assert_eq!(token_a.balance(&user_a), 979);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 0);
 }
 
@@ -1456,27 +1590,25 @@ fn test_reclaim_b_at_swap_one_deposit() {
     oracle_client.set_spot_rate(&93_105_303_347_992);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &10, &10, &100);
-    contract.deposit(&user_b, &token_b.address, &93, &20);
+    contract.init_pos(&0, &token_admin, &10, &10, &100);
+    contract.deposit(&0, &user_b, &token_b.address, &93, &20);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let swapped_b = contract.swap(&user_a);
-    let swapped_a = contract.swap(&user_b);
+    let swapped_b = contract.swap(&0, &user_a);
+    let swapped_a = contract.swap(&0, &user_b);
     assert_eq!(swapped_a, 0);
     assert_eq!(swapped_b, 0);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 92);
     assert_eq!(token_b.balance(&user_b), 979);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 0);
 }
 
@@ -1491,32 +1623,31 @@ fn test_reclaim_at_repay() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let swap_amount = contract.swap(&user_b);
-    contract.swap(&user_a);
+    let swap_amount = contract.swap(&0, &user_b);
+    contract.swap(&0, &user_a);
     assert_eq!(swap_amount, 100);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &100);
-    contract.repay(&user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
     assert_eq!(token_b.balance(&user_b), 760);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 100);
     assert_eq!(token_b.balance(&user_b), 860);
 }
@@ -1532,35 +1663,34 @@ fn test_reclaim_at_withdraw() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let swap_amount = contract.swap(&user_b);
-    contract.swap(&user_a);
+    let swap_amount = contract.swap(&0, &user_b);
+    contract.swap(&0, &user_a);
     assert_eq!(swap_amount, 100);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &100);
-    contract.repay(&user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
     assert_eq!(token_b.balance(&user_b), 760);
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    contract.withdraw(&user_b);
+    contract.withdraw(&0, &user_b);
     assert_eq!(token_b.balance(&user_b), 860);
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 100);
     assert_eq!(token_b.balance(&user_b), 960);
 }
@@ -1577,51 +1707,50 @@ fn test_full_reclaim() {
         token_b,
         contract,
         token_admin_client_b,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
     assert_ne!(user_a, user_c);
     token_admin_client_b.mint(&user_c, &120);
 
-    contract.init_pos(&token_admin, &10, &10, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &100, &20);
-    contract.deposit(&user_c, &token_b.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &10, &10, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_b.address, &100, &20);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    let swap_amount = contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    let swap_amount = contract.swap(&0, &user_b);
     assert_eq!(swap_amount, 100);
 
     assert_eq!(token_b.balance(&user_c), 0);
-    let reclaimed_deposit = contract.reclaim(&user_c);
+    let reclaimed_deposit = contract.reclaim(&0, &user_c);
     assert_eq!(reclaimed_deposit, 100);
     assert_eq!(token_b.balance(&user_c), 100);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &100);
-    contract.repay(&user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
     assert_eq!(token_b.balance(&user_b), 880);
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let withdrawn_amount = contract.withdraw(&user_b);
+    let withdrawn_amount = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount, (0, 100));
 
     assert_eq!(token_b.balance(&user_b), 980);
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 0);
     assert_eq!(token_b.balance(&user_b), 980);
 
-    assert_eq!(contract.withdraw(&user_c), (0, 0));
+    assert_eq!(contract.withdraw(&0, &user_c), (0, 0));
     assert_eq!(token_b.balance(&user_c), 100);
 }
 
@@ -1636,48 +1765,47 @@ fn test_multiple_reclaim() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let swap_amount = contract.swap(&user_b);
-    contract.swap(&user_a);
+    let swap_amount = contract.swap(&0, &user_b);
+    contract.swap(&0, &user_a);
     assert_eq!(swap_amount, 100);
 
     assert_eq!(token_b.balance(&user_b), 760);
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 100);
     assert_eq!(token_b.balance(&user_b), 860);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_b, &token_a.address, &100);
-    contract.repay(&user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
     assert_eq!(token_b.balance(&user_b), 860);
-    contract.withdraw(&user_b);
+    contract.withdraw(&0, &user_b);
     assert_eq!(token_b.balance(&user_b), 960);
 
-    let reclaimed_deposit = contract.reclaim(&user_b);
+    let reclaimed_deposit = contract.reclaim(&0, &user_b);
     assert_eq!(reclaimed_deposit, 0);
     assert_eq!(token_b.balance(&user_b), 960);
 
-    let reclaimed_collateral = contract.reclaim_col(&user_b);
+    let reclaimed_collateral = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaimed_collateral, 40);
     assert_eq!(token_b.balance(&user_b), 1000);
 
-    let reclaimed_deposit = contract.reclaim(&user_a);
+    let reclaimed_deposit = contract.reclaim(&0, &user_a);
     assert_eq!(reclaimed_deposit, 0);
     assert_eq!(token_a.balance(&user_a), 880);
 }
@@ -1694,33 +1822,32 @@ fn test_reclaim_for_non_participant() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
     assert_eq!(token_a.balance(&user_c), 0);
     assert_eq!(token_b.balance(&user_c), 0);
 
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &100);
-    contract.repay(&user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    contract.reclaim(&user_c);
+    contract.reclaim(&0, &user_c);
 }
 
 #[test]
@@ -1734,31 +1861,30 @@ fn test_reclaim_collateral() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &100);
-    contract.repay(&user_b, &token_a.address, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    contract.withdraw(&user_a);
+    contract.withdraw(&0, &user_a);
     assert_eq!(token_a.balance(&user_a), 980);
 
-    let reclaimed_collateral = contract.reclaim_col(&user_a);
+    let reclaimed_collateral = contract.reclaim_col(&0, &user_a);
     assert_eq!(reclaimed_collateral, 20);
     assert_eq!(token_a.balance(&user_a), 1000);
 }
@@ -1779,27 +1905,26 @@ fn test_liquidated_reclaim_col() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &50, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &200);
-    contract.deposit(&user_b, &token_b.address, &200, &400);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &400);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 2);
+    // The seized collateral is fixed at liquidation, later prices don't change it
     oracle_client.set_spot_rate(&50_000_000_000_000);
-    let reclaimed_collateral = contract.reclaim_col(&user_a);
-    assert_eq!(reclaimed_collateral, 75);
-    assert_eq!(token_a.balance(&user_a), 775);
+    let reclaimed_collateral = contract.reclaim_col(&0, &user_a);
+    assert_eq!(reclaimed_collateral, 180);
+    assert_eq!(token_a.balance(&user_a), 880);
 }
 
 #[test]
@@ -1818,22 +1943,20 @@ fn test_liquidate_swap() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &100, &800);
-    contract.deposit(&user_a, &token_a.address, &800, &200);
-    contract.deposit(&user_b, &token_b.address, &800, &200);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     assert_eq!(token_a.balance(&user_b), 800);
     oracle_client.set_spot_rate(&70_000_000_000_000);
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 2);
     assert_eq!(token_a.balance(&token_admin), 2);
 }
@@ -1854,22 +1977,20 @@ fn test_liquidate_reclaimed_collateral() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &100, &800);
-    contract.deposit(&user_a, &token_a.address, &800, &200);
-    contract.deposit(&user_b, &token_b.address, &800, &200);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     oracle_client.set_spot_rate(&70_000_000_000_000);
-    contract.reclaim_col(&user_a);
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    contract.reclaim_col(&0, &user_a);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 2);
     assert_eq!(token_a.balance(&token_admin), 2);
 }
@@ -1886,26 +2007,25 @@ fn test_no_liquidate_swap() {
         token_b,
         contract,
         token_admin_client_a,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     token_admin_client_a.mint(&user_a, &100);
-    contract.init_pos(&token_admin, &100, &100, &800);
-    contract.deposit(&user_a, &token_a.address, &800, &200);
-    contract.deposit(&user_b, &token_b.address, &800, &200);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     assert_eq!(token_a.balance(&user_b), 800);
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 0);
     assert_eq!(token_a.balance(&token_admin), 0);
 }
@@ -1926,25 +2046,23 @@ fn test_liquidate_repay() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &10, &10, &800);
-    contract.deposit(&user_a, &token_a.address, &800, &200);
-    contract.deposit(&user_b, &token_b.address, &800, &200);
+    contract.init_pos(&0, &token_admin, &10, &10, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    contract.repay(&user_a, &token_b.address, &800);
+    contract.repay(&0, &user_a, &token_b.address, &800);
     SwapTest::add_time(&e, TIME_TO_REPAY);
     assert_eq!(token_a.balance(&user_b), 800);
     oracle_client.set_spot_rate(&120_000_000_000_000);
-    let reward_amount = contract.liquidate(&user_b, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_b, &token_admin);
     assert_eq!(reward_amount, 2);
     assert_eq!(token_b.balance(&token_admin), 2);
 }
@@ -1965,26 +2083,24 @@ fn test_liquidate_liquidated_user() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &100, &100, &800);
-    contract.deposit(&user_a, &token_a.address, &800, &200);
-    contract.deposit(&user_b, &token_b.address, &800, &200);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    contract.swap(&user_a);
-    contract.swap(&user_b);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
     assert_eq!(token_a.balance(&user_b), 800);
     oracle_client.set_spot_rate(&70_000_000_000_000);
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 2);
     assert_eq!(token_a.balance(&token_admin), 2);
 
-    let reward_amount = contract.liquidate(&user_a, &token_admin);
+    let reward_amount = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount, 0);
     assert_eq!(token_a.balance(&token_admin), 2);
 }
@@ -2014,24 +2130,24 @@ fn test_forward_bigger_than_spot() {
 
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let amount_to_deposit_b = contract.init_pos(&token_admin, &100, &100, &(10_000 * decimals));
-    let amount_col_b = amount_to_deposit_b * &COLLATERAL_BUFFER / 100;
+    let amount_to_deposit_b = contract.init_pos(&0, &token_admin, &100, &100, &(10_000 * decimals));
+    let amount_col_b = (amount_to_deposit_b * COLLATERAL_BUFFER + 99) / 100;
     assert_eq!(amount_to_deposit_b, 523_560_209_424);
 
     contract.deposit(
+        &0,
         &user_a,
         &token_a.address,
         &(10_000 * decimals),
         &(2_000 * decimals),
     );
     contract.deposit(
+        &0,
         &user_b,
         &token_b.address,
         &amount_to_deposit_b,
@@ -2040,8 +2156,8 @@ fn test_forward_bigger_than_spot() {
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 523_560_209_424);
     assert_eq!(swapped_amount_b, 999_999_999_999);
 
@@ -2050,22 +2166,22 @@ fn test_forward_bigger_than_spot() {
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
-    token_admin_client_b.mint(&user_a, &27_55_580_049);
-    let repay_a = contract.repay(&user_a, &token_b.address, &526_315_789_473);
-    let repay_b = contract.repay(&user_b, &token_a.address, &999_999_999_999);
-    assert_eq!(repay_a, (526_315_789_473, 526_315_789_473));
+    token_admin_client_b.mint(&user_a, &2_755_580_050);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &526_315_789_474);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &999_999_999_999);
+    assert_eq!(repay_a, (526_315_789_474, 526_315_789_474));
     assert_eq!(repay_b, (999_999_999_999, 999_999_999_999));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    assert_eq!(withdrawn_amount_a, (999_999_999_998, 0));
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    assert_eq!(withdrawn_amount_a, (999_999_999_999, 0));
     assert_eq!(withdrawn_amount_b, (0, 526_315_789_473));
-    assert_eq!(token_a.balance(&user_a), 1_000_000_000_998);
-    assert_eq!(token_b.balance(&user_b), 898_043_539_165);
+    assert_eq!(token_a.balance(&user_a), 1_000_000_000_999);
+    assert_eq!(token_b.balance(&user_b), 898_043_539_164);
 
-    let reclaim_col_b = contract.reclaim_col(&user_b);
+    let reclaim_col_b = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaim_col_b, amount_col_b);
     assert_eq!(token_b.balance(&user_b), 1_002_755_581_049);
 }
@@ -2095,24 +2211,24 @@ fn test_forward_smaller_than_spot() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("GBP"),
-        &symbol_short!("USD"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let amount_deposit_b = contract.init_pos(&token_admin, &100, &100, &(10_000 * decimals));
+    let amount_deposit_b = contract.init_pos(&0, &token_admin, &100, &100, &(10_000 * decimals));
     assert_eq!(amount_deposit_b, 526_315_789_473);
-    let token_b_collateral = 526_315_789_473 * COLLATERAL_BUFFER / 100;
+    let token_b_collateral = (526_315_789_473 * COLLATERAL_BUFFER + 99) / 100;
 
     contract.deposit(
+        &0,
         &user_a,
         &token_a.address,
         &(10_000 * decimals),
         &(2_000 * decimals),
     );
     contract.deposit(
+        &0,
         &user_b,
         &token_b.address,
         &526_315_789_473,
@@ -2120,8 +2236,8 @@ fn test_forward_smaller_than_spot() {
     );
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 526_315_789_473);
     assert_eq!(swapped_amount_b, 999_999_999_998);
 
@@ -2130,28 +2246,28 @@ fn test_forward_smaller_than_spot() {
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
-    let repay_a = contract.repay(&user_a, &token_b.address, &523_560_209_423);
-    let repay_b = contract.repay(&user_b, &token_a.address, &999_999_999_998);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &523_560_209_424);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &999_999_999_998);
 
-    assert_eq!(repay_a, (523_560_209_423, 523_560_209_423));
+    assert_eq!(repay_a, (523_560_209_424, 523_560_209_424));
     assert_eq!(repay_b, (999_999_999_998, 999_999_999_998));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    assert_eq!(withdrawn_amount_a, (999_999_999_997, 0));
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    assert_eq!(withdrawn_amount_a, (999_999_999_998, 0));
     assert_eq!(withdrawn_amount_b, (0, 523_560_209_423));
-    assert_eq!(token_a.balance(&user_a), 1_000_000_000_997);
-    assert_eq!(token_b.balance(&user_b), 891_981_263_056);
+    assert_eq!(token_a.balance(&user_a), 1_000_000_000_998);
+    assert_eq!(token_b.balance(&user_b), 891_981_263_055);
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
     assert_eq!(reclaim_amount_a, 0);
 
-    let reclaim_amount_b = contract.reclaim(&user_b);
+    let reclaim_amount_b = contract.reclaim(&0, &user_b);
     assert_eq!(reclaim_amount_b, 0);
 
-    let reclaim_col_amount_b = contract.reclaim_col(&user_b);
+    let reclaim_col_amount_b = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaim_col_amount_b, token_b_collateral);
 }
 
@@ -2179,36 +2295,35 @@ fn test_liquidate_devaluation() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_REPAY,
+        &InitParams {
+            forward_rate,
+            duration: TIME_TO_REPAY,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let deposit_amount_b = contract.init_pos(&token_admin, &10, &10, &10_000_000);
+    let deposit_amount_b = contract.init_pos(&0, &token_admin, &10, &10, &10_000_000);
     assert_eq!(deposit_amount_b, 5_263_157);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &5_263_157, &1_052_632);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &5_263_157, &1_052_632);
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 5_263_157);
     assert_eq!(swapped_amount_b, 9_999_998);
 
     oracle_client.set_spot_rate(&45_668_122_270_000);
 
-    let reward_amount_a = contract.liquidate(&user_a, &token_admin);
+    let reward_amount_a = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount_a, 0);
-    let reward_amount_b = contract.liquidate(&user_b, &token_admin);
+    let reward_amount_b = contract.liquidate(&0, &user_b, &token_admin);
     assert_eq!(reward_amount_b, 0);
 
     oracle_client.set_spot_rate(&43_668_122_270_000);
 
-    let reward_amount_a = contract.liquidate(&user_a, &token_admin);
+    let reward_amount_a = contract.liquidate(&0, &user_a, &token_admin);
     assert_eq!(reward_amount_a, 20_000);
-    let reward_amount_b = contract.liquidate(&user_b, &token_admin);
+    let reward_amount_b = contract.liquidate(&0, &user_b, &token_admin);
     assert_eq!(reward_amount_b, 0);
 }
 
@@ -2224,6 +2339,7 @@ fn test_one_to_many_position() {
         contract,
         token_admin_client_a,
         token_admin_client_b,
+        oracle_client,
         ..
     } = SwapTest::setup();
     let forward_rate: i128 = SCALE;
@@ -2234,22 +2350,22 @@ fn test_one_to_many_position() {
 
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let token_b_collateral = 333_333_333_333 * COLLATERAL_BUFFER / 100;
-    contract.init_pos(&token_admin, &1, &3, &(10_000 * decimals));
+    let token_b_collateral = (333_333_333_333 * COLLATERAL_BUFFER + 99) / 100;
+    contract.init_pos(&0, &token_admin, &1, &3, &(10_000 * decimals));
     contract.deposit(
+        &0,
         &user_a,
         &token_a.address,
         &(10_000 * decimals),
         &(2_000 * decimals),
     );
     contract.deposit(
+        &0,
         &user_b,
         &token_b.address,
         &333_333_333_333,
@@ -2262,12 +2378,14 @@ fn test_one_to_many_position() {
     token_admin_client_b.mint(&user_d, &(333_333_333_333 + token_b_collateral));
 
     contract.deposit(
+        &0,
         &user_c,
         &token_b.address,
         &333_333_333_333,
         &token_b_collateral,
     );
     contract.deposit(
+        &0,
         &user_d,
         &token_b.address,
         &333_333_333_333,
@@ -2276,10 +2394,10 @@ fn test_one_to_many_position() {
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
-    let swapped_amount_c = contract.swap(&user_c);
-    let swapped_amount_d = contract.swap(&user_d);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
+    let swapped_amount_c = contract.swap(&0, &user_c);
+    let swapped_amount_d = contract.swap(&0, &user_d);
     assert_eq!(swapped_amount_a, 999_999_999_999);
     assert_eq!(swapped_amount_b, 333_333_333_333);
     assert_eq!(swapped_amount_c, 333_333_333_333);
@@ -2292,10 +2410,10 @@ fn test_one_to_many_position() {
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
-    let repay_a = contract.repay(&user_a, &token_b.address, &999_999_999_999);
-    let repay_b = contract.repay(&user_b, &token_a.address, &333_333_333_333);
-    let repay_c = contract.repay(&user_c, &token_a.address, &333_333_333_333);
-    let repay_d = contract.repay(&user_d, &token_a.address, &333_333_333_333);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &999_999_999_999);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &333_333_333_333);
+    let repay_c = contract.repay(&0, &user_c, &token_a.address, &333_333_333_333);
+    let repay_d = contract.repay(&0, &user_d, &token_a.address, &333_333_333_333);
 
     assert_eq!(repay_a, (999_999_999_999, 999_999_999_999));
     assert_eq!(repay_b, (333_333_333_333, 333_333_333_333));
@@ -2304,10 +2422,10 @@ fn test_one_to_many_position() {
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    let withdrawn_amount_c = contract.withdraw(&user_c);
-    let withdrawn_amount_d = contract.withdraw(&user_d);
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    let withdrawn_amount_c = contract.withdraw(&0, &user_c);
+    let withdrawn_amount_d = contract.withdraw(&0, &user_d);
     assert_eq!(withdrawn_amount_a, (999_999_999_999, 0));
     assert_eq!(withdrawn_amount_b, (0, 333_333_333_333));
     assert_eq!(withdrawn_amount_c, (0, 333_333_333_333));
@@ -2323,27 +2441,26 @@ fn test_stage() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    let stage = contract.stage();
+    let stage = contract.stage(&0);
     assert_eq!(stage, Stage::Deposit);
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    let stage = contract.stage();
+    let stage = contract.stage(&0);
     assert_eq!(stage, Stage::Swap);
     SwapTest::add_time(&e, TIME_TO_MATURE);
-    let stage = contract.stage();
+    let stage = contract.stage(&0);
     assert_eq!(stage, Stage::Repay);
     SwapTest::add_time(&e, TIME_TO_REPAY);
-    let stage = contract.stage();
+    let stage = contract.stage(&0);
     assert_eq!(stage, Stage::Withdraw);
 }
 
@@ -2365,12 +2482,10 @@ fn test_users() {
     } = SwapTest::setup();
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USDC"),
-        &symbol_short!("EURC"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
     let user_c = Address::generate(&e);
     let user_d = Address::generate(&e);
@@ -2379,22 +2494,22 @@ fn test_users() {
     token_admin_client_a.mint(&user_d, &1_000);
     token_admin_client_b.mint(&user_e, &1_000);
 
-    contract.init_pos(&token_admin, &100, &50, &100);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
 
-    contract.deposit(&user_a, &token_a.address, &100, &20);
-    contract.deposit(&user_c, &token_a.address, &100, &20);
-    contract.deposit(&user_d, &token_a.address, &100, &20);
-    contract.deposit(&user_b, &token_b.address, &200, &40);
-    contract.deposit(&user_e, &token_b.address, &200, &40);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_d, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    contract.deposit(&0, &user_e, &token_b.address, &200, &40);
 
     SwapTest::add_time(&e, TIME_TO_EXEC);
-    assert_eq!(contract.swap(&user_a), 100);
-    assert_eq!(contract.swap(&user_c), 100);
-    assert_eq!(contract.swap(&user_d), 100);
-    assert_eq!(contract.swap(&user_b), 200);
-    assert_eq!(contract.swap(&user_e), 100);
+    assert_eq!(contract.swap(&0, &user_a), 100);
+    assert_eq!(contract.swap(&0, &user_c), 100);
+    assert_eq!(contract.swap(&0, &user_d), 100);
+    assert_eq!(contract.swap(&0, &user_b), 200);
+    assert_eq!(contract.swap(&0, &user_e), 100);
 
-    let (users_a, users_b) = contract.users();
+    let (users_a, users_b) = contract.users(&0, &0, &10);
     assert_eq!(
         users_a,
         Vec::from_array(
@@ -2404,19 +2519,25 @@ fn test_users() {
                     address: user_a.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_c.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_d.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
@@ -2431,20 +2552,33 @@ fn test_users() {
                     address: user_b.clone(),
                     collateral: 40,
                     min_collateral: 40,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_e.clone(),
                     collateral: 40,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
     );
 
+    // Users of the second deposit in each token
+    let (users_a, users_b) = contract.users(&0, &1, &1);
+    assert_eq!(users_a.len(), 1);
+    assert_eq!(users_a.get_unchecked(0).address, user_c);
+    assert_eq!(users_b.len(), 1);
+    assert_eq!(users_b.get_unchecked(0).address, user_e);
+    let (users_a, users_b) = contract.users(&0, &3, &10);
+    assert_eq!((users_a.len(), users_b.len()), (0, 0));
+
     oracle_client.set_spot_rate(&90_000_000_000_000);
-    let (users_a, users_b) = contract.users();
+    let (users_a, users_b) = contract.users(&0, &0, &10);
     assert_eq!(
         users_a,
         Vec::from_array(
@@ -2454,19 +2588,25 @@ fn test_users() {
                     address: user_a.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_c.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_d.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
@@ -2481,20 +2621,24 @@ fn test_users() {
                     address: user_b.clone(),
                     collateral: 40,
                     min_collateral: 40,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_e.clone(),
                     collateral: 40,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
     );
 
     oracle_client.set_spot_rate(&200_000_000_000_000); // 1 USDC  = 2 EURC
-    let (users_a, users_b) = contract.users();
+    let (users_a, users_b) = contract.users(&0, &0, &10);
     assert_eq!(
         users_a,
         Vec::from_array(
@@ -2504,19 +2648,25 @@ fn test_users() {
                     address: user_a.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_c.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_d.clone(),
                     collateral: 20,
                     min_collateral: 20,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
@@ -2531,13 +2681,17 @@ fn test_users() {
                     address: user_b.clone(),
                     collateral: 40,
                     min_collateral: 250,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 },
                 UserLiqData {
                     address: user_e.clone(),
                     collateral: 40,
                     min_collateral: 125,
-                    is_liquidated: false
+                    is_liquidated: false,
+                    liquidated_amount: 0,
+                    liquidated_collateral: 0,
                 }
             ]
         )
@@ -2567,50 +2721,48 @@ fn test_e2e_round_values() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &2, &2, &10_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &9_000_000, &1_800_000);
+    contract.init_pos(&0, &token_admin, &2, &2, &10_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &9_000_000, &1_800_000);
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 9_000_000);
     assert_eq!(swapped_amount_b, 10_000_000);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
     token_admin_client_b.mint(&user_a, &100_000);
-    let repay_a = contract.repay(&user_a, &token_b.address, &9_100_000);
-    let repay_b = contract.repay(&user_b, &token_a.address, &10_000_000);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &9_100_000);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &10_000_000);
 
     assert_eq!(repay_a, (9_100_000, 9_100_000));
     assert_eq!(repay_b, (10_000_000, 10_000_000));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
     assert_eq!(withdrawn_amount_a, (10_000_000, 0));
     assert_eq!(withdrawn_amount_b, (0, 9_100_000));
     assert_eq!(token_a.balance(&user_a), 10_000_000);
     assert_eq!(token_b.balance(&user_b), 9_100_000);
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
-    let reclaim_amount_b = contract.reclaim(&user_b);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
+    let reclaim_amount_b = contract.reclaim(&0, &user_b);
     assert_eq!(reclaim_amount_a, 0);
     assert_eq!(reclaim_amount_b, 0);
     assert_eq!(token_a.balance(&user_a), 10_000_000);
     assert_eq!(token_b.balance(&user_b), 9_100_000);
 
-    let reclaim_col_a = contract.reclaim_col(&user_a);
-    let reclaim_col_b = contract.reclaim_col(&user_b);
+    let reclaim_col_a = contract.reclaim_col(&0, &user_a);
+    let reclaim_col_b = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaim_col_a, 2_000_000);
     assert_eq!(reclaim_col_b, 1_800_000);
 
@@ -2644,63 +2796,61 @@ fn test_e2e() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &2, &2, &10_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &9_186_324, &1_837_265);
-    contract.deposit(&user_c, &token_a.address, &10_000_000, &2_000_000);
+    contract.init_pos(&0, &token_admin, &2, &2, &10_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &9_186_324, &1_837_265);
+    contract.deposit(&0, &user_c, &token_a.address, &10_000_000, &2_000_000);
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_c = contract.swap(&user_c);
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_c = contract.swap(&0, &user_c);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_c, 0);
     assert_eq!(swapped_amount_a, 9_186_324);
     assert_eq!(swapped_amount_b, 9_999_999);
 
-    let reclaim_amount_c = contract.reclaim(&user_c);
+    let reclaim_amount_c = contract.reclaim(&0, &user_c);
     assert_eq!(reclaim_amount_c, 10_000_000);
 
-    let reclaim_col_c = contract.reclaim_col(&user_c);
+    let reclaim_col_c = contract.reclaim_col(&0, &user_c);
     assert_eq!(reclaim_col_c, 2_000_000);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
     token_admin_client_b.mint(&user_a, &7_892);
-    let repay_a = contract.repay(&user_a, &token_b.address, &9_194_214);
-    let repay_b = contract.repay(&user_b, &token_a.address, &9_999_999);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &9_194_216);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &9_999_999);
 
-    assert_eq!(repay_a, (9_194_214, 9_194_214));
+    assert_eq!(repay_a, (9_194_216, 9_194_216));
     assert_eq!(repay_b, (9_999_999, 9_999_999));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    assert_eq!(withdrawn_amount_a, (9_999_998, 0));
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    assert_eq!(withdrawn_amount_a, (9_999_999, 0));
     assert_eq!(withdrawn_amount_b, (0, 9_194_214));
-    assert_eq!(token_a.balance(&user_a), 9_999_998);
+    assert_eq!(token_a.balance(&user_a), 9_999_999);
     assert_eq!(token_b.balance(&user_b), 9_194_214);
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
-    let reclaim_amount_b = contract.reclaim(&user_b);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
+    let reclaim_amount_b = contract.reclaim(&0, &user_b);
     assert_eq!(reclaim_amount_a, 0);
     assert_eq!(reclaim_amount_b, 0);
-    assert_eq!(token_a.balance(&user_a), 9_999_998);
+    assert_eq!(token_a.balance(&user_a), 9_999_999);
     assert_eq!(token_b.balance(&user_b), 9_194_214);
 
-    let reclaim_col_a = contract.reclaim_col(&user_a);
-    let reclaim_col_b = contract.reclaim_col(&user_b);
+    let reclaim_col_a = contract.reclaim_col(&0, &user_a);
+    let reclaim_col_b = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaim_col_a, 2_000_000);
     assert_eq!(reclaim_col_b, 1_837_265);
 
-    assert_eq!(token_a.balance(&user_a), 11_999_998);
+    assert_eq!(token_a.balance(&user_a), 11_999_999);
     assert_eq!(token_b.balance(&user_b), 11_031_479);
 }
 
@@ -2728,56 +2878,54 @@ fn test_e2e_multiple_deposits() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &2, &2, &10_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &9_186_324, &1_837_265);
-    contract.deposit(&user_b, &token_b.address, &9_186_324, &1_837_265);
+    contract.init_pos(&0, &token_admin, &2, &2, &10_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &9_186_324, &1_837_265);
+    contract.deposit(&0, &user_b, &token_b.address, &9_186_324, &1_837_265);
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 18_372_648);
     assert_eq!(swapped_amount_b, 19_999_998);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
     token_admin_client_b.mint(&user_a, &15784);
-    let repay_a = contract.repay(&user_a, &token_b.address, &18_388_429);
-    let repay_b = contract.repay(&user_b, &token_a.address, &19_999_998);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &18_388_431);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &19_999_998);
 
-    assert_eq!(repay_a, (18_388_429, 18_388_429));
+    assert_eq!(repay_a, (18_388_431, 18_388_431));
     assert_eq!(repay_b, (19_999_998, 19_999_998));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    assert_eq!(withdrawn_amount_a, (19_999_997, 0));
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    assert_eq!(withdrawn_amount_a, (19_999_998, 0));
     assert_eq!(withdrawn_amount_b, (0, 18_388_429));
-    assert_eq!(token_a.balance(&user_a), 19_999_997);
+    assert_eq!(token_a.balance(&user_a), 19_999_998);
     assert_eq!(token_b.balance(&user_b), 18_388_429);
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
-    let reclaim_amount_b = contract.reclaim(&user_b);
-    assert_eq!(reclaim_amount_a, 0);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
+    let reclaim_amount_b = contract.reclaim(&0, &user_b);
+    assert_eq!(reclaim_amount_a, 1);
     assert_eq!(reclaim_amount_b, 0);
-    assert_eq!(token_a.balance(&user_a), 19_999_997);
+    assert_eq!(token_a.balance(&user_a), 19_999_999);
     assert_eq!(token_b.balance(&user_b), 18_388_429);
 
-    let reclaim_col_a = contract.reclaim_col(&user_a);
-    let reclaim_col_b = contract.reclaim_col(&user_b);
+    let reclaim_col_a = contract.reclaim_col(&0, &user_a);
+    let reclaim_col_b = contract.reclaim_col(&0, &user_b);
     assert_eq!(reclaim_col_a, 4_000_000);
     assert_eq!(reclaim_col_b, 3_674_530);
 
-    assert_eq!(token_a.balance(&user_a), 23_999_997);
+    assert_eq!(token_a.balance(&user_a), 23_999_999);
     assert_eq!(token_b.balance(&user_b), 22_062_959);
 }
 
@@ -2805,54 +2953,52 @@ fn test_e2e_multiple_deposits_one_unused_position() {
     oracle_client.set_spot_rate(&spot_rate);
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &forward_rate,
-        &TIME_TO_MATURE,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
     );
-    contract.init_pos(&token_admin, &2, &2, &10_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_a, &token_a.address, &10_000_000, &2_000_000);
-    contract.deposit(&user_b, &token_b.address, &9_186_324, &1_837_265);
+    contract.init_pos(&0, &token_admin, &2, &2, &10_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &user_b, &token_b.address, &9_186_324, &1_837_265);
     SwapTest::add_time(&e, TIME_TO_EXEC);
 
-    let swapped_amount_a = contract.swap(&user_a);
-    let swapped_amount_b = contract.swap(&user_b);
+    let swapped_amount_a = contract.swap(&0, &user_a);
+    let swapped_amount_b = contract.swap(&0, &user_b);
     assert_eq!(swapped_amount_a, 9_186_324);
     assert_eq!(swapped_amount_b, 9_999_999);
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
     assert_eq!(reclaim_amount_a, 10_000_000);
 
-    let reclaim_col_a = contract.reclaim_col(&user_a);
-    assert_eq!(reclaim_col_a, 2_000_001);
+    let reclaim_col_a = contract.reclaim_col(&0, &user_a);
+    assert_eq!(reclaim_col_a, 2_000_000);
 
     SwapTest::add_time(&e, TIME_TO_MATURE);
 
     token_admin_client_b.mint(&user_a, &7_892);
-    let repay_a = contract.repay(&user_a, &token_b.address, &9_194_214);
-    let repay_b = contract.repay(&user_b, &token_a.address, &9_999_999);
+    let repay_a = contract.repay(&0, &user_a, &token_b.address, &9_194_216);
+    let repay_b = contract.repay(&0, &user_b, &token_a.address, &9_999_999);
 
-    assert_eq!(repay_a, (9_194_214, 9_194_214));
+    assert_eq!(repay_a, (9_194_216, 9_194_216));
     assert_eq!(repay_b, (9_999_999, 9_999_999));
 
     SwapTest::add_time(&e, TIME_TO_REPAY);
 
-    let withdrawn_amount_a = contract.withdraw(&user_a);
-    let withdrawn_amount_b = contract.withdraw(&user_b);
-    assert_eq!(withdrawn_amount_a, (9_999_998, 0));
+    let withdrawn_amount_a = contract.withdraw(&0, &user_a);
+    let withdrawn_amount_b = contract.withdraw(&0, &user_b);
+    assert_eq!(withdrawn_amount_a, (9_999_999, 0));
     assert_eq!(withdrawn_amount_b, (0, 9_194_214));
 
-    let reclaim_amount_a = contract.reclaim(&user_a);
-    let reclaim_amount_b = contract.reclaim(&user_b);
+    let reclaim_amount_a = contract.reclaim(&0, &user_a);
+    let reclaim_amount_b = contract.reclaim(&0, &user_b);
     assert_eq!(reclaim_amount_a, 0);
     assert_eq!(reclaim_amount_b, 0);
 
-    let reclaim_col_a = contract.reclaim_col(&user_a);
-    let reclaim_col_b = contract.reclaim_col(&user_b);
-    assert_eq!(reclaim_col_a, 1_999_999);
+    let reclaim_col_a = contract.reclaim_col(&0, &user_a);
+    let reclaim_col_b = contract.reclaim_col(&0, &user_b);
+    assert_eq!(reclaim_col_a, 2_000_000);
     assert_eq!(reclaim_col_b, 1_837_265);
 }
 
@@ -2865,20 +3011,16 @@ fn test_transfer_admin_error() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
 
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &SCALE,
-        &TIME_TO_MATURE,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
     );
-    contract.init_pos(&token_admin, &2, &2, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &2, &2, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
     contract.transfer_admin(&user_a, &user_a, &token_a.address, &100);
 }
 
@@ -2891,25 +3033,2792 @@ fn test_transfer_admin() {
         token_a,
         token_b,
         contract,
+        oracle_client,
         ..
     } = SwapTest::setup();
 
     contract.initialize(
         &token_admin,
-        &token_a.address,
-        &token_b.address,
-        &symbol_short!("USD"),
-        &symbol_short!("GBP"),
-        &SCALE,
-        &TIME_TO_MATURE,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
     );
-    contract.init_pos(&token_admin, &2, &2, &100);
-    contract.deposit(&user_a, &token_a.address, &100, &20);
+    contract.init_pos(&0, &token_admin, &2, &2, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
     assert_eq!(token_a.balance(&user_b), 0);
     contract.transfer_admin(&token_admin, &user_b, &token_a.address, &100);
     assert_eq!(token_a.balance(&user_b), 100);
 }
 
+#[test]
+fn test_set_oracles() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    assert_eq!(
+        contract.oracles(),
+        (
+            vec![&e, SwapTest::oracle_source(&e, &oracle_client.address)],
+            1
+        )
+    );
+
+    let new_oracle_client = SwapTest::create_oracle(&e, 90_000_000_000_000);
+    let sources = vec![&e, SwapTest::oracle_source(&e, &new_oracle_client.address)];
+    contract.set_oracles(&token_admin, &sources, &1);
+    assert_eq!(contract.oracles(), (sources, 1));
+
+    contract.set_spot(&0, &token_admin, &0);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.near_leg(&0);
+    assert_eq!(contract.spot_rate(&0), 90_000_000_000_000);
+}
+
+#[test]
+#[should_panic]
+fn test_set_oracles_unauthorized() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let new_oracle_client = SwapTest::create_oracle(&e, SCALE);
+    let sources = vec![&e, SwapTest::oracle_source(&e, &new_oracle_client.address)];
+    contract.set_oracles(&user_a, &sources, &1);
+}
+
+#[test]
+fn test_set_oracles_after_near_leg() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    let new_oracle_client = SwapTest::create_oracle(&e, SCALE);
+    let sources = vec![&e, SwapTest::oracle_source(&e, &new_oracle_client.address)];
+    let result = contract.try_set_oracles(&token_admin, &sources, &1);
+    assert_eq!(result, Err(Ok(Error::OracleLocked)));
+
+    let result = contract.try_propose_oracles(&user_a, &sources, &1);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = contract.try_propose_oracles(&token_admin, &sources, &2);
+    assert_eq!(result, Err(Ok(Error::InvalidQuorum)));
+    let unlock_time = contract.propose_oracles(&token_admin, &sources, &1);
+    assert_eq!(unlock_time, e.ledger().timestamp() + ORACLE_TIMELOCK);
+    let pending_oracles = PendingOracles {
+        sources: sources.clone(),
+        quorum: 1,
+        unlock_time,
+    };
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (Symbol::new(&e, "propose_oracles"), token_admin.clone()).into_val(&e),
+        pending_oracles.clone().into_val(&e),
+    );
+    assert_eq!(contract.pending_oracles(), Some(pending_oracles));
+
+    let result = contract.try_set_oracles(&token_admin, &sources, &1);
+    assert_eq!(result, Err(Ok(Error::TimeNotReached)));
+    SwapTest::add_time(&e, ORACLE_TIMELOCK);
+    let other_sources = vec![&e, SwapTest::oracle_source(&e, &oracle_client.address)];
+    let result = contract.try_set_oracles(&token_admin, &other_sources, &1);
+    assert_eq!(result, Err(Ok(Error::OracleLocked)));
+
+    contract.set_oracles(&token_admin, &sources, &1);
+    assert_eq!(contract.oracles(), (sources.clone(), 1));
+    assert_eq!(contract.pending_oracles(), None);
+    let result = contract.try_set_oracles(&token_admin, &sources, &1);
+    assert_eq!(result, Err(Ok(Error::OracleLocked)));
+}
+
+#[test]
+fn test_set_oracles_invalid_quorum() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let sources = vec![&e, SwapTest::oracle_source(&e, &oracle_client.address)];
+    let result = contract.try_set_oracles(&token_admin, &sources, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidQuorum)));
+    let result = contract.try_set_oracles(&token_admin, &sources, &2);
+    assert_eq!(result, Err(Ok(Error::InvalidQuorum)));
+}
+
+#[test]
+fn test_oracles_median() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let oracle_1 = SwapTest::create_oracle(&e, 120_000_000_000_000);
+    let oracle_2 = SwapTest::create_oracle(&e, 90_000_000_000_000);
+    let oracle_3 = SwapTest::create_oracle(&e, 110_000_000_000_000);
+    let mut sources = vec![
+        &e,
+        SwapTest::oracle_source(&e, &oracle_client.address),
+        SwapTest::oracle_source(&e, &oracle_1.address),
+        SwapTest::oracle_source(&e, &oracle_2.address),
+    ];
+    contract.set_oracles(&token_admin, &sources, &3);
+    contract.set_spot(&0, &token_admin, &0);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    assert_eq!(contract.near_leg(&0).price, SCALE);
+
+    sources.push_back(SwapTest::oracle_source(&e, &oracle_3.address));
+    contract.set_spot(&0, &token_admin, &0);
+    e.as_contract(&contract.address, || {
+        e.storage()
+            .instance()
+            .set(&DataKey::OracleSources, &sources);
+        e.storage()
+            .instance()
+            .set(&DataKey::OracleDecimals, &vec![&e, 14_u32, 14, 14, 14]);
+    });
+    assert_eq!(contract.near_leg(&0).price, 105_000_000_000_000);
+
+    // The midpoint of the two middle prices doesn't overflow
+    oracle_client.set_spot_rate(&i128::MAX);
+    oracle_1.set_spot_rate(&i128::MAX);
+    oracle_2.set_spot_rate(&(i128::MAX - 2));
+    oracle_3.set_spot_rate(&(i128::MAX - 2));
+    let price = e.as_contract(&contract.address, || crate::get_oracle_spot_price(&e));
+    assert_eq!(price.unwrap().price, i128::MAX - 1);
+}
+
+#[test]
+fn test_oracles_quorum() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let stale_oracle = SwapTest::create_oracle(&e, 50_000_000_000_000);
+    stale_oracle.set_timestamp(&e.ledger().timestamp());
+    let failing_oracle = OracleSource {
+        function: Symbol::new(&e, "not_a_function"),
+        ..SwapTest::oracle_source(&e, &oracle_client.address)
+    };
+    let valid_oracle = SwapTest::create_oracle(&e, 90_000_000_000_000);
+    let sources = vec![
+        &e,
+        failing_oracle,
+        SwapTest::oracle_source(&e, &stale_oracle.address),
+        SwapTest::oracle_source(&e, &valid_oracle.address),
+    ];
+    contract.set_oracles(&token_admin, &sources, &2);
+    contract.set_spot(&0, &token_admin, &0);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    assert_eq!(contract.try_near_leg(&0), Err(Ok(Error::StalePrice)));
+
+    contract.set_max_age(&token_admin, &TIME_TO_EXEC);
+    assert_eq!(contract.near_leg(&0).price, 70_000_000_000_000);
+}
+
+#[test]
+fn test_near_leg_stale_price() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_spot(&0, &token_admin, &0);
+    oracle_client.set_timestamp(&e.ledger().timestamp());
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    assert_eq!(contract.try_near_leg(&0), Err(Ok(Error::StalePrice)));
+
+    contract.set_max_age(&token_admin, &TIME_TO_EXEC);
+    assert_eq!(contract.max_age(), TIME_TO_EXEC);
+    assert_eq!(contract.near_leg(&0).price, SCALE);
+}
+
+#[test]
+fn test_near_leg_future_price() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_spot(&0, &token_admin, &0);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    oracle_client.set_timestamp(&(e.ledger().timestamp() + 1));
+    assert_eq!(contract.try_near_leg(&0), Err(Ok(Error::InvalidPrice)));
+    // Would overflow when adding the maximum age
+    oracle_client.set_timestamp(&u64::MAX);
+    assert_eq!(contract.try_near_leg(&0), Err(Ok(Error::InvalidPrice)));
+
+    oracle_client.set_timestamp(&e.ledger().timestamp());
+    assert_eq!(contract.near_leg(&0).price, SCALE);
+}
+
+#[test]
+#[should_panic]
+fn test_set_max_age_unauthorized() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_max_age(&user_a, &TIME_TO_EXEC);
+}
+
+#[test]
+#[should_panic]
+fn test_set_twap_window_unauthorized() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_twap_window(&user_a, &3600);
+}
+
+#[test]
+fn test_init_invalid_price() {
+    let SwapTest {
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    oracle_client.set_spot_rate(&0);
+    let result = contract.try_initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidPrice)));
+}
+
+#[test]
+fn test_liquidate_invalid_price() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&0);
+    assert_eq!(
+        contract.try_liquidate(&0, &user_a, &token_admin),
+        Err(Ok(Error::InvalidPrice))
+    );
+    assert_eq!(
+        contract.try_users(&0, &0, &10),
+        Err(Ok(Error::InvalidPrice))
+    );
+    assert_eq!(
+        contract.try_reclaim_col(&0, &user_a),
+        Err(Ok(Error::InvalidPrice))
+    );
+    assert_eq!(token_a.balance(&token_admin), 0);
+}
+
+#[test]
+fn test_withdraw_stale_price() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    oracle_client.set_timestamp(&e.ledger().timestamp());
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+
+    assert_eq!(
+        contract.try_withdraw(&0, &user_a),
+        Err(Ok(Error::StalePrice))
+    );
+    assert_eq!(
+        contract.try_liquidate(&0, &user_b, &token_admin),
+        Err(Ok(Error::StalePrice))
+    );
+    assert_eq!(token_a.balance(&user_a), 880);
+}
+
+#[test]
+fn test_init_price_unavailable() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        ..
+    } = SwapTest::setup();
+    let oracle_address = e.register_contract_wasm(None, oracle_mock::WASM);
+    let oracle_client = oracle_mock::Client::new(&e, &oracle_address);
+    let spot_rate = contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_address),
+    );
+    assert_eq!(spot_rate, 0);
+    assert_eq!(contract.spot_rate(&0), 0);
+
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    assert_eq!(contract.try_near_leg(&0), Err(Ok(Error::PriceUnavailable)));
+
+    oracle_client.set_spot_rate(&90_000_000_000_000);
+    contract.near_leg(&0);
+    assert_eq!(contract.spot_rate(&0), 90_000_000_000_000);
+}
+
+#[test]
+fn test_price_unavailable() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        ..
+    } = SwapTest::setup();
+    let oracle_address = e.register_contract_wasm(None, oracle_mock::WASM);
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_address),
+    );
+    contract.set_spot(&0, &token_admin, &SCALE);
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    assert_eq!(
+        contract.try_liquidate(&0, &user_a, &token_admin),
+        Err(Ok(Error::PriceUnavailable))
+    );
+    assert_eq!(
+        contract.try_reclaim_col(&0, &user_a),
+        Err(Ok(Error::PriceUnavailable))
+    );
+    assert_eq!(
+        contract.try_users(&0, &0, &10),
+        Err(Ok(Error::PriceUnavailable))
+    );
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    assert_eq!(
+        contract.try_withdraw(&0, &user_a),
+        Err(Ok(Error::PriceUnavailable))
+    );
+    assert_eq!(token_a.balance(&user_a), 880);
+}
+
+#[test]
+fn test_twap_liquidation() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_twap_window(&token_admin, &3600);
+    assert_eq!(contract.twap_window(), 3600);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&70_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+
+    SwapTest::add_time(&e, 1800);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+
+    SwapTest::add_time(&e, 1800);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 2);
+    assert_eq!(token_a.balance(&token_admin), 2);
+
+    // A weighted sum that doesn't fit in an i128 is an error
+    oracle_client.set_spot_rate(&(i128::MAX / 2));
+    contract.poke_price();
+    SwapTest::add_time(&e, 60);
+    let price = e.as_contract(&contract.address, || crate::get_liquidation_price(&e));
+    assert_eq!(price, Err(Error::ArithmeticOverflow));
+}
+
+#[test]
+fn test_twap_price_spike() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_twap_window(&token_admin, &3600);
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&50_000_000_000_000);
+    contract.poke_price();
+    SwapTest::add_time(&e, 60);
+    oracle_client.set_spot_rate(&SCALE);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+
+    let (users_a, _) = contract.users(&0, &0, &10);
+    assert_eq!(users_a.get(0).unwrap().min_collateral, 160);
+}
+
+#[test]
+fn test_poke_price_observations() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    for i in 1..31 {
+        e.budget().reset_default();
+        SwapTest::add_time(&e, 60);
+        oracle_client.set_spot_rate(&(SCALE + i));
+        let price_data = contract.poke_price();
+        assert_eq!(price_data.price, SCALE + i);
+    }
+    contract.poke_price();
+
+    let observations: Vec<PriceData> = e.as_contract(&contract.address, || {
+        e.storage()
+            .persistent()
+            .get(&DataKey::PriceObservations)
+            .unwrap()
+    });
+    assert_eq!(observations.len(), OBSERVATIONS_CAPACITY);
+    assert_eq!(observations.first().unwrap().price, SCALE + 7);
+    assert_eq!(observations.last().unwrap().price, SCALE + 30);
+}
+
+#[test]
+fn test_twap_window_observations() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    // 23 intervals of the maximum price age
+    let max_window = (OBSERVATIONS_CAPACITY as u64 - 1) * contract.max_age();
+    let result = contract.try_set_twap_window(&token_admin, &(max_window + 1));
+    assert_eq!(result, Err(Ok(Error::InvalidTwapWindow)));
+    contract.set_twap_window(&token_admin, &max_window);
+
+    // Poking every ledger for two hours keeps observations 157 seconds apart with a 1 hour window
+    contract.set_twap_window(&token_admin, &3600);
+    for _ in 0..1440 {
+        e.budget().reset_default();
+        SwapTest::add_ledgers(&e, 1);
+        contract.poke_price();
+    }
+
+    let observations: Vec<PriceData> = e.as_contract(&contract.address, || {
+        e.storage()
+            .persistent()
+            .get(&DataKey::PriceObservations)
+            .unwrap()
+    });
+    assert_eq!(observations.len(), OBSERVATIONS_CAPACITY);
+    for i in 1..observations.len() {
+        let elapsed =
+            observations.get(i).unwrap().timestamp - observations.get(i - 1).unwrap().timestamp;
+        assert!(elapsed >= 157);
+    }
+    assert!(observations.first().unwrap().timestamp <= e.ledger().timestamp() - 3600);
+}
+
+#[test]
+fn test_custom_risk_params() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let risk_params = RiskParams {
+        collateral_buffer: 30,
+        collateral_threshold: 150,
+        liquidation_reward: 5,
+        time_to_exec: 3600,
+        time_to_repay: 3600,
+        partial_liquidation: false,
+    };
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            risk_params: risk_params.clone(),
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    assert_eq!(contract.risk_params(), risk_params);
+    contract.init_pos(&0, &token_admin, &100, &100, &700);
+    let result = contract.try_deposit(&0, &user_a, &token_a.address, &700, &200);
+    assert_eq!(result, Err(Ok(Error::InsufficientCollateral)));
+    contract.deposit(&0, &user_a, &token_a.address, &700, &210);
+    contract.deposit(&0, &user_b, &token_b.address, &700, &210);
+    assert_eq!(contract.stage(&0), Stage::Deposit);
+    SwapTest::add_time(&e, 3600);
+    assert_eq!(contract.stage(&0), Stage::Swap);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    oracle_client.set_spot_rate(&70_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 10);
+    assert_eq!(token_a.balance(&token_admin), 10);
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(contract.stage(&0), Stage::Repay);
+    SwapTest::add_time(&e, 3600);
+    assert_eq!(contract.stage(&0), Stage::Withdraw);
+}
+
+#[test]
+fn test_init_invalid_risk_params() {
+    let SwapTest {
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let invalid_params = [
+        RiskParams {
+            collateral_threshold: 100,
+            ..SwapTest::risk_params()
+        },
+        RiskParams {
+            liquidation_reward: COLLATERAL_BUFFER,
+            ..SwapTest::risk_params()
+        },
+        RiskParams {
+            collateral_buffer: 0,
+            ..SwapTest::risk_params()
+        },
+        RiskParams {
+            time_to_exec: 0,
+            ..SwapTest::risk_params()
+        },
+    ];
+    for risk_params in invalid_params.iter() {
+        let result = contract.try_initialize(
+            &token_admin,
+            &InitParams {
+                risk_params: risk_params.clone(),
+                ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+            },
+        );
+        assert_eq!(result, Err(Ok(Error::InvalidRiskParams)));
+    }
+
+    // Valid on their own but the stage times of the series would overflow
+    let long_params = [
+        RiskParams {
+            time_to_exec: u64::MAX - TIME_TO_MATURE,
+            ..SwapTest::risk_params()
+        },
+        RiskParams {
+            time_to_repay: u64::MAX,
+            ..SwapTest::risk_params()
+        },
+    ];
+    for risk_params in long_params.iter() {
+        let result = contract.try_initialize(
+            &token_admin,
+            &InitParams {
+                risk_params: risk_params.clone(),
+                ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+            },
+        );
+        assert_eq!(result, Err(Ok(Error::InvalidDuration)));
+    }
+}
+
+#[test]
+fn test_init_invalid_params() {
+    let SwapTest {
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let invalid_params = [
+        (
+            token_a.address.clone(),
+            TOKEN_NAME,
+            SCALE,
+            TIME_TO_MATURE,
+            Error::SameTokens,
+        ),
+        (
+            token_b.address.clone(),
+            TOKEN_NAME,
+            0,
+            TIME_TO_MATURE,
+            Error::InvalidForwardRate,
+        ),
+        (
+            token_b.address.clone(),
+            TOKEN_NAME,
+            -SCALE,
+            TIME_TO_MATURE,
+            Error::InvalidForwardRate,
+        ),
+        (
+            token_b.address.clone(),
+            TOKEN_NAME,
+            SCALE,
+            0,
+            Error::InvalidDuration,
+        ),
+        (
+            token_b.address.clone(),
+            TOKEN_NAME,
+            SCALE,
+            u64::MAX,
+            Error::InvalidDuration,
+        ),
+        (
+            token_b.address.clone(),
+            symbol_short!("EURC"),
+            SCALE,
+            TIME_TO_MATURE,
+            Error::TokenNameMismatch,
+        ),
+        (
+            oracle_client.address.clone(),
+            TOKEN_NAME,
+            SCALE,
+            TIME_TO_MATURE,
+            Error::TokenNameMismatch,
+        ),
+    ];
+    for (token, name, forward_rate, duration, error) in invalid_params.iter() {
+        let result = contract.try_initialize(
+            &token_admin,
+            &InitParams {
+                name_token_b: name.clone(),
+                forward_rate: *forward_rate,
+                duration: *duration,
+                ..SwapTest::init_params(&token_a.address, token, &oracle_client.address)
+            },
+        );
+        assert_eq!(result, Err(Ok(*error)));
+    }
+
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let (token_a_data, token_b_data) = contract.tokens(&0);
+    assert_eq!(token_a_data.decimals, 7);
+    assert_eq!(token_b_data.decimals, 7);
+}
+
+#[test]
+fn test_partial_liquidation() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            risk_params: RiskParams {
+                partial_liquidation: true,
+                ..SwapTest::risk_params()
+            },
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &160);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &160);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&85_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 5);
+    assert_eq!(token_a.balance(&token_admin), 5);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("liq_part"), 0_u32, user_a.clone()).into_val(&e),
+        (
+            token_admin.clone(),
+            token_a.address.clone(),
+            5_i128,
+            97_i128,
+            544_i128,
+        )
+            .into_val(&e),
+    );
+
+    let balance = contract.balance(&0, &user_a);
+    assert!(!balance.is_liquidated);
+    assert_eq!(balance.liquidated_amount, 544);
+    assert_eq!(balance.liquidated_collateral, 102);
+    let (users_a, _) = contract.users(&0, &0, &10);
+    assert_eq!(users_a.get(0).unwrap().min_collateral, 58);
+    assert_eq!(contract.tokens(&0).0.liquidated_collateral, 97);
+
+    // The remaining position is healthy
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(
+        contract.repay(&0, &user_a, &token_b.address, &1000),
+        (256, 256)
+    );
+    contract.repay(&0, &user_b, &token_a.address, &1000);
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+
+    // Repaying what is left open after the partial liquidation clears the expiry
+    assert!(!contract.health(&0, &user_a).is_liquidatable);
+    assert!(!contract.health(&0, &user_b).is_liquidatable);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+    assert_eq!(contract.liquidate(&0, &user_b, &token_admin), 0);
+    assert!(!contract.balance(&0, &user_a).is_liquidated);
+    assert_eq!(contract.withdraw(&0, &user_a), (256, 0));
+    assert_eq!(contract.reclaim_col(&0, &user_a), 58);
+}
+
+#[test]
+fn test_partial_liquidation_rounding() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            risk_params: RiskParams {
+                partial_liquidation: true,
+                ..SwapTest::risk_params()
+            },
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &160);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &160);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    let spot_rate = 85_000_000_000_000;
+    e.as_contract(&contract.address, || {
+        // The loss of 141.18 and the minimum collateral of 176.47 round up as the user owes them
+        assert_eq!(
+            crate::get_position_loss(&e, 0, 800, spot_rate, true),
+            Ok((800, 142))
+        );
+        assert_eq!(
+            crate::get_position_min_collateral(&e, 0, 800, spot_rate, true),
+            Ok(178)
+        );
+        // So the first guess liquidates 515, which takes 92 + 5 and leaves 63 of collateral,
+        // one under the 64 the rest needs. One step of 29 more ends at 544.
+        assert_eq!(
+            crate::get_position_min_collateral(&e, 0, 800 - 515, spot_rate, true),
+            Ok(64)
+        );
+        assert_eq!(
+            crate::get_partial_liquidation(&e, 0, &user_a, spot_rate, true, 160, 178),
+            Ok(Some(PartialLiquidation {
+                amount: 544,
+                liquidated_collateral: 97,
+                reward_amount: 5,
+            }))
+        );
+    });
+}
+
+#[test]
+fn test_repeated_partial_liquidations() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            risk_params: RiskParams {
+                partial_liquidation: true,
+                ..SwapTest::risk_params()
+            },
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &160);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &160);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&85_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 5);
+    oracle_client.set_spot_rate(&84_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+    let balance = contract.balance(&0, &user_a);
+    assert_eq!(balance.liquidated_amount, 638);
+    assert_eq!(balance.liquidated_collateral, 120);
+    oracle_client.set_spot_rate(&83_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 0);
+
+    let (users_a, _) = contract.users(&0, &0, &10);
+    assert_eq!(
+        users_a.get(0).unwrap(),
+        UserLiqData {
+            address: user_a.clone(),
+            collateral: 160,
+            min_collateral: 22,
+            is_liquidated: false,
+            liquidated_amount: 720,
+            liquidated_collateral: 138,
+        }
+    );
+    assert_eq!(contract.tokens(&0).0.liquidated_collateral, 133);
+    assert_eq!(token_a.balance(&token_admin), 5);
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(
+        contract.repay(&0, &user_a, &token_b.address, &1000),
+        (80, 80)
+    );
+    assert_eq!(
+        contract.repay(&0, &user_b, &token_a.address, &1000),
+        (800, 800)
+    );
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    assert_eq!(contract.withdraw(&0, &user_a), (80, 0));
+    assert_eq!(contract.withdraw(&0, &user_b), (853, 80));
+    assert_eq!(contract.reclaim_col(&0, &user_a), 22);
+    assert_eq!(contract.reclaim_col(&0, &user_b), 160);
+    assert_eq!(token_a.balance(&contract.address), 0);
+    assert_eq!(token_b.balance(&contract.address), 0);
+}
+
+#[test]
+fn test_partial_liquidation_falls_back_to_full() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            risk_params: RiskParams {
+                partial_liquidation: true,
+                ..SwapTest::risk_params()
+            },
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    oracle_client.set_spot_rate(&70_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 2);
+    let balance = contract.balance(&0, &user_a);
+    assert!(balance.is_liquidated);
+    assert_eq!(balance.liquidated_amount, 0);
+}
+
+#[test]
+fn test_auction() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_b,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let auction_params = AuctionParams {
+        start_price: 110,
+        end_price: 90,
+        duration: 3600,
+    };
+    contract.set_auction(&token_admin, &auction_params);
+    assert_eq!(contract.auction(), Some(auction_params));
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    oracle_client.set_spot_rate(&70_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 2);
+
+    let keeper = Address::generate(&e);
+    token_admin_client_b.mint(&keeper, &1000);
+    assert_eq!(
+        contract.auction_price(&0, &token_a.address),
+        77_000_000_000_000
+    );
+    assert_eq!(
+        contract.buy_collateral(&0, &keeper, &token_a.address, &100, &77),
+        (100, 77)
+    );
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("buy_col"), 0_u32, keeper.clone()).into_val(&e),
+        (token_a.address.clone(), 100_i128, 77_i128).into_val(&e),
+    );
+
+    SwapTest::add_time(&e, 1800);
+    assert_eq!(
+        contract.auction_price(&0, &token_a.address),
+        70_000_000_000_000
+    );
+    let result = contract.try_buy_collateral(&0, &keeper, &token_a.address, &50, &30);
+    assert_eq!(result, Err(Ok(Error::AuctionPriceTooHigh)));
+    assert_eq!(
+        contract.buy_collateral(&0, &keeper, &token_a.address, &50, &35),
+        (50, 35)
+    );
+    SwapTest::add_time(&e, 3600);
+    assert_eq!(
+        contract.auction_price(&0, &token_a.address),
+        63_000_000_000_000
+    );
+    assert_eq!(token_a.balance(&keeper), 150);
+    assert_eq!(token_b.balance(&keeper), 888);
+
+    let (token_a_data, token_b_data) = contract.tokens(&0);
+    assert_eq!(token_a_data.liquidated_collateral, 198);
+    assert_eq!(token_a_data.auctioned_collateral, 150);
+    assert_eq!(token_b_data.auction_proceeds, 112);
+
+    // Proceeds are paid before the liquidated collateral
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    contract.repay(&0, &user_b, &token_a.address, &1000);
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    assert_eq!(contract.withdraw(&0, &user_b), (848, 112));
+    assert_eq!(contract.reclaim_col(&0, &user_b), 200);
+
+    let (token_a_data, token_b_data) = contract.tokens(&0);
+    assert_eq!(token_a_data.used_liq_collateral, 48);
+    assert_eq!(token_b_data.used_auction_proceeds, 112);
+    assert_eq!(token_a.balance(&contract.address), 0);
+    assert_eq!(token_b.balance(&contract.address), 0);
+}
+
+#[test]
+fn test_auction_disabled() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    assert_eq!(contract.auction(), None);
+    let result = contract.try_buy_collateral(&0, &user_a, &token_a.address, &100, &100);
+    assert_eq!(result, Err(Ok(Error::AuctionDisabled)));
+
+    contract.set_auction(
+        &token_admin,
+        &AuctionParams {
+            start_price: 110,
+            end_price: 90,
+            duration: 3600,
+        },
+    );
+    let result = contract.try_buy_collateral(&0, &user_a, &token_a.address, &100, &100);
+    assert_eq!(result, Err(Ok(Error::NoCollateralToAuction)));
+}
+
+#[test]
+fn test_set_auction_errors() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let auction_params = AuctionParams {
+        start_price: 110,
+        end_price: 90,
+        duration: 3600,
+    };
+    let result = contract.try_set_auction(&user_a, &auction_params);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    let invalid_params = [
+        AuctionParams {
+            start_price: 80,
+            ..auction_params.clone()
+        },
+        AuctionParams {
+            end_price: 0,
+            ..auction_params.clone()
+        },
+        AuctionParams {
+            duration: 0,
+            ..auction_params.clone()
+        },
+    ];
+    for auction_params in invalid_params.iter() {
+        let result = contract.try_set_auction(&token_admin, auction_params);
+        assert_eq!(result, Err(Ok(Error::InvalidAuctionParams)));
+    }
+}
+
+#[test]
+fn test_health() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+
+    // No authorization is needed
+    e.set_auths(&[]);
+    assert_eq!(
+        contract.health(&0, &user_a),
+        UserHealth {
+            collateral: 200,
+            min_collateral: 160,
+            health_factor: 125_000_000_000_000,
+            liquidation_price: 83_333_333_333_333,
+            is_liquidatable: false,
+        }
+    );
+    assert_eq!(
+        contract.health(&0, &user_b).liquidation_price,
+        120_000_000_000_000
+    );
+
+    oracle_client.set_spot_rate(&84_000_000_000_000);
+    let health = contract.health(&0, &user_a);
+    assert_eq!(health.min_collateral, 192);
+    assert!(!health.is_liquidatable);
+
+    oracle_client.set_spot_rate(&83_000_000_000_000);
+    let health = contract.health(&0, &user_a);
+    let (users_a, _) = contract.users(&0, &0, &10);
+    assert_eq!(
+        health.min_collateral,
+        users_a.get(0).unwrap().min_collateral
+    );
+    assert!(health.health_factor < SCALE);
+    assert!(health.is_liquidatable);
+    assert!(!contract.health(&0, &user_b).is_liquidatable);
+
+    e.mock_all_auths();
+    assert_eq!(contract.liquidate(&0, &user_a, &token_admin), 2);
+    assert!(!contract.health(&0, &user_a).is_liquidatable);
+}
+
+#[test]
+fn test_deposits_pagination() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_a, &1000);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &700, &1, &1);
+
+    // More positions than a single vector of deposits could hold
+    for _ in 0..700 {
+        e.budget().reset_default();
+        contract.deposit(&0, &user_a, &token_a.address, &1, &1);
+    }
+    e.budget().reset_default();
+    contract.deposit(&0, &user_b, &token_b.address, &700, &140);
+
+    assert_eq!(contract.deposits_count(&0), (700, 1));
+
+    let (deposits_a, deposits_b) = contract.deposits(&0, &0, &10);
+    assert_eq!(deposits_a.len(), 10);
+    assert_eq!(deposits_b.len(), 1);
+    assert_eq!(deposits_b.get(0).unwrap().address, user_b);
+
+    let (deposits_a, deposits_b) = contract.deposits(&0, &695, &10);
+    assert_eq!(deposits_a.len(), 5);
+    assert_eq!(deposits_b.len(), 0);
+    assert!(deposits_a.iter().all(|p| p.address == user_a && p.is_valid));
+
+    let (deposits_a, deposits_b) = contract.deposits(&0, &700, &10);
+    assert_eq!(deposits_a.len(), 0);
+    assert_eq!(deposits_b.len(), 0);
+
+    e.budget().reset_default();
+    let (deposits_a, _) = contract.deposits(&0, &10, &u32::MAX);
+    assert_eq!(deposits_a.len(), 690);
+}
+
+#[test]
+fn test_swap_cost_independent_of_other_positions() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let user_c = Address::generate(&e);
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_a, &1000);
+    token_admin_client_a.mint(&user_c, &2);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &600, &1, &1);
+
+    for _ in 0..599 {
+        e.budget().reset_default();
+        contract.deposit(&0, &user_a, &token_a.address, &1, &1);
+    }
+    e.budget().reset_default();
+    contract.deposit(&0, &user_c, &token_a.address, &1, &1);
+    contract.deposit(&0, &user_b, &token_b.address, &600, &120);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    e.budget().reset_default();
+    assert_eq!(contract.swap(&0, &user_a), 599);
+    let cost_many_positions = e.budget().cpu_instruction_cost();
+
+    // The last position is found without walking the ones before it
+    e.budget().reset_default();
+    assert_eq!(contract.swap(&0, &user_c), 1);
+    assert!(e.budget().cpu_instruction_cost() < cost_many_positions);
+
+    e.budget().reset_default();
+    assert_eq!(contract.swap(&0, &user_b), 600);
+}
+
+#[test]
+fn test_pro_rata_allocation() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let user_c = Address::generate(&e);
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_c, &1000);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            allocation_policy: AllocationPolicy::ProRata,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    assert_eq!(contract.allocation_policy(), AllocationPolicy::ProRata);
+    contract.init_pos(&0, &token_admin, &3, &3, &100);
+
+    // Token A is oversubscribed: 300 deposited against 200 of token B
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    // The late depositor gets the same fraction filled, rounding down
+    assert_eq!(contract.swap(&0, &user_a), 133);
+    assert_eq!(contract.swap(&0, &user_c), 66);
+    assert_eq!(contract.swap(&0, &user_b), 200);
+
+    assert_eq!(contract.reclaim(&0, &user_a), 67);
+    assert_eq!(contract.reclaim(&0, &user_c), 34);
+    assert_eq!(contract.reclaim(&0, &user_b), 0);
+}
+
+#[test]
+fn test_variable_size_deposits() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let user_c = Address::generate(&e);
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_c, &1000);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    let position_a = PositionData {
+        min_deposit: 50,
+        max_deposit: 400,
+        cap: 600,
+    };
+    let position_b = PositionData {
+        min_deposit: 50,
+        max_deposit: 500,
+        cap: 500,
+    };
+    contract.init_limits(&0, &token_admin, &position_a, &position_b);
+    assert_eq!(contract.limits(&0), (position_a, position_b));
+
+    let result = contract.try_deposit(&0, &user_a, &token_a.address, &40, &8);
+    assert_eq!(result, Err(Ok(Error::DepositAmountOutOfRange)));
+
+    contract.deposit(&0, &user_a, &token_a.address, &350, &70);
+    // Below the minimum is allowed once the user total reaches it
+    contract.deposit(&0, &user_a, &token_a.address, &20, &4);
+    let result = contract.try_deposit(&0, &user_a, &token_a.address, &50, &10);
+    assert_eq!(result, Err(Ok(Error::DepositAmountOutOfRange)));
+
+    contract.deposit(&0, &user_c, &token_a.address, &200, &40);
+    let result = contract.try_deposit(&0, &user_c, &token_a.address, &50, &10);
+    assert_eq!(result, Err(Ok(Error::DepositCapExceeded)));
+    contract.deposit(&0, &user_c, &token_a.address, &0, &10);
+
+    contract.deposit(&0, &user_b, &token_b.address, &450, &90);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    // Token A fills in deposit order: 370 for user_a and the remaining 80 for user_c
+    assert_eq!(contract.swap(&0, &user_a), 370);
+    assert_eq!(contract.swap(&0, &user_c), 80);
+    assert_eq!(contract.swap(&0, &user_b), 450);
+    assert_eq!(contract.reclaim(&0, &user_a), 0);
+    assert_eq!(contract.reclaim(&0, &user_c), 120);
+
+    let (deposits_a, _) = contract.deposits(&0, &0, &10);
+    assert_eq!(deposits_a.len(), 3);
+    assert_eq!(deposits_a.get(1).unwrap().amount, 20);
+    assert_eq!(deposits_a.get(2).unwrap().offset, 370);
+}
+
+#[test]
+fn test_init_invalid_limits() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let position = PositionData {
+        min_deposit: 50,
+        max_deposit: 400,
+        cap: 600,
+    };
+
+    let result = contract.try_init_limits(&0, &user_a, &position, &position);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    for invalid in [
+        PositionData {
+            min_deposit: 0,
+            ..position.clone()
+        },
+        PositionData {
+            min_deposit: 500,
+            ..position.clone()
+        },
+        PositionData {
+            cap: 300,
+            ..position.clone()
+        },
+    ] {
+        let result = contract.try_init_limits(&0, &token_admin, &position, &invalid);
+        assert_eq!(result, Err(Ok(Error::InvalidPositionData)));
+    }
+}
+
+#[test]
+fn test_cancel_deposit() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let user_c = Address::generate(&e);
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_c, &1000);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &3, &3, &100);
+
+    let result = contract.try_cancel_deposit(&0, &user_a);
+    assert_eq!(result, Err(Ok(Error::NothingToCancel)));
+
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    assert_eq!(token_a.balance(&user_a), 760);
+
+    assert_eq!(contract.cancel_deposit(&0, &user_a), (200, 40));
+    assert_eq!(token_a.balance(&user_a), 1000);
+    assert_eq!(token_a.balance(&contract.address), 120);
+    assert_eq!(contract.tokens(&0).0.deposited_amount, 100);
+    assert_eq!(contract.tokens(&0).0.collateral_amount, 20);
+
+    let (deposits_a, _) = contract.deposits(&0, &0, &10);
+    assert!(!deposits_a.get(0).unwrap().is_valid);
+    assert!(!deposits_a.get(1).unwrap().is_valid);
+    assert!(deposits_a.get(2).unwrap().is_valid);
+    let result = contract.try_cancel_deposit(&0, &user_a);
+    assert_eq!(result, Err(Ok(Error::NothingToCancel)));
+
+    // The cap is free again and the cancelled positions no longer take part in the swap
+    contract.deposit(&0, &user_a, &token_a.address, &200, &40);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    let result = contract.try_cancel_deposit(&0, &user_a);
+    assert_eq!(result, Err(Ok(Error::WrongStageToCancel)));
+
+    assert_eq!(contract.swap(&0, &user_c), 100);
+    assert_eq!(contract.swap(&0, &user_a), 0);
+    assert_eq!(contract.swap(&0, &user_b), 100);
+    assert_eq!(contract.reclaim(&0, &user_a), 200);
+}
+
+#[test]
+fn test_cancel_deposit_offsets() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let user_c = Address::generate(&e);
+    let user_d = Address::generate(&e);
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    token_admin_client_a.mint(&user_c, &1000);
+    token_admin_client_a.mint(&user_d, &1000);
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &3, &3, &100);
+
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    for _ in 0..5 {
+        contract.deposit(&0, &user_d, &token_a.address, &100, &20);
+        contract.cancel_deposit(&0, &user_d);
+    }
+    contract.deposit(&0, &user_d, &token_a.address, &100, &20);
+    // Cancelled after the later positions were created, they move up in the queue
+    contract.cancel_deposit(&0, &user_a);
+
+    let (deposits_a, _) = contract.deposits(&0, &0, &10);
+    assert_eq!(deposits_a.len(), 8);
+    assert_eq!(deposits_a.get(7).unwrap().offset, 700);
+
+    contract.deposit(&0, &user_b, &token_b.address, &150, &30);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    assert_eq!(contract.swap(&0, &user_c), 100);
+    assert_eq!(contract.swap(&0, &user_d), 50);
+    assert_eq!(contract.swap(&0, &user_b), 150);
+    assert_eq!(contract.reclaim(&0, &user_d), 50);
+}
+
+#[test]
+fn test_position_configuration() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let spot_rate: i128 = 100_000_000_000_000;
+    let forward_rate: i128 = 100_000_000_000_000;
+
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &2, &2, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &200, &40);
+
+    let result = contract.try_init_pos(&0, &token_admin, &1, &2, &100);
+    assert_eq!(result, Err(Ok(Error::CapBelowDeposited)));
+    let result = contract.try_init_pos(&0, &token_admin, &4, &2, &50);
+    assert_eq!(result, Err(Ok(Error::MinDepositLocked)));
+
+    contract.init_pos(&0, &token_admin, &4, &4, &100);
+    assert_eq!(contract.limits(&0).0.cap, 400);
+
+    let result = contract.try_increase_capacity(&0, &user_a, &token_a.address, &100);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = contract.try_increase_capacity(&0, &token_admin, &token_a.address, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidPositionData)));
+    assert_eq!(
+        contract.increase_capacity(&0, &token_admin, &token_a.address, &100),
+        500
+    );
+    assert_eq!(contract.limits(&0).0.cap, 500);
+    assert_eq!(contract.limits(&0).1.cap, 400);
+    let result = contract.try_increase_capacity(&0, &token_admin, &token_a.address, &i128::MAX);
+    assert_eq!(result, Err(Ok(Error::ArithmeticOverflow)));
+    let result = contract.try_init_pos(&0, &token_admin, &4, &4, &(i128::MAX / 2));
+    assert_eq!(result, Err(Ok(Error::ArithmeticOverflow)));
+
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    let result = contract.try_increase_capacity(&0, &token_admin, &token_a.address, &100);
+    assert_eq!(result, Err(Ok(Error::WrongStageToConfigure)));
+    let result = contract.try_init_pos(&0, &token_admin, &5, &5, &100);
+    assert_eq!(result, Err(Ok(Error::WrongStageToConfigure)));
+}
+
+#[test]
+fn test_mismatched_decimals() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    // Token A has 7 decimals, token B 18 and the oracle reports prices with 8
+    let token_b_address = e.register_contract(None, token_mock::TokenMock);
+    let token_admin_client_b = token_mock::TokenMockClient::new(&e, &token_b_address);
+    token_admin_client_b.initialize(&18, &String::from_str(&e, "bbb"));
+    let token_b = TokenClient::new(&e, &token_b_address);
+    let name_token_b = Symbol::new(&e, "bbb");
+    oracle_client.set_decimals(&8);
+    oracle_client.set_spot_rate(&200_000_000);
+
+    token_admin_client_a.mint(&user_a, &12_000_000);
+    token_admin_client_b.mint(&user_b, &2_400_000_000_000_000_000);
+
+    let spot_rate = contract.initialize(
+        &token_admin,
+        &InitParams {
+            name_token_b,
+            forward_rate: 2 * SCALE,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    assert_eq!(spot_rate, 2 * SCALE);
+
+    // One token A is worth two tokens B
+    let amount_deposit_token_b = contract.init_pos(&0, &token_admin, &1, &1, &10_000_000);
+    assert_eq!(amount_deposit_token_b, 2_000_000_000_000_000_000);
+    assert_eq!(
+        contract.try_deposit(&0, &user_a, &token_a.address, &10_000_000, &1_999_999),
+        Err(Ok(Error::InsufficientCollateral))
+    );
+    contract.deposit(&0, &user_a, &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(
+        &0,
+        &user_b,
+        &token_b.address,
+        &2_000_000_000_000_000_000,
+        &400_000_000_000_000_000,
+    );
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    assert_eq!(contract.swap(&0, &user_a), 2_000_000_000_000_000_000);
+    assert_eq!(contract.swap(&0, &user_b), 10_000_000);
+
+    let health = contract.health(&0, &user_a);
+    assert_eq!(health.min_collateral, 2_000_000);
+    assert_eq!(health.liquidation_price, 172_413_793_103_448);
+    assert!(!health.is_liquidatable);
+    let health = contract.health(&0, &user_b);
+    assert_eq!(health.min_collateral, 400_000_000_000_000_000);
+    assert_eq!(health.liquidation_price, 232_000_000_000_000);
+    assert!(!health.is_liquidatable);
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(
+        contract.repay(&0, &user_a, &token_b.address, &2_000_000_000_000_000_000),
+        (2_000_000_000_000_000_000, 2_000_000_000_000_000_000)
+    );
+    assert_eq!(
+        contract.repay(&0, &user_b, &token_a.address, &10_000_000),
+        (10_000_000, 10_000_000)
+    );
+
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    assert_eq!(contract.withdraw(&0, &user_a), (10_000_000, 0));
+    assert_eq!(
+        contract.withdraw(&0, &user_b),
+        (0, 2_000_000_000_000_000_000)
+    );
+    assert_eq!(contract.reclaim_col(&0, &user_a), 2_000_000);
+    assert_eq!(contract.reclaim_col(&0, &user_b), 400_000_000_000_000_000);
+    assert_eq!(token_a.balance(&user_a), 12_001_000);
+    assert_eq!(token_b.balance(&user_b), 2_400_000_000_000_000_000);
+}
+
+#[test]
+fn test_mul_div_rounding() {
+    assert_eq!(mul_div(7, 3, 2, Rounding::Down), Ok(10));
+    assert_eq!(mul_div(7, 3, 2, Rounding::Up), Ok(11));
+    assert_eq!(mul_div(7, 3, 2, Rounding::HalfEven), Ok(10));
+    assert_eq!(mul_div(9, 3, 2, Rounding::HalfEven), Ok(14));
+    assert_eq!(mul_div(8, 3, 5, Rounding::HalfEven), Ok(5));
+    assert_eq!(mul_div(6, 3, 2, Rounding::Up), Ok(9));
+    assert_eq!(mul_div(-7, 3, 2, Rounding::Down), Ok(-11));
+    assert_eq!(mul_div(-7, 3, 2, Rounding::Up), Ok(-10));
+    assert_eq!(mul_div(-7, 3, 2, Rounding::HalfEven), Ok(-10));
+    assert_eq!(calculate_percentage(801, 20, Rounding::Down), Ok(160));
+    assert_eq!(calculate_percentage(801, 20, Rounding::Up), Ok(161));
+}
+
+#[test]
+fn test_mul_div_overflow() {
+    // The product overflows an i128 but the result fits
+    let amount = 10_i128.pow(30);
+    assert_eq!(mul_div(amount, SCALE, SCALE, Rounding::Down), Ok(amount));
+    assert_eq!(
+        mul_div(i128::MAX, i128::MAX, i128::MAX, Rounding::Down),
+        Ok(i128::MAX)
+    );
+    assert_eq!(
+        mul_div(i128::MIN, 2, -2, Rounding::Down),
+        Err(Error::ArithmeticOverflow)
+    );
+    assert_eq!(mul_div(i128::MIN, 2, 2, Rounding::Down), Ok(i128::MIN));
+    assert_eq!(
+        mul_div(i128::MAX, i128::MAX - 1, i128::MAX, Rounding::Up),
+        Ok(i128::MAX - 1)
+    );
+    assert_eq!(
+        mul_div(i128::MAX, 3, 7, Rounding::Up),
+        Ok(72_917_650_054_486_813_599_294_558_735_378_902_455)
+    );
+    assert_eq!(
+        convert_b_to_a(amount, SCALE / 2, 18, 18, Rounding::Down),
+        Ok(2 * amount)
+    );
+    assert_eq!(
+        convert_a_to_b(10_i128.pow(20), 3 * SCALE, 7, 18, Rounding::Down),
+        Ok(3 * 10_i128.pow(31))
+    );
+
+    assert_eq!(
+        mul_div(i128::MAX, 2, 1, Rounding::Down),
+        Err(Error::ArithmeticOverflow)
+    );
+    assert_eq!(
+        mul_div(i128::MAX, i128::MAX, 1, Rounding::Down),
+        Err(Error::ArithmeticOverflow)
+    );
+    assert_eq!(mul_div(i128::MAX, 1, 1, Rounding::Up), Ok(i128::MAX));
+    assert_eq!(mul_div(1, 1, 0, Rounding::Down), Err(Error::DivisionByZero));
+    assert_eq!(
+        convert_b_to_a(amount, 0, 7, 7, Rounding::Down),
+        Err(Error::DivisionByZero)
+    );
+    assert_eq!(
+        convert_a_to_b(amount, SCALE, 0, 40, Rounding::Down),
+        Err(Error::ArithmeticOverflow)
+    );
+}
+
+#[test]
+fn test_liabilities_covered_after_settlement() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        token_admin_client_b,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let spot_rate: i128 = 91_863_245_477_859;
+    let forward_rate: i128 = 91_942_156_764_123;
+    let users_a = [Address::generate(&e), Address::generate(&e)];
+    let users_b = [Address::generate(&e), Address::generate(&e)];
+    let users: std::vec::Vec<&Address> = users_a.iter().chain(users_b.iter()).collect();
+    for user in users.iter() {
+        token_admin_client_a.mint(user, &20_000_000);
+        token_admin_client_b.mint(user, &20_000_000);
+    }
+
+    oracle_client.set_spot_rate(&spot_rate);
+    contract.initialize(
+        &token_admin,
+        &InitParams {
+            forward_rate,
+            ..SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address)
+        },
+    );
+    contract.init_pos(&0, &token_admin, &2, &2, &10_000_000);
+    contract.deposit(&0, &users_a[0], &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &users_a[1], &token_a.address, &10_000_000, &2_000_000);
+    contract.deposit(&0, &users_b[0], &token_b.address, &9_186_324, &1_837_265);
+    contract.deposit(&0, &users_b[1], &token_b.address, &9_186_324, &1_837_265);
+    assert_eq!(contract.open_users(&0), 4);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    for user in users.iter() {
+        contract.swap(&0, user);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    }
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    for user in users_a.iter() {
+        contract.repay(&0, user, &token_b.address, &20_000_000);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    }
+    for user in users_b.iter() {
+        contract.repay(&0, user, &token_a.address, &20_000_000);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    }
+
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    // Only the dust is swept while the users still have claims
+    let swept_a = contract.sweep_dust(&token_admin, &token_a.address);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    for user in users.iter() {
+        contract.withdraw(&0, user);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+        contract.reclaim(&0, user);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+        contract.reclaim_col(&0, user);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    }
+    assert_eq!(contract.open_users(&0), 0);
+
+    let dust_a = token_a.balance(&contract.address);
+    let dust_b = token_b.balance(&contract.address);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), dust_a);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_b.address), dust_b);
+    assert_eq!(token_a.balance(&contract.address), 0);
+    assert_eq!(token_b.balance(&contract.address), 0);
+    assert_eq!(contract.swept(&token_a.address), swept_a + dust_a);
+    assert_eq!(contract.swept(&token_b.address), dust_b);
+}
+
+#[test]
+fn test_liabilities_covered_after_liquidations() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        token_admin_client_b,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    let users = [&user_a, &user_b];
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_auction(
+        &token_admin,
+        &AuctionParams {
+            start_price: 110,
+            end_price: 90,
+            duration: 3600,
+        },
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+
+    // Token B gains value so the user that deposited token B gets liquidated
+    oracle_client.set_spot_rate(&130_000_000_000_000);
+    assert_eq!(contract.liquidate(&0, &user_b, &token_admin), 2);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+
+    let keeper = Address::generate(&e);
+    token_admin_client_a.mint(&keeper, &1000);
+    contract.buy_collateral(&0, &keeper, &token_b.address, &100, &1000);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    token_admin_client_b.mint(&user_a, &100);
+    contract.repay(&0, &user_a, &token_b.address, &1000);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    contract.withdraw(&0, &user_a);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    for user in users {
+        contract.reclaim(&0, user);
+        contract.reclaim_col(&0, user);
+        SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    }
+
+    // The liquidated collateral didn't cover the loss, the user that repaid keeps a claim
+    let balance = contract.balance(&0, &user_a);
+    assert_eq!(
+        (balance.returned_amount, balance.withdrawn_amount),
+        (800, 776)
+    );
+    assert_eq!(contract.open_users(&0), 1);
+    let balance_a = token_a.balance(&contract.address);
+    let balance_b = token_b.balance(&contract.address);
+    contract.sweep_dust(&token_admin, &token_a.address);
+    contract.sweep_dust(&token_admin, &token_b.address);
+    SwapTest::assert_liabilities_covered(&e, &contract, &users);
+    assert_eq!(
+        contract.swept(&token_a.address) + contract.swept(&token_b.address),
+        balance_a + balance_b
+            - token_a.balance(&contract.address)
+            - token_b.balance(&contract.address)
+    );
+}
+
+#[test]
+fn test_sweep_dust_errors() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+
+    let result = contract.try_sweep_dust(&user_a, &token_a.address);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    // The deposit and the collateral are owed to the user
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), 0);
+    assert_eq!(token_a.balance(&contract.address), 120);
+
+    // Tokens sent to the contract directly are not owed to anyone
+    token_admin_client_a.mint(&contract.address, &7);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), 7);
+    assert_eq!(token_a.balance(&contract.address), 120);
+
+    SwapTest::add_time(&e, TIME_TO_EXEC + TIME_TO_MATURE + TIME_TO_REPAY);
+    assert_eq!(contract.reclaim(&0, &user_a), 100);
+    assert_eq!(contract.reclaim_col(&0, &user_a), 20);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), 0);
+    let result = contract.try_sweep_dust(&token_admin, &user_a);
+    assert_eq!(result, Err(Ok(Error::InvalidToken)));
+}
+
+#[test]
+fn test_cancel_deposit_closes_user() {
+    let SwapTest {
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &0, &10);
+    assert_eq!(contract.open_users(&0), 1);
+    contract.cancel_deposit(&0, &user_a);
+    assert_eq!(contract.open_users(&0), 0);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    assert_eq!(contract.open_users(&0), 1);
+}
+
+#[test]
+fn test_legacy_user_migration() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    let balance = contract.balance(&0, &user_a);
+
+    // Rewrite the user with one entry per field, as stored before UserState
+    e.as_contract(&contract.address, || {
+        let storage = e.storage().persistent();
+        storage.remove(&DataKey::UserState(0, user_a.clone()));
+        storage.set(&DataKey::DepositedToken(user_a.clone()), &token_a.address);
+        storage.set(&DataKey::DepositedAmount(user_a.clone()), &100_i128);
+        storage.set(&DataKey::Collateral(user_a.clone()), &20_i128);
+        e.storage().instance().remove(&DataKey::OpenUsers(0));
+    });
+    assert_eq!(contract.balance(&0, &user_a), balance);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), 0);
+
+    // The first write counts the legacy user as open, its funds are kept from the dust
+    contract.deposit(&0, &user_a, &token_a.address, &0, &10);
+    let balance = contract.balance(&0, &user_a);
+    assert_eq!(balance.deposited_amount, 100);
+    assert_eq!(balance.collateral, 30);
+    assert_eq!(contract.open_users(&0), 1);
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(contract.sweep_dust(&token_admin, &token_a.address), 0);
+    assert_eq!(token_a.balance(&contract.address), 130);
+    e.as_contract(&contract.address, || {
+        let storage = e.storage().persistent();
+        assert!(storage.has(&DataKey::UserState(0, user_a.clone())));
+        assert!(!storage.has(&DataKey::DepositedToken(user_a.clone())));
+        assert!(!storage.has(&DataKey::DepositedAmount(user_a.clone())));
+        assert!(!storage.has(&DataKey::Collateral(user_a.clone())));
+    });
+}
+
+#[test]
+fn test_series() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    assert_eq!(contract.series_count(), 1);
+
+    let result = contract.try_add_series(&user_a, &SCALE, &TIME_TO_MATURE);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = contract.try_add_series(&token_admin, &0, &TIME_TO_MATURE);
+    assert_eq!(result, Err(Ok(Error::InvalidForwardRate)));
+    let result = contract.try_add_series(&token_admin, &SCALE, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidDuration)));
+    let result = contract.try_add_series(&token_admin, &SCALE, &u64::MAX);
+    assert_eq!(result, Err(Ok(Error::InvalidDuration)));
+
+    let series = contract.add_series(&token_admin, &(2 * SCALE), &(2 * TIME_TO_MATURE));
+    assert_eq!(series, 1);
+    assert_eq!(contract.series_count(), 2);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (Symbol::new(&e, "add_series"), token_admin.clone()).into_val(&e),
+        (1_u32, 2 * SCALE, 2 * TIME_TO_MATURE, SCALE).into_val(&e),
+    );
+    let (token_a_data, token_b_data) = contract.tokens(&1);
+    assert_eq!(token_a_data.address, token_a.address);
+    assert_eq!(token_b_data.address, token_b.address);
+
+    let result = contract.try_deposit(&5, &user_a, &token_a.address, &100, &20);
+    assert_eq!(result, Err(Ok(Error::InvalidSeries)));
+
+    // Each series keeps its own positions and deposits
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.init_pos(&1, &token_admin, &100, &200, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    contract.deposit(&1, &user_a, &token_a.address, &200, &40);
+    assert_eq!(contract.deposits_count(&0), (1, 1));
+    assert_eq!(contract.deposits_count(&1), (1, 0));
+    assert_eq!(contract.balance(&0, &user_a).deposited_amount, 100);
+    assert_eq!(contract.balance(&1, &user_a).deposited_amount, 200);
+
+    // The later maturity keeps series 1 in the Swap stage while series 0 is repaid
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    assert_eq!(contract.swap(&0, &user_a), 100);
+    assert_eq!(contract.swap(&0, &user_b), 100);
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(contract.stage(&0), Stage::Repay);
+    assert_eq!(contract.stage(&1), Stage::Swap);
+    assert_eq!(contract.balance(&1, &user_a).swapped_amount, 0);
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    assert_eq!(contract.stage(&1), Stage::Repay);
+}
+
+#[test]
+fn test_upgrade_timelock() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let wasm_hash = BytesN::from_array(&e, &[1; 32]);
+    let result = contract.try_propose_upgrade(&user_a, &wasm_hash);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = contract.try_upgrade(&token_admin, &wasm_hash);
+    assert_eq!(result, Err(Ok(Error::UpgradeNotProposed)));
+    let result = contract.try_cancel_upgrade(&token_admin);
+    assert_eq!(result, Err(Ok(Error::UpgradeNotProposed)));
+
+    let unlock_time = contract.propose_upgrade(&token_admin, &wasm_hash);
+    assert_eq!(unlock_time, e.ledger().timestamp() + UPGRADE_TIMELOCK);
+    let pending_upgrade = PendingUpgrade {
+        wasm_hash: wasm_hash.clone(),
+        unlock_time,
+    };
+    assert_eq!(contract.pending_upgrade(), Some(pending_upgrade.clone()));
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (Symbol::new(&e, "propose_upgrade"), token_admin.clone()).into_val(&e),
+        pending_upgrade.into_val(&e),
+    );
+
+    // Only the proposed WASM can be applied, and only once the timelock ends
+    let other_wasm_hash = BytesN::from_array(&e, &[2; 32]);
+    let result = contract.try_upgrade(&token_admin, &other_wasm_hash);
+    assert_eq!(result, Err(Ok(Error::UpgradeNotProposed)));
+    SwapTest::add_time(&e, UPGRADE_TIMELOCK - 1);
+    let result = contract.try_upgrade(&token_admin, &wasm_hash);
+    assert_eq!(result, Err(Ok(Error::TimeNotReached)));
+
+    // A new proposal restarts the timelock
+    let unlock_time = contract.propose_upgrade(&token_admin, &other_wasm_hash);
+    SwapTest::add_time(&e, 1);
+    let result = contract.try_upgrade(&token_admin, &other_wasm_hash);
+    assert_eq!(result, Err(Ok(Error::TimeNotReached)));
+    assert_eq!(
+        contract
+            .pending_upgrade()
+            .map(|upgrade| upgrade.unlock_time),
+        Some(unlock_time)
+    );
+
+    let result = contract.try_cancel_upgrade(&user_a);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    contract.cancel_upgrade(&token_admin);
+    assert_eq!(contract.pending_upgrade(), None);
+    SwapTest::add_time(&e, UPGRADE_TIMELOCK);
+    let result = contract.try_upgrade(&token_admin, &other_wasm_hash);
+    assert_eq!(result, Err(Ok(Error::UpgradeNotProposed)));
+}
+
+#[test]
+fn test_upgrade_wasm() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    let user_c = Address::generate(&e);
+    token_admin_client_a.mint(&user_c, &200);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.cancel_deposit(&0, &user_c);
+    contract.deposit(&0, &user_c, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    let balance_a = contract.balance(&0, &user_a);
+    let balance_c = contract.balance(&0, &user_c);
+
+    // The contract registered from this crate moves to the WASM built from it, which the same
+    // call then instantiates at a much higher cost
+    e.budget().reset_unlimited();
+    assert!(contract.pending_upgrade().is_none());
+    let native_cost = e.budget().cpu_instruction_cost();
+    let new_wasm_hash = e.deployer().upload_contract_wasm(SWAP_WASM);
+    contract.propose_upgrade(&token_admin, &new_wasm_hash);
+    SwapTest::add_time(&e, UPGRADE_TIMELOCK);
+    contract.upgrade(&token_admin, &new_wasm_hash);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("upgrade"), token_admin.clone()).into_val(&e),
+        new_wasm_hash.into_val(&e),
+    );
+
+    e.budget().reset_unlimited();
+    assert_eq!(contract.pending_upgrade(), None);
+    assert!(e.budget().cpu_instruction_cost() > 10 * native_cost);
+
+    // The new code keeps the stored data and the users go on from where they were
+    assert_eq!(contract.migrate(&token_admin), SCHEMA_VERSION);
+    assert_eq!(contract.balance(&0, &user_a), balance_a);
+    assert_eq!(contract.balance(&0, &user_c), balance_c);
+    assert_eq!(contract.open_users(&0), 3);
+    assert_eq!(contract.stage(&0), Stage::Swap);
+    assert_eq!(contract.swap(&0, &user_a), 100);
+    assert_eq!(contract.swap(&0, &user_c), 100);
+    assert_eq!(contract.swap(&0, &user_b), 200);
+}
+
+#[test]
+fn test_migrate() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    assert_eq!(contract.version(), SCHEMA_VERSION);
+    let result = contract.try_migrate(&user_a);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    assert_eq!(contract.migrate(&token_admin), SCHEMA_VERSION);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("migrate"), token_admin.clone()).into_val(&e),
+        (SCHEMA_VERSION, SCHEMA_VERSION).into_val(&e),
+    );
+
+    // Contracts initialized before the schema version was stored have no migration step
+    e.as_contract(&contract.address, || {
+        e.storage().instance().remove(&DataKey::SchemaVersion);
+    });
+    assert_eq!(contract.version(), 0);
+    let result = contract.try_migrate(&token_admin);
+    assert_eq!(result, Err(Ok(Error::InvalidSchemaVersion)));
+    assert_eq!(contract.version(), 0);
+
+    // Data of a newer code is left untouched
+    e.as_contract(&contract.address, || {
+        e.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &(SCHEMA_VERSION + 1));
+    });
+    let result = contract.try_migrate(&token_admin);
+    assert_eq!(result, Err(Ok(Error::InvalidSchemaVersion)));
+    assert_eq!(contract.version(), SCHEMA_VERSION + 1);
+}
+
+#[test]
+fn test_ttl() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        token_admin_client_a,
+        token_admin_client_b,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    let end_ledgers =
+        ((TIME_TO_EXEC + TIME_TO_MATURE + TIME_TO_REPAY + CLAIM_PERIOD) / LEDGER_TIME) as u32;
+    let users = vec![&e, user_a.clone(), user_b.clone()];
+    assert_eq!(contract.ttl(&0, &users), (end_ledgers, vec![&e, 0, 0]));
+
+    contract.init_pos(&0, &token_admin, &100, &100, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &100, &20);
+    let expected_ttl = end_ledgers;
+    assert_eq!(
+        contract.ttl(&0, &users),
+        (expected_ttl, vec![&e, expected_ttl, expected_ttl])
+    );
+
+    // The entries outlive the default TTL without anyone extending them by hand,
+    // and they aren't extended again until their TTL falls below the threshold
+    SwapTest::add_ledgers(&e, (TIME_TO_EXEC / LEDGER_TIME) as u32);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    SwapTest::add_ledgers(&e, (TIME_TO_MATURE / LEDGER_TIME) as u32);
+    token_admin_client_a.mint(&user_b, &100);
+    token_admin_client_b.mint(&user_a, &100);
+    contract.repay(&0, &user_a, &token_b.address, &100);
+    contract.repay(&0, &user_b, &token_a.address, &100);
+    let elapsed = ((TIME_TO_EXEC + TIME_TO_MATURE) / LEDGER_TIME) as u32;
+    let remaining_ttl = expected_ttl - elapsed;
+    assert_eq!(
+        contract.ttl(&0, &users),
+        (remaining_ttl, vec![&e, remaining_ttl, remaining_ttl])
+    );
+
+    SwapTest::add_ledgers(&e, (TIME_TO_REPAY / LEDGER_TIME) as u32);
+    assert_eq!(contract.withdraw(&0, &user_a), (100, 0));
+    assert_eq!(contract.withdraw(&0, &user_b), (0, 100));
+    assert_eq!(contract.reclaim_col(&0, &user_b), 20);
+
+    // Keepers extend the entries of users that don't call the contract
+    let claim_ttl = (CLAIM_PERIOD / LEDGER_TIME) as u32;
+    assert_eq!(
+        contract.ttl(&0, &users),
+        (claim_ttl, vec![&e, claim_ttl, claim_ttl])
+    );
+    SwapTest::add_ledgers(&e, claim_ttl - 1);
+    assert_eq!(contract.ttl(&0, &users), (1, vec![&e, 1, 1]));
+    contract.bump_ttl(&0, &vec![&e, user_a.clone(), user_b.clone()]);
+    let bumped_ttl = 2 * TTL_BUMP_INTERVAL;
+    assert_eq!(
+        contract.ttl(&0, &users),
+        (bumped_ttl, vec![&e, bumped_ttl, bumped_ttl])
+    );
+    // Bumping again before the TTL falls below the threshold leaves the entries alone
+    SwapTest::add_ledgers(&e, 1);
+    contract.bump_ttl(&0, &vec![&e, user_a.clone(), user_b.clone()]);
+    assert_eq!(
+        contract.ttl(&0, &users),
+        (bumped_ttl - 1, vec![&e, bumped_ttl - 1, bumped_ttl - 1])
+    );
+    let balance = contract.balance(&0, &user_a);
+    assert_eq!((balance.collateral, balance.withdrawn_collateral), (20, 0));
+}
+
+#[test]
+fn test_events_init() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("init"), token_admin.clone()).into_val(&e),
+        (
+            token_a.address.clone(),
+            token_b.address.clone(),
+            SCALE,
+            TIME_TO_MATURE,
+            SCALE,
+        )
+            .into_val(&e),
+    );
+
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("init_pos"), 0_u32, token_admin.clone()).into_val(&e),
+        (100_u64, 50_u64, 100_i128, 200_i128).into_val(&e),
+    );
+}
+
+#[test]
+fn test_events_deposit() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("deposit"), 0_u32, user_a.clone()).into_val(&e),
+        (
+            token_a.address.clone(),
+            100_i128,
+            20_i128,
+            100_i128,
+            20_i128,
+        )
+            .into_val(&e),
+    );
+
+    contract.deposit(&0, &user_a, &token_a.address, &100, &30);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("deposit"), 0_u32, user_a.clone()).into_val(&e),
+        (
+            token_a.address.clone(),
+            100_i128,
+            30_i128,
+            200_i128,
+            50_i128,
+        )
+            .into_val(&e),
+    );
+}
+
+#[test]
+fn test_events_near_leg_and_set_spot() {
+    let SwapTest {
+        e,
+        token_admin,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.set_spot(&0, &token_admin, &0);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("set_spot"), 0_u32, token_admin.clone()).into_val(&e),
+        0_i128.into_val(&e),
+    );
+
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    oracle_client.set_spot_rate(&90_000_000_000_000);
+    let price_data = contract.near_leg(&0);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("near_leg"), 0_u32).into_val(&e),
+        (90_000_000_000_000_i128, price_data.timestamp).into_val(&e),
+    );
+}
+
+#[test]
+fn test_events_swap_repay_withdraw() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &50, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.deposit(&0, &user_b, &token_b.address, &200, &40);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+
+    contract.swap(&0, &user_a);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("swap"), 0_u32, user_a.clone()).into_val(&e),
+        (token_b.address.clone(), 100_i128, 100_i128).into_val(&e),
+    );
+    contract.swap(&0, &user_b);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("swap"), 0_u32, user_b.clone()).into_val(&e),
+        (token_a.address.clone(), 100_i128, 100_i128).into_val(&e),
+    );
+
+    SwapTest::add_time(&e, TIME_TO_MATURE);
+    contract.repay(&0, &user_a, &token_b.address, &60);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("repay"), 0_u32, user_a.clone()).into_val(&e),
+        (token_b.address.clone(), 60_i128, 60_i128, 100_i128).into_val(&e),
+    );
+    contract.repay(&0, &user_a, &token_b.address, &60);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("repay"), 0_u32, user_a.clone()).into_val(&e),
+        (token_b.address.clone(), 40_i128, 100_i128, 100_i128).into_val(&e),
+    );
+    contract.repay(&0, &user_b, &token_a.address, &100);
+
+    SwapTest::add_time(&e, TIME_TO_REPAY);
+    contract.withdraw(&0, &user_a);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("withdraw"), 0_u32, user_a.clone()).into_val(&e),
+        (100_i128, 0_i128, 0_i128, 100_i128).into_val(&e),
+    );
+
+    contract.reclaim(&0, &user_b);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("reclaim"), 0_u32, user_b.clone()).into_val(&e),
+        (token_b.address.clone(), 100_i128, 100_i128).into_val(&e),
+    );
+
+    contract.reclaim_col(&0, &user_a);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (Symbol::new(&e, "reclaim_col"), 0_u32, user_a.clone()).into_val(&e),
+        (token_a.address.clone(), 20_i128, 20_i128).into_val(&e),
+    );
+}
+
+#[test]
+fn test_events_liquidate() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &100, &100, &800);
+    contract.deposit(&0, &user_a, &token_a.address, &800, &200);
+    contract.deposit(&0, &user_b, &token_b.address, &800, &200);
+    SwapTest::add_time(&e, TIME_TO_EXEC);
+    contract.swap(&0, &user_a);
+    contract.swap(&0, &user_b);
+    oracle_client.set_spot_rate(&70_000_000_000_000);
+    let events_before = e.events().all().len();
+    contract.liquidate(&0, &user_b, &token_admin);
+    assert_eq!(e.events().all().len(), events_before);
+
+    contract.liquidate(&0, &user_a, &token_admin);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (symbol_short!("liquidate"), 0_u32, user_a.clone()).into_val(&e),
+        (
+            token_admin.clone(),
+            token_a.address.clone(),
+            2_i128,
+            198_i128,
+        )
+            .into_val(&e),
+    );
+}
+
+#[test]
+fn test_events_transfer_admin() {
+    let SwapTest {
+        e,
+        token_admin,
+        user_a,
+        user_b,
+        token_a,
+        token_b,
+        contract,
+        oracle_client,
+        ..
+    } = SwapTest::setup();
+    contract.initialize(
+        &token_admin,
+        &SwapTest::init_params(&token_a.address, &token_b.address, &oracle_client.address),
+    );
+    contract.init_pos(&0, &token_admin, &2, &2, &100);
+    contract.deposit(&0, &user_a, &token_a.address, &100, &20);
+    contract.transfer_admin(&token_admin, &user_b, &token_a.address, &100);
+    SwapTest::assert_last_event(
+        &e,
+        &contract.address,
+        (Symbol::new(&e, "transfer_admin"), token_admin.clone()).into_val(&e),
+        (user_b.clone(), token_a.address.clone(), 100_i128).into_val(&e),
+    );
+}
+
 // #[test]
 // fn test_multiple_deposits_two_accounts() {
 //     let SwapTest {
@@ -2937,12 +5846,12 @@ fn test_transfer_admin() {
 //         &forward_rate,
 //         &TIME_TO_MATURE,
 //     );
-//     contract.init_pos(&token_admin, &100, &100, &1);
+//     contract.init_pos(&0, &token_admin, &100, &100, &1);
 //     // assert_eq!(e.budget().cpu_instruction_cost(),1249445);
 //     // assert_eq!(e.budget().memory_bytes_cost(),190878);
 //     for _ in 0..49 {
 //         e.budget().reset_default();
-//         contract.deposit(&user_a, &token_a.address, &1, &1);
+//         contract.deposit(&0, &user_a, &token_a.address, &1, &1);
 //         // let cpu = e.budget().cpu_instruction_cost();
 //         // let mem = e.budget().memory_bytes_cost();
 //         // std::println!("inst {}, cpu: {}, mem: {}", i, cpu, mem);
@@ -2950,7 +5859,7 @@ fn test_transfer_admin() {
 //     // assert_eq!(e.budget().cpu_instruction_cost(),96454169);
 //     // assert_eq!(e.budget().memory_bytes_cost(),20252758);
 
-//     // let (deposits_a, deposits_b) = contract.deposits();
+//     // let (deposits_a, deposits_b) = contract.deposits(&0);
 //     // assert_eq!(deposits_a.len(), 100);
 //     // assert_eq!(deposits_b.len(), 0);
 
@@ -2973,9 +5882,9 @@ fn test_transfer_admin() {
 //     // assert_eq!(res, Vec::new(&e));
 
 //     for _ in 0..49 {
-//         contract.deposit(&user_b, &token_b.address, &1, &1);
+//         contract.deposit(&0, &user_b, &token_b.address, &1, &1);
 //     }
-//     // contract.deposit(&user_b, &token_b.address, &1, &1);
+//     // contract.deposit(&0, &user_b, &token_b.address, &1, &1);
 // }
 
 // #[test]
@@ -3004,17 +5913,17 @@ fn test_transfer_admin() {
 //         &forward_rate,
 //         &TIME_TO_MATURE,
 //     );
-//     contract.init_pos(&token_admin, &10000, &10000, &1, &1);
+//     contract.init_pos(&0, &token_admin, &10000, &10000, &1, &1);
 //     for _ in 0..27 {
 //         let user = Address::generate(&e);
 //         token_admin_client_a.mint(&user, &2);
-//         contract.deposit(&user, &token_a.address, &1, &1);
+//         contract.deposit(&0, &user, &token_a.address, &1, &1);
 //     }
 
 //     for _ in 0..27 {
 //         let user = Address::generate(&e);
 //         token_admin_client_b.mint(&user, &2);
-//         contract.deposit(&user, &token_b.address, &1, &1);
+//         contract.deposit(&0, &user, &token_b.address, &1, &1);
 //     }
-//     // contract.deposit(&user_b, &token_b.address, &1, &1);
+//     // contract.deposit(&0, &user_b, &token_b.address, &1, &1);
 // }
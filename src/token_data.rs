@@ -3,12 +3,13 @@ use types::{storage::DataKey, token::Token};
 
 use crate::types;
 
-pub(crate) fn init_token_a(e: &Env, token: &Address, name: Symbol) {
+pub(crate) fn init_token_a(e: &Env, series: u32, token: &Address, name: Symbol, decimals: u32) {
     e.storage().instance().set(
-        &DataKey::TokenA,
+        &DataKey::TokenA(series),
         &Token {
             name,
             address: token.clone(),
+            decimals,
             deposited_amount: 0,
             swapped_amount: 0,
             returned_amount: 0,
@@ -18,16 +19,22 @@ pub(crate) fn init_token_a(e: &Env, token: &Address, name: Symbol) {
             withdrawn_collateral: 0,
             liquidated_collateral: 0,
             used_liq_collateral: 0,
+            auctioned_collateral: 0,
+            auction_proceeds: 0,
+            used_auction_proceeds: 0,
+            liquidation_rewards: 0,
+            auction_start: 0,
         },
     );
 }
 
-pub(crate) fn init_token_b(e: &Env, token: &Address, name: Symbol) {
+pub(crate) fn init_token_b(e: &Env, series: u32, token: &Address, name: Symbol, decimals: u32) {
     e.storage().instance().set(
-        &DataKey::TokenB,
+        &DataKey::TokenB(series),
         &Token {
             name,
             address: token.clone(),
+            decimals,
             deposited_amount: 0,
             swapped_amount: 0,
             returned_amount: 0,
@@ -37,96 +44,165 @@ pub(crate) fn init_token_b(e: &Env, token: &Address, name: Symbol) {
             withdrawn_collateral: 0,
             liquidated_collateral: 0,
             used_liq_collateral: 0,
+            auctioned_collateral: 0,
+            auction_proceeds: 0,
+            used_auction_proceeds: 0,
+            liquidation_rewards: 0,
+            auction_start: 0,
         },
     );
 }
 
-pub(crate) fn get_token_a(e: &Env) -> Token {
-    e.storage().instance().get(&DataKey::TokenA).unwrap()
+pub(crate) fn get_token_a(e: &Env, series: u32) -> Token {
+    e.storage()
+        .instance()
+        .get(&DataKey::TokenA(series))
+        .unwrap()
 }
 
-pub(crate) fn get_token_b(e: &Env) -> Token {
-    e.storage().instance().get(&DataKey::TokenB).unwrap()
+pub(crate) fn get_token_b(e: &Env, series: u32) -> Token {
+    e.storage()
+        .instance()
+        .get(&DataKey::TokenB(series))
+        .unwrap()
 }
 
-fn get_token(e: &Env, token: &Address) -> Token {
-    let token_a_address = get_token_a_address(&e);
-    let key = match token_a_address == token.clone() {
-        true => DataKey::TokenA,
-        false => DataKey::TokenB,
-    };
+fn get_token_key(e: &Env, series: u32, token: &Address) -> DataKey {
+    match get_token_a_address(e) == token.clone() {
+        true => DataKey::TokenA(series),
+        false => DataKey::TokenB(series),
+    }
+}
+
+fn get_token(e: &Env, series: u32, token: &Address) -> Token {
+    let key = get_token_key(e, series, token);
     e.storage().instance().get(&key).unwrap()
 }
 
-fn edit_token(e: &Env, token: &Address, data: Token) {
-    let token_a_address = get_token_a_address(&e);
-    let key = match token_a_address == token.clone() {
-        true => DataKey::TokenA,
-        false => DataKey::TokenB,
-    };
+fn edit_token(e: &Env, series: u32, token: &Address, data: Token) {
+    let key = get_token_key(e, series, token);
     e.storage().instance().set(&key, &data);
 }
 
+// Every series trades the same pair, series 0 always exists
 pub(crate) fn get_token_a_address(e: &Env) -> Address {
-    let token_data: Token = get_token_a(&e);
+    let token_data: Token = get_token_a(&e, 0);
     token_data.address
 }
 
 pub(crate) fn get_token_b_address(e: &Env) -> Address {
-    let token_data: Token = get_token_b(&e);
+    let token_data: Token = get_token_b(&e, 0);
     token_data.address
 }
 
-pub(crate) fn add_token_deposited_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_deposited_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.deposited_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_swapped_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_swapped_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.swapped_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_returned_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_returned_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.returned_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_withdrawn_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_withdrawn_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.withdrawn_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_reclaimed_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_reclaimed_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.reclaimed_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_collateral_amount(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_collateral_amount(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.collateral_amount += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_withdrawn_collateral(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_withdrawn_collateral(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.withdrawn_collateral += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_liquidated_collateral(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+// The auction of the liquidated collateral restarts with every liquidation
+pub(crate) fn add_token_liquidated_collateral(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.liquidated_collateral += amount;
-    edit_token(e, &token, token_data);
+    token_data.auction_start = e.ledger().timestamp();
+    edit_token(e, series, token, token_data);
 }
 
-pub(crate) fn add_token_used_liq_collateral(e: &Env, token: &Address, amount: i128) {
-    let mut token_data = get_token(&e, &token);
+pub(crate) fn add_token_used_liq_collateral(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
     token_data.used_liq_collateral += amount;
-    edit_token(e, &token, token_data);
+    edit_token(e, series, token, token_data);
+}
+
+pub(crate) fn add_token_auctioned_collateral(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
+    token_data.auctioned_collateral += amount;
+    edit_token(e, series, token, token_data);
+}
+
+pub(crate) fn add_token_auction_proceeds(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
+    token_data.auction_proceeds += amount;
+    edit_token(e, series, token, token_data);
+}
+
+pub(crate) fn add_token_used_auction_proceeds(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
+    token_data.used_auction_proceeds += amount;
+    edit_token(e, series, token, token_data);
+}
+
+pub(crate) fn add_token_liquidation_rewards(e: &Env, series: u32, token: &Address, amount: i128) {
+    let mut token_data = get_token(e, series, token);
+    token_data.liquidation_rewards += amount;
+    edit_token(e, series, token, token_data);
+}
+
+// Amount of the token the contract still owes to the users of the series: deposits not swapped
+// or reclaimed yet, repayments not withdrawn, collateral not withdrawn or paid as liquidation
+// reward, and the auction proceeds not withdrawn.
+pub(crate) fn get_owed_amount(e: &Env, series: u32, token: &Address) -> i128 {
+    let token_data = get_token(e, series, token);
+    token_data.deposited_amount - token_data.swapped_amount - token_data.reclaimed_amount
+        + token_data.returned_amount
+        - token_data.withdrawn_amount
+        + token_data.collateral_amount
+        - token_data.withdrawn_collateral
+        - token_data.liquidation_rewards
+        - token_data.used_liq_collateral
+        - token_data.auctioned_collateral
+        + token_data.auction_proceeds
+        - token_data.used_auction_proceeds
+}
+
+// Swept amounts are shared by every series
+pub(crate) fn get_swept_amount(e: &Env, token: &Address) -> i128 {
+    e.storage()
+        .instance()
+        .get(&DataKey::SweptAmount(token.clone()))
+        .unwrap_or(0)
+}
+
+pub(crate) fn add_swept_amount(e: &Env, token: &Address, amount: i128) {
+    let swept_amount = get_swept_amount(e, token) + amount;
+    e.storage()
+        .instance()
+        .set(&DataKey::SweptAmount(token.clone()), &swept_amount);
 }
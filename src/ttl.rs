@@ -0,0 +1,161 @@
+use core::cmp::{max, min};
+
+use soroban_sdk::{Address, Env};
+use types::storage::DataKey;
+
+use crate::constants::{CLAIM_PERIOD, LEDGER_TIME, TTL_BUMP_INTERVAL};
+use crate::position::{get_cancelled_nodes, get_user_positions};
+use crate::storage::{get_init_time, get_risk_params, get_series_count, get_time_to_mature};
+use crate::types;
+use crate::user::{save_user, try_load_user};
+
+// Entries are extended to live until the end of the claim period that follows the max time,
+// they are extended again once their TTL falls one bump interval below that target.
+fn get_end_time(e: &Env, series: u32) -> u64 {
+    let risk_params = get_risk_params(e);
+    get_init_time(e, series)
+        + risk_params.time_to_exec
+        + get_time_to_mature(e, series)
+        + risk_params.time_to_repay
+        + CLAIM_PERIOD
+}
+
+// Returns (threshold, target) in ledgers
+fn get_ttl_params_until(e: &Env, end_time: u64) -> (u32, u32) {
+    let ledgers = end_time.saturating_sub(e.ledger().timestamp()) / LEDGER_TIME;
+    let target = min(
+        max(ledgers, 2 * TTL_BUMP_INTERVAL as u64),
+        e.storage().max_ttl() as u64,
+    ) as u32;
+    (target.saturating_sub(TTL_BUMP_INTERVAL), target)
+}
+
+// TTL of the entries of a series
+pub(crate) fn get_ttl_params(e: &Env, series: u32) -> (u32, u32) {
+    get_ttl_params_until(e, get_end_time(e, series))
+}
+
+// The instance and the entries shared by every series live until the last series ends
+fn get_contract_ttl_params(e: &Env) -> (u32, u32) {
+    let end_time = (0..get_series_count(e))
+        .map(|series| get_end_time(e, series))
+        .max()
+        .unwrap_or(0);
+    get_ttl_params_until(e, end_time)
+}
+
+fn extend(e: &Env, key: &DataKey, threshold: u32, target: u32) {
+    if e.storage().persistent().has(key) {
+        e.storage().persistent().extend_ttl(key, threshold, target);
+    }
+}
+
+// Extends a persistent entry shared by every series when it is written
+pub(crate) fn extend_persistent(e: &Env, key: &DataKey) {
+    let (threshold, target) = get_contract_ttl_params(e);
+    extend(e, key, threshold, target);
+}
+
+// Extends a persistent entry of a series when it is written
+pub(crate) fn extend_series_persistent(e: &Env, series: u32, key: &DataKey) {
+    let (threshold, target) = get_ttl_params(e, series);
+    extend(e, key, threshold, target);
+}
+
+// Entries known to live until `live_until` are extended again once their TTL falls below
+// the threshold, they are then extended up to the target and live until the returned ledger
+pub(crate) fn get_bumped_live_until(
+    e: &Env,
+    live_until: u32,
+    threshold: u32,
+    target: u32,
+) -> Option<u32> {
+    let sequence = e.ledger().sequence();
+    match live_until < sequence + threshold {
+        true => Some(sequence + target),
+        false => None,
+    }
+}
+
+// Extends the instance and the persistent entries shared by every series
+pub(crate) fn bump_contract(e: &Env) {
+    let (threshold, target) = get_contract_ttl_params(e);
+    let Some(live_until) = get_bumped_live_until(e, get_live_until(e), threshold, target) else {
+        return;
+    };
+    e.storage().instance().extend_ttl(target, target);
+    extend(e, &DataKey::PriceObservations, target, target);
+    e.storage().instance().set(&DataKey::LiveUntil, &live_until);
+}
+
+// Extends the contract and the persistent entries shared by the users of the series
+pub(crate) fn bump_series(e: &Env, series: u32) {
+    bump_contract(e);
+    let (threshold, target) = get_ttl_params(e, series);
+    let series_live_until = get_series_live_until(e, series);
+    let Some(live_until) = get_bumped_live_until(e, series_live_until, threshold, target) else {
+        return;
+    };
+    for key in [
+        DataKey::PositionCountA(series),
+        DataKey::PositionCountB(series),
+        DataKey::CancelledAmountA(series),
+        DataKey::CancelledAmountB(series),
+    ] {
+        extend(e, &key, target, target);
+    }
+    e.storage()
+        .instance()
+        .set(&DataKey::SeriesLiveUntil(series), &live_until);
+}
+
+// Extends the entry of the user in the series, its deposit positions and the cancelled
+// amounts read to find their offset
+pub(crate) fn bump_user(e: &Env, series: u32, user: &Address) {
+    let Some(user_state) = try_load_user(e, series, user) else {
+        return;
+    };
+    let (threshold, target) = get_ttl_params(e, series);
+    if get_bumped_live_until(e, user_state.live_until, threshold, target).is_none() {
+        return;
+    }
+    for key in [
+        DataKey::UserPositionsA(series, user.clone()),
+        DataKey::UserPositionsB(series, user.clone()),
+    ] {
+        extend(e, &key, target, target);
+    }
+    for index in get_user_positions(e, series, user, true).iter() {
+        extend(e, &DataKey::UsedPositionA(series, index), target, target);
+        for node in get_cancelled_nodes(index) {
+            extend(e, &DataKey::CancelledTreeA(series, node), target, target);
+        }
+    }
+    for index in get_user_positions(e, series, user, false).iter() {
+        extend(e, &DataKey::UsedPositionB(series, index), target, target);
+        for node in get_cancelled_nodes(index) {
+            extend(e, &DataKey::CancelledTreeB(series, node), target, target);
+        }
+    }
+
+    // Saving extends the entry and records its TTL, legacy users are migrated on the way
+    save_user(e, series, user, &user_state);
+}
+
+// Ledger until which the instance and the entries shared by every series are at least alive
+pub(crate) fn get_live_until(e: &Env) -> u32 {
+    e.storage().instance().get(&DataKey::LiveUntil).unwrap_or(0)
+}
+
+// Ledger until which the entries shared by the users of the series are at least alive
+pub(crate) fn get_series_live_until(e: &Env, series: u32) -> u32 {
+    e.storage()
+        .instance()
+        .get(&DataKey::SeriesLiveUntil(series))
+        .unwrap_or(0)
+}
+
+// Ledger until which the entries of the user in the series are at least alive
+pub(crate) fn get_user_live_until(e: &Env, series: u32, user: &Address) -> u32 {
+    try_load_user(e, series, user).map_or(0, |user_state| user_state.live_until)
+}
@@ -0,0 +1,11 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum AllocationPolicy {
+    /// Positions are filled in deposit order
+    Fifo = 1,
+    /// Every depositor gets the same fraction of its deposit filled
+    ProRata = 2,
+}
@@ -0,0 +1,13 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Dutch auction of the liquidated collateral.
+pub struct AuctionParams {
+    /// Starting price as a percentage of the oracle price.
+    pub start_price: i128,
+    /// Final price as a percentage of the oracle price.
+    pub end_price: i128,
+    /// Seconds for the price to fall linearly from the start price to the final price.
+    pub duration: u64,
+}
@@ -17,6 +17,37 @@ pub enum Error {
     AlreadyRepaid = 11,
     Unauthorized = 12,
     ContractAlreadyInitialized = 13,
+    // No longer returned, kept so their codes keep their meaning
     AllPositionsAreUsed = 14,
     DepositAmountDoesntMatchPosition = 15,
+    OracleLocked = 16,
+    StalePrice = 17,
+    InvalidPrice = 18,
+    PriceUnavailable = 19,
+    InvalidQuorum = 20,
+    InvalidRiskParams = 21,
+    InvalidAuctionParams = 22,
+    AuctionDisabled = 23,
+    NoCollateralToAuction = 24,
+    AuctionPriceTooHigh = 25,
+    InvalidPositionData = 26,
+    WrongStageToCancel = 27,
+    NothingToCancel = 28,
+    CapBelowDeposited = 29,
+    MinDepositLocked = 30,
+    WrongStageToConfigure = 31,
+    SameTokens = 32,
+    InvalidForwardRate = 33,
+    InvalidDuration = 34,
+    TokenNameMismatch = 35,
+    InvalidTokenContract = 36,
+    InvalidOracle = 37,
+    ArithmeticOverflow = 38,
+    DivisionByZero = 39,
+    InvalidSeries = 40,
+    UpgradeNotProposed = 41,
+    InvalidSchemaVersion = 42,
+    InvalidTwapWindow = 43,
+    DepositCapExceeded = 44,
+    DepositAmountOutOfRange = 45,
 }
@@ -0,0 +1,27 @@
+use soroban_sdk::{contracttype, Address, Symbol};
+
+use crate::types::{allocation_policy::AllocationPolicy, risk_params::RiskParams};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Settings of the contract and of its series 0, set by `initialize`.
+pub struct InitParams {
+    /// Address of token A to swap.
+    pub token_a: Address,
+    /// Address of token B to swap.
+    pub token_b: Address,
+    /// Symbol of token A.
+    pub name_token_a: Symbol,
+    /// Symbol of token B.
+    pub name_token_b: Symbol,
+    /// Forward rate of series 0.
+    pub forward_rate: i128,
+    /// Duration of series 0 until it matures.
+    pub duration: u64,
+    /// Address of the oracle providing the spot rate.
+    pub oracle: Address,
+    /// Collateral percentages, liquidation reward and stage durations.
+    pub risk_params: RiskParams,
+    /// How deposits are filled when one side is oversubscribed.
+    pub allocation_policy: AllocationPolicy,
+}
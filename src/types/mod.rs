@@ -1,11 +1,21 @@
+pub mod allocation_policy;
 pub mod asset;
 pub mod asset_type;
+pub mod auction_params;
 pub mod error;
+pub mod init_params;
+pub mod oracle_source;
+pub mod partial_liquidation;
+pub mod pending_oracles;
+pub mod pending_upgrade;
 pub mod position;
 pub mod position_data;
 pub mod price_data;
+pub mod risk_params;
 pub mod stage;
 pub mod storage;
 pub mod token;
 pub mod user;
+pub mod user_health;
 pub mod user_liq_data;
+pub mod user_state;
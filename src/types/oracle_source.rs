@@ -0,0 +1,17 @@
+use soroban_sdk::{contracttype, Address, Symbol};
+
+use super::asset::Asset;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An oracle queried to get the spot rate.
+pub struct OracleSource {
+    /// The address of the oracle contract.
+    pub address: Address,
+    /// The function returning the price data.
+    pub function: Symbol,
+    /// The asset of token A in the oracle.
+    pub base_asset: Asset,
+    /// The asset of token B in the oracle.
+    pub quote_asset: Asset,
+}
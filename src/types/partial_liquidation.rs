@@ -0,0 +1,10 @@
+/// Part of a position closed by a partial liquidation
+#[derive(Clone, PartialEq, Debug)]
+pub struct PartialLiquidation {
+    /// Swapped amount closed
+    pub amount: i128,
+    /// Loss of the closed part, seized from the collateral
+    pub liquidated_collateral: i128,
+    /// Penalty on the used deposited amount of the closed part, paid to the liquidator
+    pub reward_amount: i128,
+}
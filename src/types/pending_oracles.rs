@@ -0,0 +1,15 @@
+use soroban_sdk::{contracttype, Vec};
+
+use crate::types::oracle_source::OracleSource;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Oracles proposed by the admin while they are locked, waiting for their timelock.
+pub struct PendingOracles {
+    /// Oracles to query.
+    pub sources: Vec<OracleSource>,
+    /// Minimum amount of valid prices.
+    pub quorum: u32,
+    /// Time from which the oracles can be set.
+    pub unlock_time: u64,
+}
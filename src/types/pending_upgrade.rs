@@ -0,0 +1,11 @@
+use soroban_sdk::{contracttype, BytesN};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Upgrade of the contract code proposed by the admin, waiting for its timelock.
+pub struct PendingUpgrade {
+    /// Hash of the installed WASM the contract is upgraded to.
+    pub wasm_hash: BytesN<32>,
+    /// Time from which the upgrade can be applied.
+    pub unlock_time: u64,
+}
@@ -5,4 +5,8 @@ use soroban_sdk::{contracttype, Address};
 pub struct Position {
     pub address: Address,
     pub is_valid: bool,
+    /// Amount deposited in the position
+    pub amount: i128,
+    /// Amount deposited in the token before this position, including cancelled positions
+    pub offset: i128,
 }
@@ -3,7 +3,10 @@ use soroban_sdk::contracttype;
 #[derive(Clone, PartialEq, Debug)]
 #[contracttype]
 pub struct PositionData {
-    pub limit: u64,
-    pub used: u64,
-    pub deposit_amount: i128,
+    /// Minimum amount a user must have deposited
+    pub min_deposit: i128,
+    /// Maximum amount a user can deposit
+    pub max_deposit: i128,
+    /// Maximum total amount that can be deposited in the token
+    pub cap: i128,
 }
@@ -0,0 +1,19 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Risk parameters of the contract, defined at initialization.
+pub struct RiskParams {
+    /// Minimum collateral as a percentage of the deposited amount.
+    pub collateral_buffer: i128,
+    /// Collateral required as a percentage of the mark to market loss.
+    pub collateral_threshold: i128,
+    /// Percentage of the collateral paid to the liquidator.
+    pub liquidation_reward: i128,
+    /// Seconds from initialization until the near leg can be executed.
+    pub time_to_exec: u64,
+    /// Seconds after maturity to repay the far leg.
+    pub time_to_repay: u64,
+    /// Liquidate only the part of the position needed to restore the minimum collateral.
+    pub partial_liquidation: bool,
+}
@@ -4,16 +4,36 @@ use soroban_sdk::{contracttype, Address};
 #[contracttype]
 pub enum DataKey {
     Admin,
-    TokenA,
-    TokenB,
-    SpotRate,
-    ForwardRate,
-    InitTime,
-    TimeToMature,
-    PositionA,
-    PositionB,
-    UsedPositionsA,
-    UsedPositionsB,
+    SeriesCount,
+    TokenA(u32),
+    TokenB(u32),
+    SpotRate(u32),
+    ForwardRate(u32),
+    InitTime(u32),
+    TimeToMature(u32),
+    RiskParams,
+    AllocationPolicy,
+    AuctionParams,
+    OracleSources,
+    OracleDecimals,
+    OracleQuorum,
+    MaxPriceAge,
+    TwapWindow,
+    PriceObservations,
+    PositionA(u32),
+    PositionB(u32),
+    PositionCountA(u32),
+    PositionCountB(u32),
+    UsedPositionA(u32, u32),
+    UsedPositionB(u32, u32),
+    UserPositionsA(u32, Address),
+    UserPositionsB(u32, Address),
+    CancelledAmountA(u32),
+    CancelledAmountB(u32),
+    CancelledTreeA(u32, u32),
+    CancelledTreeB(u32, u32),
+    UserState(u32, Address),
+    // Legacy per-user entries of series 0, only read to migrate the user to UserState
     DepositedToken(Address),
     DepositedAmount(Address),
     Collateral(Address),
@@ -23,4 +43,13 @@ pub enum DataKey {
     WithdrawnCollateralAmount(Address),
     ReclaimedAmount(Address),
     IsLiquidated(Address),
+    LiquidatedAmount(Address),
+    LiquidatedCollateral(Address),
+    OpenUsers(u32),
+    SweptAmount(Address),
+    LiveUntil,
+    SeriesLiveUntil(u32),
+    PendingUpgrade,
+    PendingOracles,
+    SchemaVersion,
 }
@@ -5,6 +5,7 @@ use soroban_sdk::{contracttype, Address, Symbol};
 pub struct Token {
     pub name: Symbol,
     pub address: Address,
+    pub decimals: u32,
     pub deposited_amount: i128,
     pub swapped_amount: i128,
     pub returned_amount: i128,
@@ -14,4 +15,9 @@ pub struct Token {
     pub withdrawn_collateral: i128,
     pub liquidated_collateral: i128,
     pub used_liq_collateral: i128,
+    pub auctioned_collateral: i128,
+    pub auction_proceeds: i128,
+    pub used_auction_proceeds: i128,
+    pub liquidation_rewards: i128,
+    pub auction_start: u64,
 }
@@ -12,4 +12,6 @@ pub struct User {
     pub collateral: i128,
     pub withdrawn_collateral: i128,
     pub is_liquidated: bool,
+    pub liquidated_amount: i128,
+    pub liquidated_collateral: i128,
 }
@@ -0,0 +1,11 @@
+use soroban_sdk::contracttype;
+
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct UserHealth {
+    pub collateral: i128,
+    pub min_collateral: i128,
+    pub health_factor: i128,
+    pub liquidation_price: i128,
+    pub is_liquidatable: bool,
+}
@@ -7,4 +7,6 @@ pub struct UserLiqData {
     pub collateral: i128,
     pub min_collateral: i128,
     pub is_liquidated: bool,
+    pub liquidated_amount: i128,
+    pub liquidated_collateral: i128,
 }
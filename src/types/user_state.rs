@@ -0,0 +1,24 @@
+use soroban_sdk::{contracttype, Address};
+
+/// State of a user that deposited, stored in a single persistent entry per address
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct UserState {
+    pub deposited_token: Address,
+    pub deposited_amount: i128,
+    pub swapped_amount: i128,
+    pub returned_amount: i128,
+    pub withdrawn_amount: i128,
+    pub reclaimed_amount: i128,
+    pub collateral: i128,
+    pub withdrawn_collateral: i128,
+    pub is_liquidated: bool,
+    /// Swapped amount closed by partial liquidations
+    pub liquidated_amount: i128,
+    /// Collateral seized by liquidations, including the liquidator reward
+    pub liquidated_collateral: i128,
+    /// Whether the user still has claims to settle
+    pub is_open: bool,
+    /// Ledger until which the entry is at least alive
+    pub live_until: u32,
+}
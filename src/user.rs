@@ -1,137 +1,173 @@
 use soroban_sdk::{Address, Env};
-use types::{storage::DataKey, user::User};
+use types::{error::Error, storage::DataKey, user::User, user_state::UserState};
 
+use crate::storage::{get_open_users, put_open_users};
+use crate::ttl::{get_bumped_live_until, get_ttl_params};
 use crate::types;
 
-fn get_and_add(e: &Env, key: DataKey, amount: i128) {
-    let count: i128 = e.storage().persistent().get(&key).unwrap_or_default();
-    e.storage().persistent().set(&key, &(count + amount));
-}
-
-pub(crate) fn get_deposited_token(e: &Env, to: &Address) -> Option<Address> {
-    let key = DataKey::DepositedToken(to.clone());
-    e.storage().persistent().get(&key)
-}
-
-pub(crate) fn get_deposited_amount(e: &Env, to: &Address) -> i128 {
-    let key = DataKey::DepositedAmount(to.clone());
-    e.storage().persistent().get(&key).unwrap_or_default()
-}
-
-pub(crate) fn get_collateral(e: &Env, to: &Address) -> i128 {
-    let key = DataKey::Collateral(to.clone());
-    e.storage().persistent().get(&key).unwrap_or_default()
-}
-
-pub(crate) fn get_withdrawn_collateral(e: &Env, to: &Address) -> i128 {
-    let key = DataKey::WithdrawnCollateralAmount(to.clone());
-    e.storage().persistent().get(&key).unwrap_or_default()
-}
-
-pub(crate) fn get_returned_amount(e: &Env, to: &Address) -> i128 {
-    e.storage()
-        .persistent()
-        .get(&DataKey::ReturnedAmount(to.clone()))
-        .unwrap_or_default()
-}
-
-pub(crate) fn get_swapped_amount(e: &Env, to: &Address) -> i128 {
-    e.storage()
-        .persistent()
-        .get(&DataKey::SwappedAmount(to.clone()))
-        .unwrap_or_default()
-}
-
-pub(crate) fn get_withdrawn_amount(e: &Env, to: &Address) -> i128 {
-    e.storage()
-        .persistent()
-        .get(&DataKey::WithdrawnAmount(to.clone()))
-        .unwrap_or_default()
-}
-
-pub(crate) fn get_reclaimed_amount(e: &Env, to: &Address) -> i128 {
-    e.storage()
-        .persistent()
-        .get(&DataKey::ReclaimedAmount(to.clone()))
-        .unwrap_or_default()
-}
-
-pub(crate) fn is_liquidated(e: &Env, to: &Address) -> bool {
-    e.storage()
-        .persistent()
-        .get(&DataKey::IsLiquidated(to.clone()))
-        .unwrap_or(false)
-}
-
-pub(crate) fn get_user_deposit(e: &Env, to: &Address) -> (i128, i128) {
-    let deposited_amount = get_deposited_amount(&e, &to);
-    let collateral = get_collateral(&e, &to);
-    (deposited_amount, collateral)
-}
+// Users that deposited before UserState existed keep one entry per field until their next write,
+// they all belong to series 0. They are open while their deposit or collateral is in the contract,
+// they are counted in the open users of the series once migrated
+fn load_legacy_user(e: &Env, series: u32, to: &Address) -> Option<UserState> {
+    if series != 0 {
+        return None;
+    }
 
-pub(crate) fn get_user_balance(e: &Env, to: &Address) -> User {
-    let deposited_token = get_deposited_token(&e, &to).unwrap();
-    User {
+    let storage = e.storage().persistent();
+    let deposited_token = storage.get(&DataKey::DepositedToken(to.clone()))?;
+    let get_amount = |key: DataKey| -> i128 { storage.get(&key).unwrap_or_default() };
+    let deposited_amount = get_amount(DataKey::DepositedAmount(to.clone()));
+    let collateral = get_amount(DataKey::Collateral(to.clone()));
+    Some(UserState {
         deposited_token,
-        deposited_amount: get_deposited_amount(&e, &to),
-        swapped_amount: get_swapped_amount(&e, &to),
-        returned_amount: get_returned_amount(&e, &to),
-        withdrawn_amount: get_withdrawn_amount(&e, &to),
-        reclaimed_amount: get_reclaimed_amount(&e, &to),
-        collateral: get_collateral(&e, &to),
-        withdrawn_collateral: get_withdrawn_collateral(&e, &to),
-        is_liquidated: is_liquidated(&e, &to),
+        deposited_amount,
+        swapped_amount: get_amount(DataKey::SwappedAmount(to.clone())),
+        returned_amount: get_amount(DataKey::ReturnedAmount(to.clone())),
+        withdrawn_amount: get_amount(DataKey::WithdrawnAmount(to.clone())),
+        reclaimed_amount: get_amount(DataKey::ReclaimedAmount(to.clone())),
+        collateral,
+        withdrawn_collateral: get_amount(DataKey::WithdrawnCollateralAmount(to.clone())),
+        is_liquidated: storage
+            .get(&DataKey::IsLiquidated(to.clone()))
+            .unwrap_or(false),
+        liquidated_amount: get_amount(DataKey::LiquidatedAmount(to.clone())),
+        liquidated_collateral: get_amount(DataKey::LiquidatedCollateral(to.clone())),
+        is_open: deposited_amount > 0 || collateral > 0,
+        live_until: 0,
+    })
+}
+
+fn remove_legacy_user(e: &Env, to: &Address) {
+    for key in [
+        DataKey::DepositedToken(to.clone()),
+        DataKey::DepositedAmount(to.clone()),
+        DataKey::SwappedAmount(to.clone()),
+        DataKey::ReturnedAmount(to.clone()),
+        DataKey::WithdrawnAmount(to.clone()),
+        DataKey::ReclaimedAmount(to.clone()),
+        DataKey::Collateral(to.clone()),
+        DataKey::WithdrawnCollateralAmount(to.clone()),
+        DataKey::IsLiquidated(to.clone()),
+        DataKey::LiquidatedAmount(to.clone()),
+        DataKey::LiquidatedCollateral(to.clone()),
+    ] {
+        e.storage().persistent().remove(&key);
     }
 }
 
-pub(crate) fn put_deposited_token(e: &Env, to: &Address, token: &Address) {
+// Returns None if the address never deposited
+pub(crate) fn try_load_user(e: &Env, series: u32, to: &Address) -> Option<UserState> {
     e.storage()
         .persistent()
-        .set(&DataKey::DepositedToken(to.clone()), &token);
+        .get(&DataKey::UserState(series, to.clone()))
+        .or_else(|| load_legacy_user(e, series, to))
+}
+
+// Field of the user, its default value if the address never deposited
+fn get_user_field<T: Default>(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    field: impl FnOnce(UserState) -> T,
+) -> T {
+    try_load_user(e, series, to).map(field).unwrap_or_default()
+}
+
+// Writes the user and extends its entry when it is due, the legacy entries are dropped
+// on the first write
+pub(crate) fn save_user(e: &Env, series: u32, to: &Address, user: &UserState) {
+    let key = DataKey::UserState(series, to.clone());
+    if series == 0 && !e.storage().persistent().has(&key) {
+        if let Some(legacy_user) = load_legacy_user(e, series, to) {
+            if legacy_user.is_open {
+                put_open_users(e, series, get_open_users(e, series) + 1);
+            }
+            remove_legacy_user(e, to);
+        }
+    }
+
+    let (threshold, target) = get_ttl_params(e, series);
+    let bumped_live_until = get_bumped_live_until(e, user.live_until, threshold, target);
+    let user = UserState {
+        live_until: bumped_live_until.unwrap_or(user.live_until),
+        ..user.clone()
+    };
+    e.storage().persistent().set(&key, &user);
+    if bumped_live_until.is_some() {
+        e.storage().persistent().extend_ttl(&key, target, target);
+    }
 }
 
-pub(crate) fn put_deposited_amount(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::DepositedAmount(to.clone());
-    get_and_add(e, key, amount);
+// Applies the changes of an entry point to the user and writes it once, nothing is written
+// if they fail. Only for users that deposited
+pub(crate) fn update_user<T>(
+    e: &Env,
+    series: u32,
+    to: &Address,
+    f: impl FnOnce(&mut UserState) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut user = try_load_user(e, series, to).unwrap();
+    let result = f(&mut user)?;
+    save_user(e, series, to, &user);
+    Ok(result)
 }
 
-pub(crate) fn put_swapped_amount(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::SwappedAmount(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn get_deposited_token(e: &Env, series: u32, to: &Address) -> Option<Address> {
+    try_load_user(e, series, to).map(|user| user.deposited_token)
 }
 
-pub(crate) fn put_collateral(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::Collateral(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn get_deposited_amount(e: &Env, series: u32, to: &Address) -> i128 {
+    get_user_field(e, series, to, |user| user.deposited_amount)
 }
 
-pub(crate) fn put_withdrawn_collateral(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::WithdrawnCollateralAmount(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn get_collateral(e: &Env, series: u32, to: &Address) -> i128 {
+    get_user_field(e, series, to, |user| user.collateral)
 }
 
-pub(crate) fn put_withdrawn_amount(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::WithdrawnAmount(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn get_returned_amount(e: &Env, series: u32, to: &Address) -> i128 {
+    get_user_field(e, series, to, |user| user.returned_amount)
 }
 
-pub(crate) fn put_returned_amount(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::ReturnedAmount(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn get_withdrawn_amount(e: &Env, series: u32, to: &Address) -> i128 {
+    get_user_field(e, series, to, |user| user.withdrawn_amount)
 }
 
-pub(crate) fn put_reclaimed_amount(e: &Env, to: &Address, amount: i128) {
-    let key = DataKey::ReclaimedAmount(to.clone());
-    get_and_add(e, key, amount);
+pub(crate) fn is_liquidated(e: &Env, series: u32, to: &Address) -> bool {
+    get_user_field(e, series, to, |user| user.is_liquidated)
 }
 
-pub(crate) fn put_is_liquidated(e: &Env, to: &Address, val: bool) {
-    let key = DataKey::IsLiquidated(to.clone());
-    e.storage().persistent().set(&key, &val);
+pub(crate) fn get_user_balance(e: &Env, series: u32, to: &Address) -> User {
+    let user = try_load_user(e, series, to).unwrap();
+    User {
+        deposited_token: user.deposited_token,
+        deposited_amount: user.deposited_amount,
+        swapped_amount: user.swapped_amount,
+        returned_amount: user.returned_amount,
+        withdrawn_amount: user.withdrawn_amount,
+        reclaimed_amount: user.reclaimed_amount,
+        collateral: user.collateral,
+        withdrawn_collateral: user.withdrawn_collateral,
+        is_liquidated: user.is_liquidated,
+        liquidated_amount: user.liquidated_amount,
+        liquidated_collateral: user.liquidated_collateral,
+    }
 }
 
-pub(crate) fn has_not_repaid(e: &Env, to: &Address) -> bool {
-    let swapped_amount = get_swapped_amount(&e, &to);
-    let returned_amount = get_returned_amount(&e, &to);
-    swapped_amount >= returned_amount
+// State of a user before its first deposit
+pub(crate) fn new_user(token: &Address) -> UserState {
+    UserState {
+        deposited_token: token.clone(),
+        deposited_amount: 0,
+        swapped_amount: 0,
+        returned_amount: 0,
+        withdrawn_amount: 0,
+        reclaimed_amount: 0,
+        collateral: 0,
+        withdrawn_collateral: 0,
+        is_liquidated: false,
+        liquidated_amount: 0,
+        liquidated_collateral: 0,
+        is_open: false,
+        live_until: 0,
+    }
 }